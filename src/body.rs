@@ -0,0 +1,91 @@
+use crate::{Inertia, MotionVec6, TransformationMatrix, TranslationVector};
+
+/// Rigid body of a kinematic tree, with its center of mass in body coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Body {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub center_of_mass: TranslationVector,
+    pub inertia: Inertia,
+}
+
+/// Kinematic state of a body, as produced by a forward kinematics pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyKinematics {
+    /// world to body coordinate transform
+    pub pose: TransformationMatrix,
+    /// spatial velocity in body coordinates
+    pub velocity: MotionVec6,
+    /// spatial acceleration in body coordinates when qdd = 0
+    pub bias_acceleration: MotionVec6,
+    /// body Jacobian in body coordinates, one column per generalized velocity
+    pub jacobian: Vec<MotionVec6>,
+}
+
+impl Body {
+    pub fn new(
+        name: &str,
+        parent: Option<usize>,
+        center_of_mass: TranslationVector,
+        inertia: Inertia,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            parent,
+            center_of_mass,
+            inertia,
+        }
+    }
+}
+
+impl BodyKinematics {
+    pub fn new(pose: TransformationMatrix, dofs: usize) -> Self {
+        Self {
+            pose,
+            velocity: MotionVec6::new(),
+            bias_acceleration: MotionVec6::new(),
+            jacobian: vec![MotionVec6::new(); dofs],
+        }
+    }
+
+    /// World coordinates of a point given in body coordinates
+    pub fn point_position(&self, point: TranslationVector) -> TranslationVector {
+        self.pose.inverse_transform().transform_point(point)
+    }
+
+    /// World velocity of a body-fixed point
+    pub fn point_velocity(&self, point: TranslationVector) -> TranslationVector {
+        !self.pose.to_rotation() * linear_at(self.velocity, point)
+    }
+
+    /// World acceleration of a body-fixed point when qdd = 0
+    pub fn point_bias_acceleration(&self, point: TranslationVector) -> TranslationVector {
+        let w = TranslationVector::from_array(self.velocity.rotational_motion());
+        let v = linear_at(self.velocity, point);
+        !self.pose.to_rotation() * (linear_at(self.bias_acceleration, point) + w.cross(v))
+    }
+
+    /// World-frame linear Jacobian of a body-fixed point, one column per generalized velocity
+    pub fn point_jacobian(&self, point: TranslationVector) -> Vec<TranslationVector> {
+        let rotation = !self.pose.to_rotation();
+        self.jacobian
+            .iter()
+            .map(|column| rotation * linear_at(*column, point))
+            .collect()
+    }
+
+    /// Body Jacobian columns expressed in world coordinates
+    pub fn world_jacobian(&self) -> Vec<MotionVec6> {
+        let to_world = self.pose.inverse_transform();
+        self.jacobian
+            .iter()
+            .map(|column| *column >> to_world)
+            .collect()
+    }
+}
+
+// linear part of a spatial motion vector shifted to a body-fixed point
+fn linear_at(motion: MotionVec6, point: TranslationVector) -> TranslationVector {
+    let w = TranslationVector::from_array(motion.rotational_motion());
+    TranslationVector::from_array(motion.translational_motion()) + w.cross(point)
+}
@@ -0,0 +1,251 @@
+use std::collections::BTreeSet;
+
+use crate::body::{Body, BodyKinematics};
+use crate::geometry::{Collider, Shape};
+use crate::{TransformationMatrix, TranslationVector};
+
+/// World axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: TranslationVector,
+    pub max: TranslationVector,
+}
+
+/// Sweep-and-prune broad phase over the colliders of a model
+///
+/// Bodies joined to their parent overlap at the joint, so pairs of colliders on adjacent bodies
+/// are skipped unless the pair has been included explicitly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BroadPhase {
+    margin: f64,
+    exclude_adjacent: bool,
+    excluded: BTreeSet<(Option<usize>, Option<usize>)>,
+    included: BTreeSet<(Option<usize>, Option<usize>)>,
+    boxes: Vec<Aabb>,
+    // collider indices sorted by the lower bound along `axis`, kept between updates
+    order: Vec<usize>,
+    axis: usize,
+}
+
+impl Aabb {
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        let (a_min, a_max) = (self.min.to_array(), self.max.to_array());
+        let (b_min, b_max) = (other.min.to_array(), other.max.to_array());
+        (0..3).all(|i| a_min[i] <= b_max[i] && b_min[i] <= a_max[i])
+    }
+
+    pub fn inflate(&self, margin: f64) -> Self {
+        let margin = TranslationVector::from_array([margin; 3]);
+        Self {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+}
+
+/// Bounding box of a shape given its world to shape transform
+pub fn bounding_box(shape: &Shape, pose: TransformationMatrix) -> Aabb {
+    if let Shape::HalfSpace = shape {
+        return Aabb {
+            min: TranslationVector::from_array([f64::NEG_INFINITY; 3]),
+            max: TranslationVector::from_array([f64::INFINITY; 3]),
+        };
+    }
+    let rotation = pose.to_rotation();
+    let origin = pose.to_translation();
+    let mut min = [0.0; 3];
+    let mut max = [0.0; 3];
+    for i in 0..3 {
+        let mut axis = [0.0; 3];
+        axis[i] = 1.0;
+        let axis = TranslationVector::from_array(axis);
+        let upper = !rotation * shape.support(rotation * axis);
+        let lower = !rotation * shape.support(rotation * -axis);
+        max[i] = upper.to_array()[i] + origin.to_array()[i];
+        min[i] = lower.to_array()[i] + origin.to_array()[i];
+    }
+    Aabb {
+        min: TranslationVector::from_array(min),
+        max: TranslationVector::from_array(max),
+    }
+}
+
+impl BroadPhase {
+    pub fn new() -> Self {
+        Self {
+            margin: 0.0,
+            exclude_adjacent: true,
+            excluded: BTreeSet::new(),
+            included: BTreeSet::new(),
+            boxes: Vec::new(),
+            order: Vec::new(),
+            axis: 0,
+        }
+    }
+
+    /// Grow every box by `margin` so pairs are reported slightly before they touch
+    pub fn margin(mut self, margin: f64) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn exclude_adjacent(mut self, exclude_adjacent: bool) -> Self {
+        self.exclude_adjacent = exclude_adjacent;
+        self
+    }
+
+    /// Never report pairs between these two bodies, `None` being the world
+    pub fn exclude(&mut self, body_a: Option<usize>, body_b: Option<usize>) {
+        let pair = ordered(body_a, body_b);
+        self.included.remove(&pair);
+        self.excluded.insert(pair);
+    }
+
+    /// Report pairs between these two bodies even if they are adjacent
+    pub fn include(&mut self, body_a: Option<usize>, body_b: Option<usize>) {
+        let pair = ordered(body_a, body_b);
+        self.excluded.remove(&pair);
+        self.included.insert(pair);
+    }
+
+    pub fn boxes(&self) -> &[Aabb] {
+        &self.boxes
+    }
+
+    /// Refit the boxes to the current poses and return the candidate collider pairs
+    pub fn update(
+        &mut self,
+        bodies: &[Body],
+        colliders: &[Collider],
+        kinematics: &[BodyKinematics],
+    ) -> Vec<(usize, usize)> {
+        self.boxes = colliders
+            .iter()
+            .map(|collider| {
+                bounding_box(&collider.shape, collider.pose(kinematics)).inflate(self.margin)
+            })
+            .collect();
+        if self.order.len() != colliders.len() {
+            self.order = (0..colliders.len()).collect();
+        }
+        self.axis = self.sweep_axis();
+
+        // insertion sort is close to linear when the order barely changes between steps
+        let axis = self.axis;
+        let lower = |boxes: &[Aabb], i: usize| boxes[i].min.to_array()[axis];
+        for i in 1..self.order.len() {
+            let mut j = i;
+            while j > 0 && lower(&self.boxes, self.order[j - 1]) > lower(&self.boxes, self.order[j])
+            {
+                self.order.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for (position, &i) in self.order.iter().enumerate() {
+            let upper = self.boxes[i].max.to_array()[axis];
+            for &j in &self.order[position + 1..] {
+                if lower(&self.boxes, j) > upper {
+                    break;
+                }
+                if self.boxes[i].overlaps(&self.boxes[j])
+                    && self.accepts(bodies, colliders[i].body, colliders[j].body)
+                {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    fn accepts(&self, bodies: &[Body], body_a: Option<usize>, body_b: Option<usize>) -> bool {
+        if body_a == body_b {
+            return false;
+        }
+        let pair = ordered(body_a, body_b);
+        if self.excluded.contains(&pair) {
+            return false;
+        }
+        if self.included.contains(&pair) || !self.exclude_adjacent {
+            return true;
+        }
+        // root bodies keep colliding with the world, which is where the ground usually lives
+        match (body_a, body_b) {
+            (Some(a), Some(b)) => bodies[a].parent != Some(b) && bodies[b].parent != Some(a),
+            _ => true,
+        }
+    }
+
+    // sweep along the axis where the finite box centers are most spread out
+    fn sweep_axis(&self) -> usize {
+        let centers: Vec<[f64; 3]> = self
+            .boxes
+            .iter()
+            .map(|b| (b.min + b.max).to_array())
+            .filter(|c| c.iter().all(|x| x.is_finite()))
+            .collect();
+        if centers.len() < 2 {
+            return self.axis;
+        }
+        let variance = |i: usize| {
+            let mean = centers.iter().map(|c| c[i]).sum::<f64>() / centers.len() as f64;
+            centers.iter().map(|c| (c[i] - mean).powi(2)).sum::<f64>()
+        };
+        (0..3)
+            .max_by(|a, b| variance(*a).total_cmp(&variance(*b)))
+            .unwrap_or(0)
+    }
+}
+
+impl Default for BroadPhase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn ordered(a: Option<usize>, b: Option<usize>) -> (Option<usize>, Option<usize>) {
+    (a.min(b), a.max(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Inertia;
+
+    #[test]
+    fn skips_adjacent_and_excluded_pairs() {
+        let inertia = Inertia::new(1.0, 0.1, 0.1, 0.1, 0.0, 0.0, 0.0);
+        let bodies = [
+            Body::new("thigh", None, TranslationVector::new(), inertia),
+            Body::new("shank", Some(0), TranslationVector::new(), inertia),
+            Body::new("foot", Some(1), TranslationVector::new(), inertia),
+        ];
+        let kinematics: Vec<BodyKinematics> = [0.0, 0.3, 0.6]
+            .iter()
+            .map(|z| {
+                let pose = TranslationVector::from_array([0.0, 0.0, *z]).as_transform();
+                BodyKinematics::new(pose, 0)
+            })
+            .collect();
+        let sphere = Shape::Sphere { radius: 0.35 };
+        let mut colliders: Vec<Collider> = (0..3)
+            .map(|i| Collider::new(Some(i), sphere.clone(), TransformationMatrix::identity()))
+            .collect();
+        colliders.push(Collider::new(
+            None,
+            Shape::HalfSpace,
+            TransformationMatrix::identity(),
+        ));
+
+        let mut broad_phase = BroadPhase::new();
+        let pairs = broad_phase.update(&bodies, &colliders, &kinematics);
+        assert_eq!(pairs, vec![(0, 2), (0, 3), (1, 3), (2, 3)]);
+
+        broad_phase.include(Some(0), Some(1));
+        broad_phase.exclude(Some(2), None);
+        let pairs = broad_phase.update(&bodies, &colliders, &kinematics);
+        assert_eq!(pairs, vec![(0, 1), (0, 2), (0, 3), (1, 3)]);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::body::BodyKinematics;
+use crate::{ForceVec6, MotionVec6, TransformationMatrix, TranslationVector};
+
+// spring-damper length below which its points count as coincident
+const MIN_LENGTH: f64 = 1e-12;
+
+/// Six degree of freedom spring-damper between a frame on one body and a frame on another
+///
+/// The deflection is the rotation vector and origin of frame B in frame A, and its rate the
+/// twist of B relative to A, both in A coordinates and ordered [angular; linear] like the
+/// spatial vectors. The restoring wrench is -K deflection - D rate, with its force acting at the
+/// origin of B so that only the rotational part turns B, and it reacts on A.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bushing {
+    /// `None` is the world
+    pub body_a: Option<usize>,
+    /// body to frame transform
+    pub frame_a: TransformationMatrix,
+    pub body_b: Option<usize>,
+    pub frame_b: TransformationMatrix,
+    pub stiffness: [[f64; 6]; 6],
+    pub damping: [[f64; 6]; 6],
+}
+
+/// Linear spring-damper between two body-fixed points, pulling them together when stretched
+/// past its rest length
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpringDamper {
+    pub body_a: Option<usize>,
+    /// in body coordinates, or world coordinates for the world
+    pub point_a: TranslationVector,
+    pub body_b: Option<usize>,
+    pub point_b: TranslationVector,
+    pub stiffness: f64,
+    pub damping: f64,
+    pub rest_length: f64,
+}
+
+impl Bushing {
+    pub fn new(
+        body_a: Option<usize>,
+        frame_a: TransformationMatrix,
+        body_b: Option<usize>,
+        frame_b: TransformationMatrix,
+    ) -> Self {
+        Self {
+            body_a,
+            frame_a,
+            body_b,
+            frame_b,
+            stiffness: [[0.0; 6]; 6],
+            damping: [[0.0; 6]; 6],
+        }
+    }
+
+    /// Diagonal stiffness about and along the axes of frame A
+    pub fn stiffness(mut self, rotational: [f64; 3], translational: [f64; 3]) -> Self {
+        self.stiffness = diagonal(rotational, translational);
+        self
+    }
+
+    pub fn damping(mut self, rotational: [f64; 3], translational: [f64; 3]) -> Self {
+        self.damping = diagonal(rotational, translational);
+        self
+    }
+
+    /// Rotation vector and origin of frame B in frame A
+    pub fn deflection(&self, kinematics: &[BodyKinematics]) -> MotionVec6 {
+        let (a, b) = (self.pose_a(kinematics), self.pose_b(kinematics));
+        let w = (a.to_rotation() * !b.to_rotation()).log().to_array();
+        let p = a.transform_point(b.to_translation()).to_array();
+        MotionVec6::from_array([w[0], w[1], w[2], p[0], p[1], p[2]])
+    }
+
+    /// Velocity of frame B relative to frame A, in A coordinates at the origin of A
+    pub fn deflection_rate(&self, kinematics: &[BodyKinematics]) -> MotionVec6 {
+        let (a, b) = (self.pose_a(kinematics), self.pose_b(kinematics));
+        let velocity = |body: Option<usize>, frame: TransformationMatrix| {
+            body.map_or(MotionVec6::new(), |body| kinematics[body].velocity >> frame)
+        };
+        let v_a = velocity(self.body_a, self.frame_a);
+        let v_b = velocity(self.body_b, self.frame_b) >> (a * !b);
+        v_b - v_a
+    }
+
+    /// World wrenches about the origin on the two bodies, equal and opposite, with world ends
+    /// left out
+    pub fn wrenches(&self, kinematics: &[BodyKinematics]) -> Vec<(usize, ForceVec6)> {
+        let deflection = self.deflection(kinematics).data;
+        let rate = self.deflection_rate(kinematics).data;
+        let mut local = [0.0; 6];
+        for (i, value) in local.iter_mut().enumerate() {
+            *value = -(0..6)
+                .map(|j| self.stiffness[i][j] * deflection[j] + self.damping[i][j] * rate[j])
+                .sum::<f64>();
+        }
+        // move the force from the origin of A to the origin of B, p x f
+        let p = TranslationVector::from_array([deflection[3], deflection[4], deflection[5]]);
+        let force = TranslationVector::from_array([local[3], local[4], local[5]]);
+        for (value, moment) in local.iter_mut().zip(p.cross(force).to_array()) {
+            *value += moment;
+        }
+        let on_b = ForceVec6::from_array(local) >> !self.pose_a(kinematics);
+        pair(self.body_a, self.body_b, on_b)
+    }
+
+    // world to frame transforms of the two ends
+    fn pose_a(&self, kinematics: &[BodyKinematics]) -> TransformationMatrix {
+        frame_pose(self.body_a, self.frame_a, kinematics)
+    }
+
+    fn pose_b(&self, kinematics: &[BodyKinematics]) -> TransformationMatrix {
+        frame_pose(self.body_b, self.frame_b, kinematics)
+    }
+}
+
+impl SpringDamper {
+    pub fn new(
+        body_a: Option<usize>,
+        point_a: TranslationVector,
+        body_b: Option<usize>,
+        point_b: TranslationVector,
+        stiffness: f64,
+    ) -> Self {
+        Self {
+            body_a,
+            point_a,
+            body_b,
+            point_b,
+            stiffness,
+            damping: 0.0,
+            rest_length: 0.0,
+        }
+    }
+
+    pub fn damping(mut self, damping: f64) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn rest_length(mut self, rest_length: f64) -> Self {
+        self.rest_length = rest_length;
+        self
+    }
+
+    /// Distance between the points and its rate of change, taken as zero while the points
+    /// coincide and the line between them is undefined
+    pub fn length(&self, kinematics: &[BodyKinematics]) -> (f64, f64) {
+        let (a, b) = (
+            self.end(self.body_a, self.point_a, kinematics),
+            self.end(self.body_b, self.point_b, kinematics),
+        );
+        let length = (b.0 - a.0).norm();
+        if length < MIN_LENGTH {
+            return (length, 0.0);
+        }
+        let direction = (b.0 - a.0) * (1.0 / length);
+        (length, direction.dot(b.1 - a.1))
+    }
+
+    /// Tension along the line between the points, negative when pushing them apart
+    pub fn tension(&self, kinematics: &[BodyKinematics]) -> f64 {
+        let (length, rate) = self.length(kinematics);
+        self.stiffness * (length - self.rest_length) + self.damping * rate
+    }
+
+    /// World wrenches about the origin on the two bodies, equal and opposite, with world ends
+    /// left out, and zero while the points coincide
+    pub fn wrenches(&self, kinematics: &[BodyKinematics]) -> Vec<(usize, ForceVec6)> {
+        let (a, b) = (
+            self.end(self.body_a, self.point_a, kinematics).0,
+            self.end(self.body_b, self.point_b, kinematics).0,
+        );
+        let length = (a - b).norm();
+        if length < MIN_LENGTH {
+            return pair(self.body_a, self.body_b, ForceVec6::new());
+        }
+        let force = (a - b) * (self.tension(kinematics) / length);
+        pair(
+            self.body_a,
+            self.body_b,
+            ForceVec6::from_point_force(b, force),
+        )
+    }
+
+    // world position and velocity of one end
+    fn end(
+        &self,
+        body: Option<usize>,
+        point: TranslationVector,
+        kinematics: &[BodyKinematics],
+    ) -> (TranslationVector, TranslationVector) {
+        match body {
+            Some(body) => (
+                kinematics[body].point_position(point),
+                kinematics[body].point_velocity(point),
+            ),
+            None => (point, TranslationVector::new()),
+        }
+    }
+}
+
+fn diagonal(rotational: [f64; 3], translational: [f64; 3]) -> [[f64; 6]; 6] {
+    let mut matrix = [[0.0; 6]; 6];
+    for (i, value) in rotational.into_iter().chain(translational).enumerate() {
+        matrix[i][i] = value;
+    }
+    matrix
+}
+
+fn frame_pose(
+    body: Option<usize>,
+    frame: TransformationMatrix,
+    kinematics: &[BodyKinematics],
+) -> TransformationMatrix {
+    body.map_or(frame, |body| frame * kinematics[body].pose)
+}
+
+// the wrench on B and its reaction on A, for the ends that are bodies
+fn pair(body_a: Option<usize>, body_b: Option<usize>, on_b: ForceVec6) -> Vec<(usize, ForceVec6)> {
+    let mut wrenches = Vec::new();
+    if let Some(a) = body_a {
+        wrenches.push((a, -on_b));
+    }
+    if let Some(b) = body_b {
+        wrenches.push((b, on_b));
+    }
+    wrenches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Basis, RotationMatrix};
+
+    fn vector(x: f64, y: f64, z: f64) -> TranslationVector {
+        TranslationVector::from_array([x, y, z])
+    }
+
+    fn body_at(pose: TransformationMatrix, velocity: [f64; 6]) -> BodyKinematics {
+        let mut kinematics = BodyKinematics::new(pose, 0);
+        kinematics.velocity = MotionVec6::from_array(velocity);
+        kinematics
+    }
+
+    #[test]
+    fn bushing_restores_and_balances() {
+        // body 1 sits 2 cm along x and turned 0.1 rad about z from where body 0 holds it
+        let kinematics = [
+            body_at(vector(0.0, 0.0, 1.0).as_transform(), [0.0; 6]),
+            body_at(
+                RotationMatrix::from_angle(Basis::Z, 0.1) + vector(0.02, 0.0, 1.0),
+                [0.0, 0.0, 0.0, 0.0, 0.3, 0.0],
+            ),
+        ];
+        let bushing = Bushing::new(
+            Some(0),
+            TransformationMatrix::identity(),
+            Some(1),
+            TransformationMatrix::identity(),
+        )
+        .stiffness([10.0; 3], [1000.0; 3])
+        .damping([0.0; 3], [50.0; 3]);
+
+        let deflection = bushing.deflection(&kinematics).data;
+        assert!((deflection[2] - 0.1).abs() < 1e-12);
+        assert!((deflection[3] - 0.02).abs() < 1e-12);
+
+        let wrenches = bushing.wrenches(&kinematics);
+        let on_b = wrenches[1].1.data;
+        // the spring pulls B back along -x, and the damper resists it sliding along its own y
+        let sliding = RotationMatrix::from_angle(Basis::Z, 0.1)
+            .transpose()
+            .rotate(vector(0.0, 0.3, 0.0))
+            .to_array();
+        assert!((on_b[3] + 20.0 + 50.0 * sliding[0]).abs() < 1e-9);
+        assert!((on_b[4] + 50.0 * sliding[1]).abs() < 1e-9);
+        // the moment about the origin of B at (0.02, 0, 1) turns it back by -1 N m about z
+        let about = ForceVec6::from_array(on_b) >> vector(0.02, 0.0, 1.0);
+        assert!((about.data[2] + 1.0).abs() < 1e-9);
+        let total = wrenches[0].1 + wrenches[1].1;
+        assert!(total.data.iter().all(|v| v.abs() < 1e-12));
+    }
+
+    #[test]
+    fn anisotropic_stiffness_turns_only_through_the_angle() {
+        let offset = vector(0.02, 0.02, 0.0);
+        let turned = |angle: f64| {
+            [
+                body_at(TransformationMatrix::identity(), [0.0; 6]),
+                body_at(
+                    RotationMatrix::from_angle(Basis::Z, angle) + offset,
+                    [0.0; 6],
+                ),
+            ]
+        };
+        let bushing = Bushing::new(
+            Some(0),
+            TransformationMatrix::identity(),
+            Some(1),
+            TransformationMatrix::identity(),
+        )
+        .stiffness([0.0, 0.0, 5.0], [1000.0, 0.0, 0.0]);
+
+        for angle in [0.0, 0.1] {
+            let on_b = bushing.wrenches(&turned(angle))[1].1;
+            assert!((on_b.translational_force()[0] + 20.0).abs() < 1e-9);
+            let about = on_b >> offset;
+            assert!((about.rotational_force()[2] + 5.0 * angle).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn spring_damper_pulls_points_together() {
+        let kinematics = [body_at(
+            vector(0.0, 0.0, 0.5).as_transform(),
+            [0.0, 0.0, 0.0, 0.0, 0.0, 0.2],
+        )];
+        // from a world anchor at the origin to a point 0.1 above the body origin
+        let spring = SpringDamper::new(
+            None,
+            TranslationVector::new(),
+            Some(0),
+            vector(0.0, 0.0, 0.1),
+            100.0,
+        )
+        .damping(10.0)
+        .rest_length(0.5);
+        let (length, rate) = spring.length(&kinematics);
+        assert!((length - 0.6).abs() < 1e-12);
+        assert!((rate - 0.2).abs() < 1e-12);
+        assert!((spring.tension(&kinematics) - 12.0).abs() < 1e-12);
+
+        let wrenches = spring.wrenches(&kinematics);
+        assert_eq!(wrenches.len(), 1);
+        let force = wrenches[0].1.translational_force();
+        assert!((force[2] + 12.0).abs() < 1e-12);
+        assert!(wrenches[0]
+            .1
+            .rotational_force()
+            .iter()
+            .all(|m| m.abs() < 1e-12));
+
+        // attached at one point, the spring neither knows its direction nor pulls
+        let coincident = SpringDamper::new(
+            None,
+            vector(0.0, 0.0, 0.5),
+            Some(0),
+            TranslationVector::new(),
+            100.0,
+        )
+        .damping(10.0);
+        assert_eq!(coincident.length(&kinematics), (0.0, 0.0));
+        assert_eq!(coincident.tension(&kinematics), 0.0);
+        let wrenches = coincident.wrenches(&kinematics);
+        assert_eq!(wrenches.len(), 1);
+        assert!(wrenches[0].1.data.iter().all(|v| *v == 0.0));
+    }
+}
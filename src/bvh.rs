@@ -0,0 +1,487 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::body::{Body, BodyKinematics};
+use crate::{Basis, Inertia, RotationMatrix, TransformationMatrix, TranslationVector};
+
+// rotation channels written on export
+const EXPORT_ORDER: [Basis; 3] = [Basis::Z, Basis::X, Basis::Y];
+
+/// One degree of freedom of a BVH joint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Position(Basis),
+    Rotation(Basis),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BvhJoint {
+    pub name: String,
+    pub parent: Option<usize>,
+    /// joint origin in parent coordinates
+    pub offset: TranslationVector,
+    /// applied in order, each rotation about an axis of the frame left by the ones before it
+    pub channels: Vec<Channel>,
+    /// end of the bone for joints closing a chain
+    pub end_site: Option<TranslationVector>,
+}
+
+/// BVH motion capture hierarchy and frames
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bvh {
+    /// parents always come before their children
+    pub joints: Vec<BvhJoint>,
+    pub frame_time: f64,
+    /// channel values per frame as stored in the file, angles in degrees
+    pub frames: Vec<Vec<f64>>,
+}
+
+impl Channel {
+    fn name(&self) -> &'static str {
+        match self {
+            Channel::Position(Basis::X) => "Xposition",
+            Channel::Position(Basis::Y) => "Yposition",
+            Channel::Position(Basis::Z) => "Zposition",
+            Channel::Rotation(Basis::X) => "Xrotation",
+            Channel::Rotation(Basis::Y) => "Yrotation",
+            Channel::Rotation(Basis::Z) => "Zrotation",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        let (axis, kind) = name.split_at_checked(1)?;
+        let axis = match axis.to_ascii_uppercase().as_str() {
+            "X" => Basis::X,
+            "Y" => Basis::Y,
+            "Z" => Basis::Z,
+            _ => return None,
+        };
+        match kind.to_ascii_lowercase().as_str() {
+            "position" => Some(Channel::Position(axis)),
+            "rotation" => Some(Channel::Rotation(axis)),
+            _ => None,
+        }
+    }
+}
+
+impl Bvh {
+    pub fn read(path: impl AsRef<Path>) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(path).ok()?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut tokens = text.split_whitespace();
+        let number =
+            |tokens: &mut std::str::SplitWhitespace| -> Option<f64> { tokens.next()?.parse().ok() };
+        let mut joints: Vec<BvhJoint> = Vec::new();
+        // open joints, with None for an end site
+        let mut stack: Vec<Option<usize>> = Vec::new();
+        loop {
+            match tokens.next()? {
+                "HIERARCHY" | "{" => {}
+                "ROOT" | "JOINT" => {
+                    joints.push(BvhJoint {
+                        name: tokens.next()?.to_string(),
+                        parent: stack.last().copied().flatten(),
+                        offset: TranslationVector::new(),
+                        channels: Vec::new(),
+                        end_site: None,
+                    });
+                    stack.push(Some(joints.len() - 1));
+                }
+                "End" => {
+                    tokens.next()?;
+                    stack.push(None);
+                }
+                "OFFSET" => {
+                    let offset = TranslationVector::from_array([
+                        number(&mut tokens)?,
+                        number(&mut tokens)?,
+                        number(&mut tokens)?,
+                    ]);
+                    match stack.last()? {
+                        Some(joint) => joints[*joint].offset = offset,
+                        None => {
+                            let joint = stack.iter().rev().find_map(|joint| *joint)?;
+                            joints[joint].end_site = Some(offset);
+                        }
+                    }
+                }
+                "CHANNELS" => {
+                    let joint = (*stack.last()?)?;
+                    let count = number(&mut tokens)? as usize;
+                    joints[joint].channels = (0..count)
+                        .map(|_| Channel::parse(tokens.next()?))
+                        .collect::<Option<_>>()?;
+                }
+                "}" => {
+                    stack.pop()?;
+                }
+                "MOTION" => break,
+                _ => return None,
+            }
+        }
+
+        // Frames: n, then Frame Time: t
+        tokens.next()?;
+        let count = number(&mut tokens)? as usize;
+        tokens.next()?;
+        tokens.next()?;
+        let frame_time = number(&mut tokens)?;
+        let width: usize = joints.iter().map(|joint| joint.channels.len()).sum();
+        let frames = (0..count)
+            .map(|_| (0..width).map(|_| number(&mut tokens)).collect())
+            .collect::<Option<_>>()?;
+        Some(Self {
+            joints,
+            frame_time,
+            frames,
+        })
+    }
+
+    /// Massless bodies mirroring the joint hierarchy, one generalized coordinate per channel
+    pub fn bodies(&self) -> Vec<Body> {
+        self.joints
+            .iter()
+            .map(|joint| {
+                Body::new(
+                    &joint.name,
+                    joint.parent,
+                    TranslationVector::new(),
+                    Inertia::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+                )
+            })
+            .collect()
+    }
+
+    /// Generalized coordinates of a frame, angles in radians
+    pub fn q(&self, frame: usize) -> Vec<f64> {
+        let channels = self.joints.iter().flat_map(|joint| &joint.channels);
+        self.frames[frame]
+            .iter()
+            .zip(channels)
+            .map(|(value, channel)| match channel {
+                Channel::Position(_) => *value,
+                Channel::Rotation(_) => value.to_radians(),
+            })
+            .collect()
+    }
+
+    pub fn trajectory(&self) -> Vec<Vec<f64>> {
+        (0..self.frames.len()).map(|frame| self.q(frame)).collect()
+    }
+
+    /// Parent to joint coordinate transform for the joint's slice of q
+    pub fn joint_transform(&self, joint: usize, q: &[f64]) -> TransformationMatrix {
+        let joint = &self.joints[joint];
+        let mut rotation = RotationMatrix::identity();
+        let mut translation = joint.offset;
+        for (channel, value) in joint.channels.iter().zip(q) {
+            match channel {
+                Channel::Position(axis) => {
+                    let mut shift = [0.0; 3];
+                    shift[*axis as usize] = *value;
+                    translation = translation + TranslationVector::from_array(shift);
+                }
+                Channel::Rotation(axis) => {
+                    rotation = RotationMatrix::from_angle(*axis, *value) * rotation
+                }
+            }
+        }
+        rotation + translation
+    }
+
+    /// World to joint coordinate transforms
+    pub fn poses(&self, q: &[f64]) -> Vec<TransformationMatrix> {
+        let mut poses: Vec<TransformationMatrix> = Vec::with_capacity(self.joints.len());
+        let mut start = 0;
+        for (i, joint) in self.joints.iter().enumerate() {
+            let end = start + joint.channels.len();
+            let relative = self.joint_transform(i, &q[start..end]);
+            poses.push(match joint.parent {
+                Some(parent) => relative * poses[parent],
+                None => relative,
+            });
+            start = end;
+        }
+        poses
+    }
+
+    /// Recording of a trajectory of any tree, with fixed bone offsets taken from the first
+    /// frame, positions on the roots and Z, X, Y rotations on every joint
+    pub fn export(
+        bodies: &[Body],
+        trajectory: &[Vec<f64>],
+        frame_time: f64,
+        kinematics: impl Fn(&[f64]) -> Vec<BodyKinematics>,
+    ) -> Self {
+        let relative: Vec<Vec<TransformationMatrix>> = trajectory
+            .iter()
+            .map(|q| {
+                let kinematics = kinematics(q);
+                bodies
+                    .iter()
+                    .zip(&kinematics)
+                    .map(|(body, kin)| match body.parent {
+                        Some(parent) => kin.pose * kinematics[parent].pose.inverse_transform(),
+                        None => kin.pose,
+                    })
+                    .collect()
+            })
+            .collect();
+        let rotations = EXPORT_ORDER.map(Channel::Rotation);
+        let positions = [Basis::X, Basis::Y, Basis::Z].map(Channel::Position);
+
+        let joints = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let root = body.parent.is_none();
+                let leaf = bodies.iter().all(|other| other.parent != Some(i));
+                BvhJoint {
+                    name: body.name.replace(char::is_whitespace, "_"),
+                    parent: body.parent,
+                    offset: match relative.first() {
+                        Some(first) if !root => first[i].to_translation(),
+                        _ => TranslationVector::new(),
+                    },
+                    channels: if root {
+                        positions.iter().chain(&rotations).copied().collect()
+                    } else {
+                        rotations.to_vec()
+                    },
+                    end_site: leaf.then(TranslationVector::new),
+                }
+            })
+            .collect();
+
+        let frames = relative
+            .iter()
+            .map(|transforms| {
+                let mut values = Vec::new();
+                for (body, transform) in bodies.iter().zip(transforms) {
+                    if body.parent.is_none() {
+                        values.extend(transform.to_translation().to_array());
+                    }
+                    let angles = transform.to_rotation().to_euler(EXPORT_ORDER);
+                    values.extend(angles.map(f64::to_degrees));
+                }
+                values
+            })
+            .collect();
+        Self {
+            joints,
+            frame_time,
+            frames,
+        }
+    }
+
+    fn write_joint(&self, f: &mut fmt::Formatter, joint: usize, depth: usize) -> fmt::Result {
+        let indent = "\t".repeat(depth);
+        let current = &self.joints[joint];
+        let keyword = if current.parent.is_some() {
+            "JOINT"
+        } else {
+            "ROOT"
+        };
+        let [x, y, z] = current.offset.to_array();
+        writeln!(f, "{indent}{keyword} {}", current.name)?;
+        writeln!(f, "{indent}{{")?;
+        writeln!(f, "{indent}\tOFFSET {x} {y} {z}")?;
+        let channels: Vec<&str> = current.channels.iter().map(Channel::name).collect();
+        writeln!(
+            f,
+            "{indent}\tCHANNELS {} {}",
+            channels.len(),
+            channels.join(" ")
+        )?;
+        for child in (0..self.joints.len()).filter(|i| self.joints[*i].parent == Some(joint)) {
+            self.write_joint(f, child, depth + 1)?;
+        }
+        if let Some(end) = current.end_site {
+            let [x, y, z] = end.to_array();
+            writeln!(f, "{indent}\tEnd Site")?;
+            writeln!(f, "{indent}\t{{")?;
+            writeln!(f, "{indent}\t\tOFFSET {x} {y} {z}")?;
+            writeln!(f, "{indent}\t}}")?;
+        }
+        writeln!(f, "{indent}}}")
+    }
+
+    // joints in the depth first order the hierarchy is written in
+    fn traversal(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.joints.len());
+        let mut stack: Vec<usize> = (0..self.joints.len())
+            .rev()
+            .filter(|i| self.joints[*i].parent.is_none())
+            .collect();
+        while let Some(joint) = stack.pop() {
+            order.push(joint);
+            stack.extend(
+                (0..self.joints.len())
+                    .rev()
+                    .filter(|i| self.joints[*i].parent == Some(joint)),
+            );
+        }
+        order
+    }
+}
+
+impl fmt::Display for Bvh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "HIERARCHY")?;
+        for root in (0..self.joints.len()).filter(|i| self.joints[*i].parent.is_none()) {
+            self.write_joint(f, root, 0)?;
+        }
+        writeln!(f, "MOTION")?;
+        writeln!(f, "Frames: {}", self.frames.len())?;
+        writeln!(f, "Frame Time: {}", self.frame_time)?;
+        // channel values follow the hierarchy, which need not be the order of `joints`
+        let starts: Vec<usize> = self
+            .joints
+            .iter()
+            .scan(0, |start, joint| {
+                *start += joint.channels.len();
+                Some(*start - joint.channels.len())
+            })
+            .collect();
+        let order = self.traversal();
+        for frame in &self.frames {
+            let values: Vec<String> = order
+                .iter()
+                .flat_map(|&joint| {
+                    &frame[starts[joint]..starts[joint] + self.joints[joint].channels.len()]
+                })
+                .map(|value| value.to_string())
+                .collect();
+            writeln!(f, "{}", values.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALK: &str = "HIERARCHY
+ROOT Hips
+{
+    OFFSET 0 0 0
+    CHANNELS 6 Xposition Yposition Zposition Yrotation Xrotation Zrotation
+    JOINT LeftUpLeg
+    {
+        OFFSET 0.1 -0.05 0
+        CHANNELS 3 Xrotation Yrotation Zrotation
+        JOINT LeftLeg
+        {
+            OFFSET 0 -0.45 0
+            CHANNELS 1 Xrotation
+            End Site
+            {
+                OFFSET 0 -0.42 0
+            }
+        }
+    }
+    JOINT Spine
+    {
+        OFFSET 0 0.1 0
+        CHANNELS 3 Zrotation Yrotation Xrotation
+    }
+}
+MOTION
+Frames: 2
+Frame Time: 0.0333333
+0 0.9 0 10 -5 3 20 4 -8 -35 2 1 0
+0.02 0.91 0.01 12 -4 2 25 5 -6 -50 3 0 1
+";
+
+    #[test]
+    fn export_reproduces_imported_poses() {
+        let bvh = Bvh::parse(WALK).unwrap();
+        assert_eq!(bvh.joints.len(), 4);
+        assert_eq!(bvh.joints[2].parent, Some(1));
+        assert_eq!(bvh.joints[3].parent, Some(0));
+        assert_eq!(
+            bvh.joints[2].end_site,
+            Some(TranslationVector::from_array([0.0, -0.42, 0.0]))
+        );
+        assert!((bvh.q(1)[9] + 50f64.to_radians()).abs() < 1e-12);
+
+        let kinematics = |q: &[f64]| {
+            bvh.poses(q)
+                .into_iter()
+                .map(|pose| BodyKinematics::new(pose, 0))
+                .collect()
+        };
+        let exported = Bvh::export(&bvh.bodies(), &bvh.trajectory(), bvh.frame_time, kinematics);
+        let read = Bvh::parse(&exported.to_string()).unwrap();
+        assert_eq!(read.joints.len(), 4);
+        for frame in 0..2 {
+            let expected = bvh.poses(&bvh.q(frame));
+            let poses = read.poses(&read.q(frame));
+            for (pose, expected) in poses.iter().zip(&expected) {
+                let point = TranslationVector::from_array([0.3, -0.2, 0.1]);
+                let error = pose.transform_point(point) - expected.transform_point(point);
+                assert!(error.norm() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn export_writes_frames_in_hierarchy_order() {
+        // body 3 hangs off body 1, so the file lists 0, 1, 3, 2
+        let bodies: Vec<Body> = [None, Some(0), Some(0), Some(1)]
+            .iter()
+            .enumerate()
+            .map(|(i, parent)| {
+                Body::new(
+                    &format!("b{i}"),
+                    *parent,
+                    TranslationVector::new(),
+                    Inertia::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+                )
+            })
+            .collect();
+        let offsets = [
+            [0.0, 1.0, 0.0],
+            [0.1, 0.0, 0.0],
+            [-0.1, 0.0, 0.0],
+            [0.0, -0.4, 0.0],
+        ];
+        let poses = |q: &[f64]| -> Vec<TransformationMatrix> {
+            let mut poses: Vec<TransformationMatrix> = Vec::new();
+            for (i, body) in bodies.iter().enumerate() {
+                let local = RotationMatrix::from_angle(Basis::Z, q[i])
+                    + TranslationVector::from_array(offsets[i]);
+                poses.push(match body.parent {
+                    Some(parent) => local * poses[parent],
+                    None => local,
+                });
+            }
+            poses
+        };
+        let kinematics = |q: &[f64]| {
+            poses(q)
+                .into_iter()
+                .map(|pose| BodyKinematics::new(pose, 0))
+                .collect()
+        };
+        let q = vec![0.1, 0.2, -0.3, 0.4];
+        let exported = Bvh::export(&bodies, std::slice::from_ref(&q), 0.01, kinematics);
+        let read = Bvh::parse(&exported.to_string()).unwrap();
+        let names: Vec<&str> = read.joints.iter().map(|j| j.name.as_str()).collect();
+        assert_eq!(names, ["b0", "b1", "b3", "b2"]);
+
+        let expected = poses(&q);
+        let point = TranslationVector::from_array([0.3, -0.2, 0.1]);
+        for (pose, body) in read.poses(&read.q(0)).iter().zip([0, 1, 3, 2]) {
+            let error = pose.transform_point(point) - expected[body].transform_point(point);
+            assert!(error.norm() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,628 @@
+use std::path::Path;
+
+use crate::ik::Marker;
+use crate::TranslationVector;
+
+const BLOCK: usize = 512;
+
+/// Number format of the machine that wrote a C3D file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Processor {
+    /// little-endian integers and IEEE floats
+    Intel,
+    /// little-endian integers and VAX floats
+    Dec,
+    /// big-endian integers and IEEE floats
+    Mips,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterData {
+    Char(Vec<String>),
+    Byte(Vec<u8>),
+    Integer(Vec<i16>),
+    Float(Vec<f32>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub description: String,
+    pub dimensions: Vec<usize>,
+    pub data: ParameterData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub description: String,
+    pub parameters: Vec<Parameter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub label: String,
+    pub context: String,
+    /// seconds from the start of the trial
+    pub time: f64,
+}
+
+/// Contents of a C3D motion capture file
+#[derive(Debug, Clone, PartialEq)]
+pub struct C3d {
+    pub processor: Processor,
+    pub groups: Vec<Group>,
+    pub point_rate: f64,
+    pub analog_rate: f64,
+    pub first_frame: usize,
+    pub point_labels: Vec<String>,
+    /// marker positions in meters, one row per frame, `None` where the marker was not seen
+    pub points: Vec<Vec<Option<TranslationVector>>>,
+    pub analog_labels: Vec<String>,
+    /// scaled analog values, one row per analog sample with one value per channel
+    pub analog: Vec<Vec<f64>>,
+    pub events: Vec<Event>,
+}
+
+// typed reads in the byte order of the file
+struct Reader<'a> {
+    bytes: &'a [u8],
+    processor: Processor,
+}
+
+impl Parameter {
+    pub fn strings(&self) -> Vec<String> {
+        match &self.data {
+            ParameterData::Char(strings) => strings.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Numeric values of any non-character parameter
+    pub fn values(&self) -> Vec<f64> {
+        match &self.data {
+            ParameterData::Char(_) => Vec::new(),
+            ParameterData::Byte(values) => values.iter().map(|v| *v as f64).collect(),
+            ParameterData::Integer(values) => values.iter().map(|v| *v as f64).collect(),
+            ParameterData::Float(values) => values.iter().map(|v| *v as f64).collect(),
+        }
+    }
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&self, at: usize) -> Option<u8> {
+        self.bytes.get(at).copied()
+    }
+
+    fn word(&self, at: usize) -> Option<[u8; 2]> {
+        let bytes: [u8; 2] = self.bytes.get(at..at + 2)?.try_into().ok()?;
+        Some(bytes)
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let bytes = self.word(at)?;
+        Some(match self.processor {
+            Processor::Intel | Processor::Dec => u16::from_le_bytes(bytes),
+            Processor::Mips => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn i16(&self, at: usize) -> Option<i16> {
+        Some(self.u16(at)? as i16)
+    }
+
+    fn f32(&self, at: usize) -> Option<f32> {
+        let b: [u8; 4] = self.bytes.get(at..at + 4)?.try_into().ok()?;
+        Some(match self.processor {
+            Processor::Intel => f32::from_le_bytes(b),
+            Processor::Mips => f32::from_be_bytes(b),
+            // VAX F floats are IEEE singles with swapped 16-bit halves, read four times too large
+            Processor::Dec => {
+                if b[1] & 0x7f == 0 && b[0] & 0x80 == 0 {
+                    0.0
+                } else {
+                    f32::from_le_bytes([b[2], b[3], b[0], b[1]]) / 4.0
+                }
+            }
+        })
+    }
+
+    fn text(&self, at: usize, length: usize) -> Option<String> {
+        let bytes = self.bytes.get(at..at + length)?;
+        Some(
+            String::from_utf8_lossy(bytes)
+                .trim_end_matches([' ', '\0'])
+                .to_string(),
+        )
+    }
+}
+
+impl C3d {
+    pub fn read(path: impl AsRef<Path>) -> Option<Self> {
+        Self::parse(&std::fs::read(path).ok()?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let parameter_start = (*bytes.first()? as usize).checked_sub(1)? * BLOCK;
+        let processor = match bytes.get(parameter_start + 3)? {
+            84 => Processor::Intel,
+            85 => Processor::Dec,
+            86 => Processor::Mips,
+            _ => return None,
+        };
+        let reader = Reader { bytes, processor };
+        let groups = parse_parameters(&reader, parameter_start)?;
+
+        // header words are numbered from one
+        let header = |word: usize| reader.u16(2 * (word - 1));
+        let first_frame = header(4)? as usize;
+        let last_frame = header(5)? as usize;
+        let frames = (last_frame + 1).saturating_sub(first_frame);
+        let data_start = (header(9)? as usize).checked_sub(1)? * BLOCK;
+        let analog_values = header(3)? as usize;
+        let samples_per_frame = (header(10)? as usize).max(1);
+
+        let mut c3d = C3d {
+            processor,
+            groups,
+            point_rate: reader.f32(2 * 10)? as f64,
+            analog_rate: 0.0,
+            first_frame,
+            point_labels: Vec::new(),
+            points: Vec::new(),
+            analog_labels: Vec::new(),
+            analog: Vec::new(),
+            events: Vec::new(),
+        };
+        let number =
+            |group: &str, name: &str| c3d.parameter(group, name)?.values().first().copied();
+        let point_count =
+            number("POINT", "USED").map_or(header(2)? as usize, |n| n as u16 as usize);
+        let scale = number("POINT", "SCALE").unwrap_or(reader.f32(2 * 6)? as f64);
+        let point_rate = number("POINT", "RATE").unwrap_or(c3d.point_rate);
+        let channels = number("ANALOG", "USED")
+            .map_or(analog_values / samples_per_frame, |n| n as u16 as usize);
+        let analog_rate = number("ANALOG", "RATE").unwrap_or(point_rate * samples_per_frame as f64);
+        let units = c3d.length_scale();
+        let channel_values = |name: &str, default: f64| {
+            let mut values = c3d.values("ANALOG", name);
+            values.resize(channels, *values.last().unwrap_or(&default));
+            values
+        };
+        let analog_scale = channel_values("SCALE", 1.0);
+        let analog_offset = channel_values("OFFSET", 0.0);
+        let general_scale = number("ANALOG", "GEN_SCALE").unwrap_or(1.0);
+        let unsigned = c3d
+            .strings("ANALOG", "FORMAT")
+            .first()
+            .is_some_and(|format| format.trim().eq_ignore_ascii_case("UNSIGNED"));
+
+        let float = scale < 0.0;
+        let size = if float { 4 } else { 2 };
+        let value = |at: usize| -> Option<f64> {
+            if float {
+                Some(reader.f32(at)? as f64)
+            } else {
+                Some(reader.i16(at)? as f64)
+            }
+        };
+        let frame_size = (4 * point_count + channels * samples_per_frame) * size;
+        let mut points = Vec::with_capacity(frames);
+        let mut analog = Vec::with_capacity(frames * samples_per_frame);
+        for frame in 0..frames {
+            let start = data_start + frame * frame_size;
+            let mut row = Vec::with_capacity(point_count);
+            for point in 0..point_count {
+                let at = start + 4 * point * size;
+                let coordinates = [value(at)?, value(at + size)?, value(at + 2 * size)?];
+                // a negative residual marks an invalid point
+                let residual = value(at + 3 * size)? as i32 as i16;
+                row.push((residual >= 0).then(|| {
+                    let factor = if float { units } else { scale * units };
+                    TranslationVector::from_array(coordinates.map(|c| c * factor))
+                }));
+            }
+            points.push(row);
+
+            let analog_start = start + 4 * point_count * size;
+            for sample in 0..samples_per_frame {
+                let mut row = Vec::with_capacity(channels);
+                for channel in 0..channels {
+                    let at = analog_start + (sample * channels + channel) * size;
+                    let raw = if unsigned && !float {
+                        reader.u16(at)? as f64
+                    } else {
+                        value(at)?
+                    };
+                    row.push(
+                        (raw - analog_offset[channel]) * general_scale * analog_scale[channel],
+                    );
+                }
+                analog.push(row);
+            }
+        }
+
+        c3d.point_rate = point_rate;
+        c3d.analog_rate = analog_rate;
+        c3d.point_labels = labels(&c3d.strings("POINT", "LABELS"), point_count, "POINT");
+        c3d.analog_labels = labels(&c3d.strings("ANALOG", "LABELS"), channels, "ANALOG");
+        c3d.points = points;
+        c3d.analog = analog;
+        c3d.events = c3d.parse_events();
+        Some(c3d)
+    }
+
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups
+            .iter()
+            .find(|group| group.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn parameter(&self, group: &str, name: &str) -> Option<&Parameter> {
+        self.group(group)?
+            .parameters
+            .iter()
+            .find(|parameter| parameter.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Values of a numeric parameter, empty when it is missing
+    pub fn values(&self, group: &str, name: &str) -> Vec<f64> {
+        self.parameter(group, name)
+            .map_or(Vec::new(), |parameter| parameter.values())
+    }
+
+    /// Strings of a character parameter, empty when it is missing
+    pub fn strings(&self, group: &str, name: &str) -> Vec<String> {
+        self.parameter(group, name)
+            .map_or(Vec::new(), |parameter| parameter.strings())
+    }
+
+    /// Factor from the point units of the file to meters, millimeters when unspecified
+    pub fn length_scale(&self) -> f64 {
+        self.strings("POINT", "UNITS")
+            .first()
+            .map_or(0.001, |unit| unit_scale(unit))
+    }
+
+    /// Factor from the units of each analog channel to SI units
+    pub fn analog_scales(&self) -> Vec<f64> {
+        let units = self.strings("ANALOG", "UNITS");
+        (0..self.analog_labels.len())
+            .map(|i| units.get(i).map_or(1.0, |unit| unit_scale(unit)))
+            .collect()
+    }
+
+    /// Samples of the analog channel with this label
+    pub fn analog_channel(&self, label: &str) -> Option<Vec<f64>> {
+        let channel = self.analog_labels.iter().position(|l| l == label)?;
+        Some(self.analog.iter().map(|row| row[channel]).collect())
+    }
+
+    /// Marker measurements per frame in the order of `markers`, matched by name, ready for
+    /// `MarkerIk::track`
+    pub fn marker_frames(&self, markers: &[Marker]) -> Vec<Vec<Option<TranslationVector>>> {
+        let columns: Vec<Option<usize>> = markers
+            .iter()
+            .map(|marker| self.point_labels.iter().position(|l| *l == marker.name))
+            .collect();
+        self.points
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|column| column.and_then(|c| row[c]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn parse_events(&self) -> Vec<Event> {
+        let count = self
+            .values("EVENT", "USED")
+            .first()
+            .map_or(0, |n| *n as usize);
+        let labels = self.strings("EVENT", "LABELS");
+        let contexts = self.strings("EVENT", "CONTEXTS");
+        let times = self.values("EVENT", "TIMES");
+        (0..count)
+            .filter(|i| 2 * i + 1 < times.len())
+            .map(|i| Event {
+                label: labels.get(i).cloned().unwrap_or_default(),
+                context: contexts.get(i).cloned().unwrap_or_default(),
+                // minutes then seconds
+                time: times[2 * i] * 60.0 + times[2 * i + 1],
+            })
+            .collect()
+    }
+}
+
+// factor to SI units for lengths, and for moments given per length unit such as Nmm
+pub(crate) fn unit_scale(unit: &str) -> f64 {
+    let unit = unit.trim().to_lowercase();
+    if unit.ends_with("mm") {
+        0.001
+    } else if unit.ends_with("cm") {
+        0.01
+    } else {
+        1.0
+    }
+}
+
+fn labels(labels: &[String], count: usize, prefix: &str) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+            labels
+                .get(i)
+                .cloned()
+                .unwrap_or_else(|| format!("{prefix}{}", i + 1))
+        })
+        .collect()
+}
+
+fn parse_parameters(reader: &Reader, start: usize) -> Option<Vec<Group>> {
+    let end = start + reader.u8(start + 2)? as usize * BLOCK;
+    let mut groups: Vec<(i8, Group)> = Vec::new();
+    let mut parameters: Vec<(i8, Parameter)> = Vec::new();
+    let mut at = start + 4;
+    while at + 2 < end.min(reader.bytes.len()) {
+        let length = (reader.u8(at)? as i8).unsigned_abs() as usize;
+        let id = reader.u8(at + 1)? as i8;
+        if length == 0 || id == 0 {
+            break;
+        }
+        let name = reader.text(at + 2, length)?;
+        let link = at + 2 + length;
+        let next = reader.i16(link)?;
+        let body = link + 2;
+        if id < 0 {
+            let description_length = reader.u8(body)? as usize;
+            groups.push((
+                -id,
+                Group {
+                    name,
+                    description: reader.text(body + 1, description_length)?,
+                    parameters: Vec::new(),
+                },
+            ));
+        } else {
+            let kind = reader.u8(body)? as i8;
+            let rank = reader.u8(body + 1)? as usize;
+            let dimensions: Vec<usize> = (0..rank)
+                .map(|i| reader.u8(body + 2 + i).map(|d| d as usize))
+                .collect::<Option<_>>()?;
+            let data_start = body + 2 + rank;
+            let count: usize = dimensions.iter().product();
+            let size = kind.unsigned_abs() as usize;
+            let data = match kind {
+                -1 => {
+                    let (width, strings) = match dimensions.split_first() {
+                        Some((width, rest)) => (*width, rest.iter().product()),
+                        None => (1, 1),
+                    };
+                    ParameterData::Char(
+                        (0..strings)
+                            .map(|i| reader.text(data_start + i * width, width))
+                            .collect::<Option<_>>()?,
+                    )
+                }
+                1 => {
+                    ParameterData::Byte(reader.bytes.get(data_start..data_start + count)?.to_vec())
+                }
+                2 => ParameterData::Integer(
+                    (0..count)
+                        .map(|i| reader.i16(data_start + 2 * i))
+                        .collect::<Option<_>>()?,
+                ),
+                4 => ParameterData::Float(
+                    (0..count)
+                        .map(|i| reader.f32(data_start + 4 * i))
+                        .collect::<Option<_>>()?,
+                ),
+                _ => return None,
+            };
+            let description_at = data_start + size * count.max(1);
+            let description_length = reader.u8(description_at).unwrap_or(0) as usize;
+            parameters.push((
+                id,
+                Parameter {
+                    name,
+                    description: reader
+                        .text(description_at + 1, description_length)
+                        .unwrap_or_default(),
+                    dimensions,
+                    data,
+                },
+            ));
+        }
+        if next <= 0 {
+            break;
+        }
+        at = link + next as usize;
+    }
+
+    for (id, parameter) in parameters {
+        if let Some((_, group)) = groups.iter_mut().find(|(g, _)| *g == id) {
+            group.parameters.push(parameter);
+        }
+    }
+    Some(groups.into_iter().map(|(_, group)| group).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // minimal C3D writer covering the parts the reader looks at
+    struct Writer {
+        processor: Processor,
+        bytes: Vec<u8>,
+    }
+
+    impl Writer {
+        fn i16(&mut self, value: i16) {
+            match self.processor {
+                Processor::Mips => self.bytes.extend(value.to_be_bytes()),
+                _ => self.bytes.extend(value.to_le_bytes()),
+            }
+        }
+
+        fn f32(&mut self, value: f32) {
+            match self.processor {
+                Processor::Intel => self.bytes.extend(value.to_le_bytes()),
+                Processor::Mips => self.bytes.extend(value.to_be_bytes()),
+                Processor::Dec => {
+                    let b = (value * 4.0).to_le_bytes();
+                    self.bytes.extend([b[2], b[3], b[0], b[1]]);
+                }
+            }
+        }
+
+        fn pad(&mut self) {
+            let blocks = self.bytes.len().div_ceil(BLOCK);
+            self.bytes.resize(blocks * BLOCK, 0);
+        }
+
+        fn group(&mut self, id: i8, name: &str) {
+            self.bytes.extend([name.len() as u8, (-id) as u8]);
+            self.bytes.extend(name.bytes());
+            self.i16(3);
+            self.bytes.push(0);
+        }
+
+        fn parameter(&mut self, id: i8, name: &str, kind: i8, dimensions: &[u8], data: &[u8]) {
+            self.bytes.extend([name.len() as u8, id as u8]);
+            self.bytes.extend(name.bytes());
+            self.i16((2 + 2 + dimensions.len() + data.len() + 1) as i16);
+            self.bytes.extend([kind as u8, dimensions.len() as u8]);
+            self.bytes.extend(dimensions);
+            self.bytes.extend(data);
+            self.bytes.push(0);
+        }
+
+        fn floats(&self, values: &[f32]) -> Vec<u8> {
+            let mut writer = Writer {
+                processor: self.processor,
+                bytes: Vec::new(),
+            };
+            values.iter().for_each(|v| writer.f32(*v));
+            writer.bytes
+        }
+
+        fn integers(&self, values: &[i16]) -> Vec<u8> {
+            let mut writer = Writer {
+                processor: self.processor,
+                bytes: Vec::new(),
+            };
+            values.iter().for_each(|v| writer.i16(*v));
+            writer.bytes
+        }
+    }
+
+    // two markers and two analog channels sampled twice per frame over three frames
+    fn write(processor: Processor, float: bool) -> Vec<u8> {
+        let mut w = Writer {
+            processor,
+            bytes: Vec::new(),
+        };
+        let scale: f32 = if float { -0.1 } else { 0.1 };
+        w.bytes.extend([2, 0x50]);
+        for word in [2, 4, 1, 3, 0] {
+            w.i16(word);
+        }
+        w.f32(scale);
+        w.i16(3);
+        w.i16(2);
+        w.f32(100.0);
+        w.pad();
+
+        w.bytes.extend([1, 0x50, 1, 84 + processor as u8]);
+        w.group(1, "POINT");
+        let used = w.integers(&[2]);
+        w.parameter(1, "USED", 2, &[], &used);
+        let data = w.floats(&[scale]);
+        w.parameter(1, "SCALE", 4, &[], &data);
+        let data = w.floats(&[100.0]);
+        w.parameter(1, "RATE", 4, &[], &data);
+        w.parameter(1, "LABELS", -1, &[4, 2], b"RASIRKNE");
+        w.parameter(1, "UNITS", -1, &[2], b"mm");
+        w.group(2, "ANALOG");
+        let used = w.integers(&[2]);
+        w.parameter(2, "USED", 2, &[], &used);
+        w.parameter(2, "LABELS", -1, &[3, 2], b"FZ1MZ1");
+        let data = w.floats(&[2.0, 0.5]);
+        w.parameter(2, "SCALE", 4, &[2], &data);
+        let data = w.integers(&[10, 0]);
+        w.parameter(2, "OFFSET", 2, &[2], &data);
+        let data = w.floats(&[1.0]);
+        w.parameter(2, "GEN_SCALE", 4, &[], &data);
+        w.group(3, "EVENT");
+        let used = w.integers(&[1]);
+        w.parameter(3, "USED", 2, &[], &used);
+        w.parameter(3, "LABELS", -1, &[11, 1], b"Foot Strike");
+        w.parameter(3, "CONTEXTS", -1, &[4, 1], b"Left");
+        let data = w.floats(&[0.0, 0.015]);
+        w.parameter(3, "TIMES", 4, &[2, 1], &data);
+        w.bytes.extend([0, 0]);
+        w.pad();
+
+        for frame in 0..3 {
+            for point in 0..2 {
+                let raw = [
+                    100 * point + frame,
+                    20,
+                    -30,
+                    if point == 1 && frame == 2 { -1 } else { 2 },
+                ];
+                for (i, v) in raw.into_iter().enumerate() {
+                    // float coordinates are stored already scaled
+                    if float {
+                        w.f32(if i < 3 { v as f32 * 0.1 } else { v as f32 });
+                    } else {
+                        w.i16(v as i16);
+                    }
+                }
+            }
+            for sample in 0..2 {
+                for channel in 0..2 {
+                    let v = 10 + 4 * frame + 2 * sample + channel;
+                    if float {
+                        w.f32(v as f32);
+                    } else {
+                        w.i16(v as i16);
+                    }
+                }
+            }
+        }
+        w.pad();
+        w.bytes
+    }
+
+    #[test]
+    fn reads_all_processor_and_storage_formats() {
+        for processor in [Processor::Intel, Processor::Dec, Processor::Mips] {
+            for float in [false, true] {
+                let c3d = C3d::parse(&write(processor, float)).unwrap();
+                assert_eq!(c3d.processor, processor);
+                assert_eq!(c3d.point_labels, ["RASI", "RKNE"]);
+                assert_eq!(c3d.points.len(), 3);
+                assert!((c3d.point_rate - 100.0).abs() < 1e-9);
+                assert!((c3d.analog_rate - 200.0).abs() < 1e-9);
+
+                let knee = c3d.points[1][1].unwrap().to_array();
+                let expected = [0.0101, 0.002, -0.003];
+                assert!(knee.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-7));
+                assert!(c3d.points[2][1].is_none());
+
+                // (raw - offset) * scale, frame 1 sample 1
+                let fz = c3d.analog_channel("FZ1").unwrap();
+                assert_eq!(fz.len(), 6);
+                assert!((fz[3] - (16.0 - 10.0) * 2.0).abs() < 1e-9);
+                assert_eq!(c3d.events[0].label, "Foot Strike");
+                assert!((c3d.events[0].time - 0.015).abs() < 1e-9);
+
+                let markers = [Marker::new("RKNE", 0, TranslationVector::new())];
+                assert_eq!(c3d.marker_frames(&markers)[1][0], c3d.points[1][1]);
+            }
+        }
+    }
+}
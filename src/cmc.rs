@@ -0,0 +1,415 @@
+use crate::body::BodyKinematics;
+use crate::ik::Marker;
+use crate::linalg::Matrix;
+use crate::muscle::Muscle;
+use crate::optimization::StaticOptimization;
+use crate::path::MusclePath;
+use crate::simulator::{Integrator, Simulator, State};
+
+/// Lengths, lengthening speeds and moment arms of the muscles at one state
+#[derive(Debug, Clone, PartialEq)]
+pub struct MuscleGeometry {
+    pub lengths: Vec<f64>,
+    pub speeds: Vec<f64>,
+    /// one row per muscle and one column per generalized coordinate
+    pub moment_arms: Matrix,
+}
+
+/// What the musculoskeletal system looks like at one state, supplied by the caller
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemDynamics {
+    pub mass_matrix: Matrix,
+    /// Coriolis, centrifugal, gravity and external generalized forces, as in H qdd + C = tau
+    pub bias_forces: Vec<f64>,
+    pub kinematics: Vec<BodyKinematics>,
+    pub muscles: MuscleGeometry,
+}
+
+/// Space in which the reference motion is followed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tracking {
+    Joint,
+    /// World positions of body-fixed points taken from the reference pose, with the joint-space
+    /// law acting in the null space of the points
+    Task(Vec<Marker>),
+}
+
+/// Computed muscle control: every control interval, PD tracking of a reference motion gives
+/// desired accelerations, inverse dynamics turns them into torques, static optimization shares
+/// the torques among the muscles, and excitations are chosen to bring the activations there by
+/// the end of the interval. The excitations are then held while the simulator integrates the
+/// skeleton together with the muscle states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComputedMuscleControl {
+    pub muscles: Vec<Muscle>,
+    pub optimization: StaticOptimization,
+    pub tracking: Tracking,
+    pub stiffness: f64,
+    pub damping: f64,
+    pub control_interval: f64,
+    pub step_size: f64,
+    pub integrator: Integrator,
+}
+
+/// Controls applied over one control interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlRecord {
+    pub t: f64,
+    pub excitations: Vec<f64>,
+    /// residual actuator torques, in the order they were added to the optimization
+    pub residuals: Vec<f64>,
+}
+
+impl MuscleGeometry {
+    pub fn from_paths(paths: &[MusclePath], kinematics: &[BodyKinematics], q: &[f64]) -> Self {
+        let dofs = kinematics.first().map_or(0, |kin| kin.jacobian.len());
+        let mut moment_arms = Matrix::new(paths.len(), dofs);
+        for (i, path) in paths.iter().enumerate() {
+            for (j, arm) in path.moment_arms(kinematics, q).into_iter().enumerate() {
+                moment_arms[(i, j)] = arm;
+            }
+        }
+        Self {
+            lengths: paths
+                .iter()
+                .map(|path| path.length(kinematics, q))
+                .collect(),
+            speeds: paths
+                .iter()
+                .map(|path| path.lengthening_speed(kinematics, q))
+                .collect(),
+            moment_arms,
+        }
+    }
+}
+
+impl ComputedMuscleControl {
+    pub fn new(muscles: Vec<Muscle>) -> Self {
+        Self {
+            muscles,
+            optimization: StaticOptimization::new(),
+            tracking: Tracking::Joint,
+            stiffness: 100.0,
+            damping: 20.0,
+            control_interval: 0.01,
+            step_size: 0.001,
+            integrator: Integrator::RungeKutta4,
+        }
+    }
+
+    pub fn optimization(mut self, optimization: StaticOptimization) -> Self {
+        self.optimization = optimization;
+        self
+    }
+
+    pub fn tracking(mut self, tracking: Tracking) -> Self {
+        self.tracking = tracking;
+        self
+    }
+
+    /// Position and velocity gains of the tracking law
+    pub fn gains(mut self, stiffness: f64, damping: f64) -> Self {
+        self.stiffness = stiffness;
+        self.damping = damping;
+        self
+    }
+
+    pub fn control_interval(mut self, control_interval: f64) -> Self {
+        self.control_interval = control_interval;
+        self
+    }
+
+    pub fn step_size(mut self, step_size: f64) -> Self {
+        self.step_size = step_size;
+        self
+    }
+
+    pub fn integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Accelerations that steer `state` toward the reference positions, velocities and
+    /// accelerations `reference` returns for the state's time
+    pub fn desired_accelerations(
+        &self,
+        state: &State,
+        reference: impl Fn(f64) -> [Vec<f64>; 3],
+        system: impl Fn(&[f64], &[f64]) -> SystemDynamics,
+    ) -> Option<Vec<f64>> {
+        let [q_ref, qd_ref, qdd_ref] = reference(state.t);
+        let joint: Vec<f64> = (0..state.q.len())
+            .map(|i| {
+                qdd_ref[i]
+                    + self.damping * (qd_ref[i] - state.qd[i])
+                    + self.stiffness * (q_ref[i] - state.q[i])
+            })
+            .collect();
+        let Tracking::Task(points) = &self.tracking else {
+            return Some(joint);
+        };
+
+        // J qdd = xdd_ref + kv (xd_ref - xd) + kp (x_ref - x) - bias, stacked over the points
+        let current = system(&state.q, &state.qd).kinematics;
+        let target = system(&q_ref, &qd_ref).kinematics;
+        let dofs = state.q.len();
+        let mut jacobian = Vec::with_capacity(3 * points.len() * dofs);
+        let mut rhs = Vec::with_capacity(3 * points.len());
+        for point in points {
+            let (now, then) = (&current[point.body], &target[point.body]);
+            let columns = now.point_jacobian(point.offset);
+            let reference_acceleration = then
+                .point_jacobian(point.offset)
+                .iter()
+                .zip(&qdd_ref)
+                .fold(then.point_bias_acceleration(point.offset), |sum, (c, a)| {
+                    sum + *c * *a
+                });
+            let desired = reference_acceleration
+                + (then.point_velocity(point.offset) - now.point_velocity(point.offset))
+                    * self.damping
+                + (then.point_position(point.offset) - now.point_position(point.offset))
+                    * self.stiffness
+                - now.point_bias_acceleration(point.offset);
+            for k in 0..3 {
+                jacobian.extend(columns.iter().map(|c| point.weight * c.to_array()[k]));
+                rhs.push(point.weight * desired.to_array()[k]);
+            }
+        }
+        let jacobian = Matrix::from_vec(rhs.len(), dofs, jacobian);
+
+        // damped least squares on the points, starting from the joint-space accelerations
+        let miss: Vec<f64> = rhs
+            .iter()
+            .zip(jacobian.multiply_vector(&joint))
+            .map(|(r, a)| r - a)
+            .collect();
+        let mut gram = &jacobian * &jacobian.transpose();
+        for i in 0..gram.rows() {
+            gram[(i, i)] += 1e-8;
+        }
+        let correction = jacobian.transpose_multiply_vector(&gram.cholesky_solve(&miss)?);
+        Some(joint.iter().zip(correction).map(|(a, c)| a + c).collect())
+    }
+
+    /// Excitations and residual torques to apply from `state` over the next control interval
+    pub fn controls(
+        &self,
+        state: &State,
+        muscle_states: &[Vec<f64>],
+        reference: impl Fn(f64) -> [Vec<f64>; 3],
+        system: impl Fn(&[f64], &[f64]) -> SystemDynamics,
+    ) -> Option<ControlRecord> {
+        let accelerations = self.desired_accelerations(state, reference, &system)?;
+        let dynamics = system(&state.q, &state.qd);
+        let torques: Vec<f64> = dynamics
+            .mass_matrix
+            .multiply_vector(&accelerations)
+            .iter()
+            .zip(&dynamics.bias_forces)
+            .map(|(a, c)| a + c)
+            .collect();
+        let geometry = &dynamics.muscles;
+        let solution = self.optimization.solve(
+            &self.muscles,
+            &geometry.lengths,
+            &geometry.speeds,
+            &geometry.moment_arms,
+            &torques,
+        )?;
+
+        // excitation that brings the activation to its target over the interval, taking the
+        // time constant fixed at its value for the current activation
+        let excitations = self
+            .muscles
+            .iter()
+            .zip(muscle_states.iter().zip(&solution.activations))
+            .map(|(muscle, (states, target))| {
+                let activation = states[0].clamp(muscle.min_activation, 1.0);
+                let scale = 0.5 + 1.5 * activation;
+                let time_constant = if *target > activation {
+                    muscle.activation_time_constant * scale
+                } else {
+                    muscle.deactivation_time_constant / scale
+                };
+                let decay = (-self.control_interval / time_constant).exp();
+                ((target - activation * decay) / (1.0 - decay)).clamp(muscle.min_activation, 1.0)
+            })
+            .collect();
+        Some(ControlRecord {
+            t: state.t,
+            excitations,
+            residuals: solution.residuals,
+        })
+    }
+
+    /// Follow the reference from `state` to `t_end`, updating the muscle states alongside.
+    /// Returns `None` if static optimization fails at some control time or the mass matrix
+    /// turns singular.
+    pub fn simulate(
+        &self,
+        state: &mut State,
+        muscle_states: &mut [Vec<f64>],
+        t_end: f64,
+        reference: impl Fn(f64) -> [Vec<f64>; 3],
+        system: impl Fn(&[f64], &[f64]) -> SystemDynamics,
+    ) -> Option<Vec<ControlRecord>> {
+        let dofs = state.q.len();
+        let mut records = Vec::new();
+        while t_end - state.t > 1e-12 {
+            let controls = self.controls(state, muscle_states, &reference, &system)?;
+            let mut residual_torques = vec![0.0; dofs];
+            for ((coordinate, _), torque) in
+                self.optimization.residuals.iter().zip(&controls.residuals)
+            {
+                residual_torques[*coordinate] += torque;
+            }
+
+            // the muscle states ride along as extra velocities, their derivatives as accelerations
+            let sizes: Vec<usize> = muscle_states.iter().map(Vec::len).collect();
+            let mut singular = false;
+            let dynamics = |_: f64, q: &[f64], qd: &[f64]| -> Vec<f64> {
+                let (q, qd, flat) = (&q[..dofs], &qd[..dofs], &qd[dofs..]);
+                let system = system(q, qd);
+                let geometry = &system.muscles;
+                let mut forces = Vec::with_capacity(sizes.len());
+                let mut derivatives = Vec::with_capacity(flat.len());
+                let mut offset = 0;
+                for (i, muscle) in self.muscles.iter().enumerate() {
+                    let states = &flat[offset..offset + sizes[i]];
+                    let (length, speed) = (geometry.lengths[i], geometry.speeds[i]);
+                    forces.push(muscle.force(states, length, speed));
+                    derivatives.extend(muscle.derivatives(
+                        states,
+                        controls.excitations[i],
+                        length,
+                        speed,
+                    ));
+                    offset += sizes[i];
+                }
+                let torques = geometry.moment_arms.transpose_multiply_vector(&forces);
+                let rhs: Vec<f64> = (0..dofs)
+                    .map(|j| torques[j] + residual_torques[j] - system.bias_forces[j])
+                    .collect();
+                // a singular mass matrix ends the simulation once the interval is done
+                let mut accelerations =
+                    system.mass_matrix.cholesky_solve(&rhs).unwrap_or_else(|| {
+                        singular = true;
+                        vec![0.0; dofs]
+                    });
+                accelerations.extend(derivatives);
+                accelerations
+            };
+            let mut simulator =
+                Simulator::new(dynamics, self.step_size).integrator(self.integrator);
+            let flat: Vec<f64> = muscle_states.iter().flatten().copied().collect();
+            let mut augmented = State {
+                t: state.t,
+                q: [state.q.clone(), vec![0.0; flat.len()]].concat(),
+                qd: [state.qd.clone(), flat].concat(),
+            };
+            simulator.simulate(&mut augmented, (state.t + self.control_interval).min(t_end));
+            if singular {
+                return None;
+            }
+
+            state.t = augmented.t;
+            state.q = augmented.q[..dofs].to_vec();
+            state.qd = augmented.qd[..dofs].to_vec();
+            let mut offset = dofs;
+            for states in muscle_states.iter_mut() {
+                let size = states.len();
+                states.copy_from_slice(&augmented.qd[offset..offset + size]);
+                offset += size;
+            }
+            records.push(controls);
+        }
+        Some(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Basis, MotionVec6, RotationMatrix, TranslationVector};
+
+    // unit mass on a 0.5 m rod hinged about z, gravity along -y, pulled by two antagonists with
+    // constant 5 cm moment arms
+    fn pendulum(q: &[f64], qd: &[f64]) -> SystemDynamics {
+        let (mass, length, arm) = (1.0, 0.5, 0.05);
+        let mut kinematics =
+            BodyKinematics::new(RotationMatrix::from_angle(Basis::Z, q[0]).as_transform(), 1);
+        kinematics.jacobian[0] = MotionVec6::from_array([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        kinematics.velocity = kinematics.jacobian[0].scale(qd[0]);
+        SystemDynamics {
+            mass_matrix: Matrix::from_vec(1, 1, vec![mass * length * length]),
+            bias_forces: vec![mass * 9.81 * length * q[0].cos()],
+            kinematics: vec![kinematics],
+            muscles: MuscleGeometry {
+                lengths: vec![0.3 - arm * q[0], 0.3 + arm * q[0]],
+                speeds: vec![-arm * qd[0], arm * qd[0]],
+                moment_arms: Matrix::from_vec(2, 1, vec![arm, -arm]),
+            },
+        }
+    }
+
+    fn reference(t: f64) -> [Vec<f64>; 3] {
+        let w = 2.0;
+        [
+            vec![0.3 * (w * t).sin()],
+            vec![0.3 * w * (w * t).cos()],
+            vec![-0.3 * w * w * (w * t).sin()],
+        ]
+    }
+
+    fn muscles() -> Vec<Muscle> {
+        vec![Muscle::new(500.0, 0.1, 0.2, 0.0); 2]
+    }
+
+    #[test]
+    fn tracks_a_joint_trajectory() {
+        let control = ComputedMuscleControl::new(muscles());
+        let mut state = State::new(vec![0.05], vec![0.0]);
+        let mut muscle_states = vec![vec![0.1], vec![0.1]];
+        let records = control
+            .simulate(&mut state, &mut muscle_states, 1.0, reference, pendulum)
+            .unwrap();
+
+        assert_eq!(records.len(), 100);
+        assert!((state.t - 1.0).abs() < 1e-9);
+        assert!((state.q[0] - reference(1.0)[0][0]).abs() < 0.01);
+        // the lower muscle holds the weight up
+        let last = records.last().unwrap();
+        assert!(last.excitations[0] > last.excitations[1]);
+
+        // a massless rod cannot be integrated
+        let massless = |q: &[f64], qd: &[f64]| SystemDynamics {
+            mass_matrix: Matrix::new(1, 1),
+            ..pendulum(q, qd)
+        };
+        let mut state = State::new(vec![0.05], vec![0.0]);
+        assert!(control
+            .simulate(&mut state, &mut muscle_states, 0.1, reference, massless)
+            .is_none());
+        assert_eq!(state.t, 0.0);
+    }
+
+    #[test]
+    fn task_space_matches_joint_space_on_a_single_hinge() {
+        let tip = Marker::new("tip", 0, TranslationVector::from_array([0.5, 0.0, 0.0]));
+        let joint = ComputedMuscleControl::new(muscles());
+        let task = joint.clone().tracking(Tracking::Task(vec![tip]));
+        let state = State {
+            t: 0.4,
+            q: vec![0.1],
+            qd: vec![-0.2],
+        };
+        let expected = joint
+            .desired_accelerations(&state, reference, pendulum)
+            .unwrap();
+        let found = task
+            .desired_accelerations(&state, reference, pendulum)
+            .unwrap();
+        assert!((found[0] - expected[0]).abs() < 0.05 * expected[0].abs());
+    }
+}
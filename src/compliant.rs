@@ -0,0 +1,234 @@
+use crate::body::BodyKinematics;
+use crate::{ForceVec6, TranslationVector};
+
+/// Elastic and dissipative force along the contact normal as a function of penetration depth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalModel {
+    /// f = k d + c dd/dt
+    KelvinVoigt { stiffness: f64, damping: f64 },
+    /// f = k d^n (1 + 3/2 c dd/dt)
+    HuntCrossley {
+        stiffness: f64,
+        exponent: f64,
+        dissipation: f64,
+    },
+}
+
+/// Smoothed Coulomb friction with an optional Stribeck drop from static to dynamic friction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrictionModel {
+    pub static_friction: f64,
+    pub dynamic_friction: f64,
+    pub viscous_friction: f64,
+    /// slip speed over which friction ramps up from zero
+    pub transition_velocity: f64,
+    /// slip speed of the Stribeck decay, zero to disable it
+    pub stribeck_velocity: f64,
+}
+
+/// Penalty contact between a body and the environment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompliantContact {
+    pub normal: NormalModel,
+    pub friction: FrictionModel,
+    depth_smoothing: f64,
+    force_smoothing: f64,
+}
+
+impl FrictionModel {
+    pub fn new(friction: f64, transition_velocity: f64) -> Self {
+        Self {
+            static_friction: friction,
+            dynamic_friction: friction,
+            viscous_friction: 0.0,
+            transition_velocity,
+            stribeck_velocity: 0.0,
+        }
+    }
+
+    pub fn stribeck(mut self, static_friction: f64, stribeck_velocity: f64) -> Self {
+        self.static_friction = static_friction;
+        self.stribeck_velocity = stribeck_velocity;
+        self
+    }
+
+    pub fn viscous(mut self, viscous_friction: f64) -> Self {
+        self.viscous_friction = viscous_friction;
+        self
+    }
+
+    /// Friction coefficient at slip speed `speed`
+    pub fn coefficient(&self, speed: f64) -> f64 {
+        let stribeck = if self.stribeck_velocity > 0.0 {
+            (self.static_friction - self.dynamic_friction)
+                * (-(speed / self.stribeck_velocity).powi(2)).exp()
+        } else {
+            0.0
+        };
+        (speed / self.transition_velocity).tanh() * (self.dynamic_friction + stribeck)
+            + self.viscous_friction * speed
+    }
+}
+
+impl CompliantContact {
+    pub fn new(normal: NormalModel, friction: FrictionModel) -> Self {
+        Self {
+            normal,
+            friction,
+            depth_smoothing: 0.0,
+            force_smoothing: 0.0,
+        }
+    }
+
+    /// Replace the kinks at zero depth and zero force by smooth transitions of the given widths,
+    /// so the force is continuously differentiable for gradient-based optimization
+    pub fn smoothing(mut self, depth: f64, force: f64) -> Self {
+        self.depth_smoothing = depth;
+        self.force_smoothing = force;
+        self
+    }
+
+    /// Normal force for a penetration `depth` growing at `depth_rate`
+    pub fn normal_force(&self, depth: f64, depth_rate: f64) -> f64 {
+        let depth = positive_part(depth, self.depth_smoothing);
+        let force = match self.normal {
+            NormalModel::KelvinVoigt { stiffness, damping } => {
+                if depth > 0.0 {
+                    stiffness * depth + damping * depth_rate
+                } else {
+                    0.0
+                }
+            }
+            NormalModel::HuntCrossley {
+                stiffness,
+                exponent,
+                dissipation,
+            } => stiffness * depth.powf(exponent) * (1.0 + 1.5 * dissipation * depth_rate),
+        };
+        // the surface can push but never pull
+        positive_part(force, self.force_smoothing)
+    }
+
+    /// Friction force opposing the tangential `slip` velocity
+    pub fn friction_force(&self, normal_force: f64, slip: TranslationVector) -> TranslationVector {
+        // the tanh ramp already vanishes at zero slip, this only keeps the direction finite
+        let speed = (slip.dot(slip) + self.friction.transition_velocity.powi(2) * 1e-6).sqrt();
+        if speed == 0.0 {
+            return TranslationVector::new();
+        }
+        let magnitude = self.friction.coefficient(speed) * normal_force;
+        slip * (-magnitude / speed)
+    }
+
+    /// World wrench about the origin on a body touching the environment at world `point`, with the
+    /// unit `normal` pointing out of the environment and `velocity` the point's velocity
+    pub fn wrench(
+        &self,
+        point: TranslationVector,
+        normal: TranslationVector,
+        depth: f64,
+        velocity: TranslationVector,
+    ) -> ForceVec6 {
+        let normal_velocity = normal.dot(velocity);
+        let normal_force = self.normal_force(depth, -normal_velocity);
+        let slip = velocity - normal * normal_velocity;
+        let force = normal * normal_force + self.friction_force(normal_force, slip);
+        ForceVec6::from_point_force(point, force)
+    }
+
+    /// `wrench` for a contact point given in body coordinates of a moving body
+    pub fn body_wrench(
+        &self,
+        kinematics: &BodyKinematics,
+        point: TranslationVector,
+        normal: TranslationVector,
+        depth: f64,
+    ) -> ForceVec6 {
+        self.wrench(
+            kinematics.point_position(point),
+            normal,
+            depth,
+            kinematics.point_velocity(point),
+        )
+    }
+}
+
+/// Sum world wrenches applied to bodies into one external force per body, for inverse and
+/// forward dynamics
+pub fn external_forces(bodies: usize, wrenches: &[(usize, ForceVec6)]) -> Vec<ForceVec6> {
+    let mut forces = vec![ForceVec6::new(); bodies];
+    for (body, wrench) in wrenches {
+        forces[*body] += *wrench;
+    }
+    forces
+}
+
+// max(x, 0), or a smooth approximation of it when width is positive
+fn positive_part(x: f64, width: f64) -> f64 {
+    if width > 0.0 {
+        0.5 * (x + (x * x + width * width).sqrt())
+    } else {
+        x.max(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn foot_pad() -> CompliantContact {
+        CompliantContact::new(
+            NormalModel::HuntCrossley {
+                stiffness: 2e6,
+                exponent: 1.5,
+                dissipation: 1.0,
+            },
+            FrictionModel::new(0.8, 0.05).stribeck(1.0, 0.1),
+        )
+    }
+
+    #[test]
+    fn hunt_crossley_with_friction() {
+        let contact = foot_pad();
+        let up = TranslationVector::from_array([0.0, 0.0, 1.0]);
+        let point = TranslationVector::from_array([0.1, 0.0, 0.0]);
+        let velocity = TranslationVector::from_array([2.0, 0.0, -0.1]);
+        let wrench = contact.wrench(point, up, 1e-3, velocity);
+
+        let normal = 2e6 * 1e-3_f64.powf(1.5) * (1.0 + 1.5 * 0.1);
+        let force = wrench.translational_force();
+        assert!((force[2] - normal).abs() < 1e-9);
+        assert!((force[0] + 0.8 * normal).abs() < 1e-3 * normal);
+        assert!((wrench.rotational_force()[1] + 0.1 * force[2]).abs() < 1e-9);
+
+        // leaving the surface fast enough never pulls
+        assert_eq!(contact.normal_force(1e-3, -10.0), 0.0);
+        assert_eq!(contact.normal_force(-1e-3, 0.0), 0.0);
+
+        // plain Coulomb friction has no force at rest rather than 0 / 0
+        let coulomb = CompliantContact::new(
+            NormalModel::KelvinVoigt {
+                stiffness: 1e5,
+                damping: 0.0,
+            },
+            FrictionModel::new(0.8, 0.0),
+        );
+        let stuck = coulomb.friction_force(100.0, TranslationVector::new());
+        assert_eq!(stuck.to_array(), [0.0; 3]);
+        let sliding = coulomb.friction_force(100.0, TranslationVector::from_array([0.0, 0.5, 0.0]));
+        assert!((sliding.to_array()[1] + 80.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn smoothed_contact_is_continuous() {
+        let contact = foot_pad().smoothing(1e-4, 1e-3);
+        let at_zero = contact.normal_force(0.0, 0.0);
+        assert!(at_zero > 0.0);
+        let (below, above) = (
+            contact.normal_force(-1e-9, 0.0),
+            contact.normal_force(1e-9, 0.0),
+        );
+        assert!((above - below).abs() < 1e-3);
+        assert!(contact.normal_force(-1e-2, 0.0) < 1e-3);
+    }
+}
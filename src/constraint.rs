@@ -0,0 +1,395 @@
+use crate::body::BodyKinematics;
+use crate::linalg::Matrix;
+use crate::{MotionVec6, TransformationMatrix};
+
+/// Loop closure between a frame on one body and a frame on another body or the world
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopConstraint {
+    /// `None` for a frame fixed in the world
+    pub body_a: Option<usize>,
+    /// body to constraint frame transform
+    pub frame_a: TransformationMatrix,
+    pub body_b: Option<usize>,
+    pub frame_b: TransformationMatrix,
+    /// constrained motion of frame b relative to frame a in frame a coordinates, angular then linear
+    pub directions: [bool; 6],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stabilization {
+    None,
+    /// J qdd = -dJ/dt qd - 2 alpha J qd - beta^2 error
+    Baumgarte {
+        alpha: f64,
+        beta: f64,
+    },
+}
+
+impl LoopConstraint {
+    pub fn new(
+        body_a: Option<usize>,
+        frame_a: TransformationMatrix,
+        body_b: Option<usize>,
+        frame_b: TransformationMatrix,
+    ) -> Self {
+        Self {
+            body_a,
+            frame_a,
+            body_b,
+            frame_b,
+            directions: [true; 6],
+        }
+    }
+
+    pub fn directions(mut self, directions: [bool; 6]) -> Self {
+        self.directions = directions;
+        self
+    }
+
+    pub fn rows(&self) -> usize {
+        self.directions.iter().filter(|d| **d).count()
+    }
+
+    /// Rotation vector then translation of frame b relative to frame a, for the constrained directions
+    pub fn position_error(&self, kinematics: &[BodyKinematics]) -> Vec<f64> {
+        let to_a = frame_pose(self.body_a, self.frame_a, kinematics);
+        let to_b = frame_pose(self.body_b, self.frame_b, kinematics);
+        let relative = to_b * !to_a;
+        let rotation = (!relative.to_rotation()).log().to_array();
+        let translation = relative.to_translation().to_array();
+        self.select([
+            rotation[0],
+            rotation[1],
+            rotation[2],
+            translation[0],
+            translation[1],
+            translation[2],
+        ])
+    }
+
+    pub fn velocity_error(&self, kinematics: &[BodyKinematics]) -> Vec<f64> {
+        let to_a = frame_pose(self.body_a, self.frame_a, kinematics);
+        let relative =
+            world_velocity(self.body_b, kinematics) - world_velocity(self.body_a, kinematics);
+        self.select((relative >> to_a).data)
+    }
+
+    pub fn jacobian(&self, kinematics: &[BodyKinematics]) -> Matrix {
+        let to_a = frame_pose(self.body_a, self.frame_a, kinematics);
+        let dofs = kinematics.first().map_or(0, |kin| kin.jacobian.len());
+        let column = |body: Option<usize>, k: usize| match body {
+            Some(i) => kinematics[i].jacobian[k] >> kinematics[i].pose.inverse_transform(),
+            None => MotionVec6::new(),
+        };
+
+        let mut jacobian = Matrix::new(self.rows(), dofs);
+        for k in 0..dofs {
+            let relative = (column(self.body_b, k) - column(self.body_a, k)) >> to_a;
+            for (row, value) in self.select(relative.data).into_iter().enumerate() {
+                jacobian[(row, k)] = value;
+            }
+        }
+        jacobian
+    }
+
+    /// The dJ/dt qd term of the constraint acceleration
+    pub fn bias(&self, kinematics: &[BodyKinematics]) -> Vec<f64> {
+        let to_a = frame_pose(self.body_a, self.frame_a, kinematics);
+        let bias = |body: Option<usize>| match body {
+            Some(i) => kinematics[i].bias_acceleration >> kinematics[i].pose.inverse_transform(),
+            None => MotionVec6::new(),
+        };
+        let frame_velocity = world_velocity(self.body_a, kinematics) >> to_a;
+        let relative_velocity = (world_velocity(self.body_b, kinematics)
+            - world_velocity(self.body_a, kinematics))
+            >> to_a;
+        let relative_bias = (bias(self.body_b) - bias(self.body_a)) >> to_a;
+        // constrained directions rotate with frame a
+        self.select((relative_bias - (frame_velocity ^ relative_velocity)).data)
+    }
+
+    fn select(&self, values: [f64; 6]) -> Vec<f64> {
+        values
+            .into_iter()
+            .zip(self.directions)
+            .filter(|(_, selected)| *selected)
+            .map(|(value, _)| value)
+            .collect()
+    }
+}
+
+fn frame_pose(
+    body: Option<usize>,
+    frame: TransformationMatrix,
+    kinematics: &[BodyKinematics],
+) -> TransformationMatrix {
+    match body {
+        Some(i) => frame * kinematics[i].pose,
+        None => frame,
+    }
+}
+
+fn world_velocity(body: Option<usize>, kinematics: &[BodyKinematics]) -> MotionVec6 {
+    match body {
+        Some(i) => kinematics[i].velocity >> kinematics[i].pose.inverse_transform(),
+        None => MotionVec6::new(),
+    }
+}
+
+pub fn constraint_jacobian(
+    constraints: &[LoopConstraint],
+    kinematics: &[BodyKinematics],
+) -> Matrix {
+    let dofs = kinematics.first().map_or(0, |kin| kin.jacobian.len());
+    constraints
+        .iter()
+        .fold(Matrix::new(0, dofs), |jacobian, constraint| {
+            jacobian.stack(&constraint.jacobian(kinematics))
+        })
+}
+
+/// Right hand side of J qdd = gamma for the stacked constraints
+pub fn constraint_acceleration(
+    constraints: &[LoopConstraint],
+    kinematics: &[BodyKinematics],
+    stabilization: Stabilization,
+) -> Vec<f64> {
+    let mut gamma = Vec::new();
+    for constraint in constraints {
+        let bias = constraint.bias(kinematics);
+        match stabilization {
+            Stabilization::None => gamma.extend(bias.iter().map(|b| -b)),
+            Stabilization::Baumgarte { alpha, beta } => {
+                let velocity = constraint.velocity_error(kinematics);
+                let position = constraint.position_error(kinematics);
+                gamma.extend(
+                    (0..bias.len())
+                        .map(|i| -bias[i] - 2.0 * alpha * velocity[i] - beta * beta * position[i]),
+                );
+            }
+        }
+    }
+    gamma
+}
+
+/// Solve H qdd + C = tau + J^T lambda subject to J qdd = gamma, returning qdd and lambda
+pub fn forward_dynamics(
+    mass_matrix: &Matrix,
+    bias_forces: &[f64],
+    tau: &[f64],
+    jacobian: &Matrix,
+    gamma: &[f64],
+) -> Option<(Vec<f64>, Vec<f64>)> {
+    let rhs: Vec<f64> = tau.iter().zip(bias_forces).map(|(t, c)| t - c).collect();
+    let unconstrained = mass_matrix.cholesky_solve(&rhs)?;
+    if jacobian.rows() == 0 {
+        return Some((unconstrained, Vec::new()));
+    }
+
+    let mobility = mobility(mass_matrix, jacobian)?;
+    let delassus = jacobian * &mobility;
+    let residual: Vec<f64> = gamma
+        .iter()
+        .zip(jacobian.multiply_vector(&unconstrained))
+        .map(|(g, a)| g - a)
+        .collect();
+    let lambda = solve_regularized(&delassus, &residual)?;
+    let qdd = unconstrained
+        .iter()
+        .zip(mobility.multiply_vector(&lambda))
+        .map(|(a, b)| a + b)
+        .collect();
+    Some((qdd, lambda))
+}
+
+/// Actuator torques and constraint forces reproducing `tau_tree`, the inverse dynamics of the
+/// spanning tree, with zero torque on the coordinates that are not `actuated`
+///
+/// Returns `None` if `actuated` does not have one entry per coordinate.
+pub fn inverse_dynamics(
+    tau_tree: &[f64],
+    jacobian: &Matrix,
+    actuated: &[bool],
+) -> Option<(Vec<f64>, Vec<f64>)> {
+    if actuated.len() != tau_tree.len() {
+        return None;
+    }
+    let passive: Vec<usize> = (0..tau_tree.len()).filter(|i| !actuated[*i]).collect();
+    // rows of J^T belonging to the passive coordinates
+    let a = jacobian.transpose().select_rows(&passive);
+    let tau_passive: Vec<f64> = passive.iter().map(|i| tau_tree[*i]).collect();
+
+    let lambda = if a.rows() >= a.cols() {
+        let a_t = a.transpose();
+        (&a_t * &a).solve(&a.transpose_multiply_vector(&tau_passive))?
+    } else {
+        let y = (&a * &a.transpose()).solve(&tau_passive)?;
+        a.transpose_multiply_vector(&y)
+    };
+    let tau = tau_tree
+        .iter()
+        .zip(jacobian.transpose_multiply_vector(&lambda))
+        .map(|(t, f)| t - f)
+        .collect();
+    Some((tau, lambda))
+}
+
+/// Mass-weighted projection of `qd` onto the constraint manifold J qd = 0
+pub fn project_velocities(mass_matrix: &Matrix, jacobian: &Matrix, qd: &[f64]) -> Option<Vec<f64>> {
+    let zeros = vec![0.0; qd.len()];
+    let momentum = mass_matrix.multiply_vector(qd);
+    let gamma = vec![0.0; jacobian.rows()];
+    // a unit impulsive step that removes the constraint-violating velocity
+    let (projected, _) = forward_dynamics(mass_matrix, &zeros, &momentum, jacobian, &gamma)?;
+    Some(projected)
+}
+
+/// Gauss-Newton projection of `q` onto the constraint manifold, with `kinematics` computing the
+/// body kinematics of a configuration
+pub fn project_positions(
+    constraints: &[LoopConstraint],
+    q: &[f64],
+    kinematics: impl Fn(&[f64]) -> Vec<BodyKinematics>,
+    tolerance: f64,
+    max_iterations: usize,
+) -> Option<Vec<f64>> {
+    let mut q = q.to_vec();
+    for _ in 0..max_iterations {
+        let kin = kinematics(&q);
+        let error: Vec<f64> = constraints
+            .iter()
+            .flat_map(|constraint| constraint.position_error(&kin))
+            .collect();
+        if error.iter().map(|e| e * e).sum::<f64>().sqrt() < tolerance {
+            return Some(q);
+        }
+        let jacobian = constraint_jacobian(constraints, &kin);
+        let y = solve_regularized(&(&jacobian * &jacobian.transpose()), &error)?;
+        for (value, step) in q.iter_mut().zip(jacobian.transpose_multiply_vector(&y)) {
+            *value -= step;
+        }
+    }
+    None
+}
+
+// H^-1 J^T, one column per constraint row
+pub(crate) fn mobility(mass_matrix: &Matrix, jacobian: &Matrix) -> Option<Matrix> {
+    let mut mobility = Matrix::new(jacobian.cols(), jacobian.rows());
+    for j in 0..jacobian.rows() {
+        let column = mass_matrix.cholesky_solve(jacobian.row(j))?;
+        for (i, value) in column.into_iter().enumerate() {
+            mobility[(i, j)] = value;
+        }
+    }
+    Some(mobility)
+}
+
+// redundant constraints make the system singular, in which case a small diagonal term picks
+// one of the equivalent solutions
+pub(crate) fn solve_regularized(matrix: &Matrix, rhs: &[f64]) -> Option<Vec<f64>> {
+    matrix.solve(rhs).or_else(|| {
+        let scale = (0..matrix.rows()).fold(0.0_f64, |max, i| max.max(matrix[(i, i)].abs()));
+        let damping = Matrix::identity(matrix.rows()).scale(1e-10 * scale.max(1.0));
+        (matrix + &damping).solve(rhs)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RotationMatrix, TranslationVector};
+
+    const LENGTH: f64 = 0.8;
+    const MASS: f64 = 2.0;
+    const I_ZZ: f64 = 0.05;
+
+    // planar body with q = [x, y, theta], pinned at (-LENGTH, 0) in body coordinates to the origin
+    fn planar_body(q: &[f64], qd: &[f64]) -> Vec<BodyKinematics> {
+        let rotation = RotationMatrix::from_z_rotation(q[2]);
+        let position = TranslationVector::from_array([q[0], q[1], 0.0]);
+        let mut kin = BodyKinematics::new(rotation + position, 3);
+        let x = rotation * TranslationVector::from_array([1.0, 0.0, 0.0]);
+        let y = rotation * TranslationVector::from_array([0.0, 1.0, 0.0]);
+        let (x, y) = (x.to_array(), y.to_array());
+        kin.jacobian[0] = MotionVec6::from_array([0.0, 0.0, 0.0, x[0], x[1], x[2]]);
+        kin.jacobian[1] = MotionVec6::from_array([0.0, 0.0, 0.0, y[0], y[1], y[2]]);
+        kin.jacobian[2] = MotionVec6::from_array([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        kin.velocity = kin.jacobian[0] * qd[0] + kin.jacobian[1] * qd[1] + kin.jacobian[2] * qd[2];
+        let v = rotation * TranslationVector::from_array([qd[0], qd[1], 0.0]);
+        let w = TranslationVector::from_array([0.0, 0.0, qd[2]]);
+        let bias = (w.cross(v) * -1.0).to_array();
+        kin.bias_acceleration = MotionVec6::from_array([0.0, 0.0, 0.0, bias[0], bias[1], bias[2]]);
+        vec![kin]
+    }
+
+    fn pin() -> LoopConstraint {
+        let pivot = TranslationVector::from_array([-LENGTH, 0.0, 0.0]).as_transform();
+        LoopConstraint::new(Some(0), pivot, None, TransformationMatrix::identity())
+            .directions([false, false, false, true, true, false])
+    }
+
+    #[test]
+    fn pendulum_forward_and_inverse_dynamics() {
+        let q = [LENGTH * 0.3_f64.cos(), LENGTH * 0.3_f64.sin(), 0.3];
+        let qd = [
+            -LENGTH * 0.3_f64.sin() * 2.0,
+            LENGTH * 0.3_f64.cos() * 2.0,
+            2.0,
+        ];
+        let kinematics = planar_body(&q, &qd);
+        let constraints = [pin()];
+        assert!(constraints[0]
+            .position_error(&kinematics)
+            .iter()
+            .all(|e| e.abs() < 1e-12));
+        assert!(constraints[0]
+            .velocity_error(&kinematics)
+            .iter()
+            .all(|e| e.abs() < 1e-12));
+
+        let mass_matrix = Matrix::from_diagonal(&[MASS, MASS, I_ZZ]);
+        let bias_forces = [0.0, MASS * 9.81, 0.0];
+        let jacobian = constraint_jacobian(&constraints, &kinematics);
+        let gamma = constraint_acceleration(&constraints, &kinematics, Stabilization::None);
+        let (qdd, lambda) =
+            forward_dynamics(&mass_matrix, &bias_forces, &[0.0; 3], &jacobian, &gamma).unwrap();
+
+        let expected = -MASS * 9.81 * LENGTH * 0.3_f64.cos() / (I_ZZ + MASS * LENGTH * LENGTH);
+        assert!((qdd[2] - expected).abs() < 1e-12);
+
+        let tau_tree: Vec<f64> = mass_matrix
+            .multiply_vector(&qdd)
+            .iter()
+            .zip(bias_forces)
+            .map(|(a, c)| a + c)
+            .collect();
+        let (tau, recovered) = inverse_dynamics(&tau_tree, &jacobian, &[false; 3]).unwrap();
+        assert!(tau.iter().all(|t| t.abs() < 1e-9));
+        for (a, b) in recovered.iter().zip(&lambda) {
+            assert!((a - b).abs() < 1e-9);
+        }
+        assert!(inverse_dynamics(&tau_tree, &jacobian, &[false; 2]).is_none());
+    }
+
+    #[test]
+    fn projection_restores_the_loop() {
+        let constraints = [pin()];
+        let q = [0.9, 0.1, 0.2];
+        let projected =
+            project_positions(&constraints, &q, |q| planar_body(q, &[0.0; 3]), 1e-12, 20).unwrap();
+        let kinematics = planar_body(&projected, &[0.0; 3]);
+        assert!(constraints[0]
+            .position_error(&kinematics)
+            .iter()
+            .all(|e| e.abs() < 1e-12));
+
+        let mass_matrix = Matrix::from_diagonal(&[MASS, MASS, I_ZZ]);
+        let jacobian = constraint_jacobian(&constraints, &kinematics);
+        let qd = project_velocities(&mass_matrix, &jacobian, &[1.0, -0.5, 0.7]).unwrap();
+        let kinematics = planar_body(&projected, &qd);
+        assert!(constraints[0]
+            .velocity_error(&kinematics)
+            .iter()
+            .all(|e| e.abs() < 1e-12));
+    }
+}
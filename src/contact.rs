@@ -0,0 +1,387 @@
+use crate::body::BodyKinematics;
+use crate::constraint::{self, LoopConstraint};
+use crate::linalg::Matrix;
+use crate::{ForceVec6, TransformationMatrix, TranslationVector};
+
+/// Rigid contact between a body and the environment
+///
+/// Point contacts constrain the world velocity of a body-fixed point along the normal and two
+/// tangents, in that order. Frame contacts constrain all six directions of a body-fixed frame in
+/// frame coordinates, angular then linear, with the frame's z axis along the surface normal.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Contact {
+    Point {
+        body: usize,
+        point: TranslationVector,
+        normal: TranslationVector,
+    },
+    Frame {
+        body: usize,
+        frame: TransformationMatrix,
+    },
+}
+
+impl Contact {
+    pub fn point(body: usize, point: TranslationVector, normal: TranslationVector) -> Self {
+        Contact::Point {
+            body,
+            point,
+            normal: normal * (1.0 / normal.norm()),
+        }
+    }
+
+    pub fn frame(body: usize, frame: TransformationMatrix) -> Self {
+        Contact::Frame { body, frame }
+    }
+
+    pub fn body(&self) -> usize {
+        match self {
+            Contact::Point { body, .. } | Contact::Frame { body, .. } => *body,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        match self {
+            Contact::Point { .. } => 3,
+            Contact::Frame { .. } => 6,
+        }
+    }
+
+    /// Row of the normal direction among this contact's rows
+    pub fn normal_row(&self) -> usize {
+        match self {
+            Contact::Point { .. } => 0,
+            Contact::Frame { .. } => 5,
+        }
+    }
+
+    pub fn jacobian(&self, kinematics: &[BodyKinematics]) -> Matrix {
+        match self {
+            Contact::Point {
+                body,
+                point,
+                normal,
+            } => {
+                let columns = kinematics[*body].point_jacobian(*point);
+                let mut jacobian = Matrix::new(3, columns.len());
+                for (row, axis) in tangent_basis(*normal).iter().enumerate() {
+                    for (k, column) in columns.iter().enumerate() {
+                        jacobian[(row, k)] = axis.dot(*column);
+                    }
+                }
+                jacobian
+            }
+            Contact::Frame { .. } => self.frame_constraint(kinematics).jacobian(kinematics),
+        }
+    }
+
+    /// The dJ/dt qd term of the contact acceleration
+    pub fn bias(&self, kinematics: &[BodyKinematics]) -> Vec<f64> {
+        match self {
+            Contact::Point {
+                body,
+                point,
+                normal,
+            } => {
+                let acceleration = kinematics[*body].point_bias_acceleration(*point);
+                tangent_basis(*normal)
+                    .iter()
+                    .map(|axis| axis.dot(acceleration))
+                    .collect()
+            }
+            Contact::Frame { .. } => self.frame_constraint(kinematics).bias(kinematics),
+        }
+    }
+
+    pub fn velocity(&self, kinematics: &[BodyKinematics]) -> Vec<f64> {
+        match self {
+            Contact::Point {
+                body,
+                point,
+                normal,
+            } => {
+                let velocity = kinematics[*body].point_velocity(*point);
+                tangent_basis(*normal)
+                    .iter()
+                    .map(|axis| axis.dot(velocity))
+                    .collect()
+            }
+            Contact::Frame { .. } => self.frame_constraint(kinematics).velocity_error(kinematics),
+        }
+    }
+
+    /// World wrench about the origin applied to the body by the contact force `lambda`
+    pub fn wrench(&self, kinematics: &[BodyKinematics], lambda: &[f64]) -> ForceVec6 {
+        match self {
+            Contact::Point {
+                body,
+                point,
+                normal,
+            } => {
+                let [n, t1, t2] = tangent_basis(*normal);
+                let force = n * lambda[0] + t1 * lambda[1] + t2 * lambda[2];
+                ForceVec6::from_point_force(kinematics[*body].point_position(*point), force)
+            }
+            Contact::Frame { body, frame } => {
+                let to_frame = *frame * kinematics[*body].pose;
+                let mut wrench = [0.0; 6];
+                wrench.copy_from_slice(&lambda[..6]);
+                ForceVec6::from_array(wrench) >> to_frame.inverse_transform()
+            }
+        }
+    }
+
+    // a frame contact is a loop closure against a world frame at the frame's current pose
+    fn frame_constraint(&self, kinematics: &[BodyKinematics]) -> LoopConstraint {
+        match self {
+            Contact::Frame { body, frame } => {
+                LoopConstraint::new(None, *frame * kinematics[*body].pose, Some(*body), *frame)
+            }
+            Contact::Point { .. } => unreachable!(),
+        }
+    }
+}
+
+/// Contact normal followed by two unit tangents completing a right-handed basis
+pub fn tangent_basis(normal: TranslationVector) -> [TranslationVector; 3] {
+    let n = normal.to_array();
+    let least = (0..3)
+        .min_by(|a, b| n[*a].abs().total_cmp(&n[*b].abs()))
+        .unwrap();
+    let mut axis = [0.0; 3];
+    axis[least] = 1.0;
+    let t1 = normal.cross(TranslationVector::from_array(axis));
+    let t1 = t1 * (1.0 / t1.norm());
+    [normal, t1, normal.cross(t1)]
+}
+
+pub fn contact_jacobian(contacts: &[Contact], kinematics: &[BodyKinematics]) -> Matrix {
+    let dofs = kinematics.first().map_or(0, |kin| kin.jacobian.len());
+    contacts
+        .iter()
+        .fold(Matrix::new(0, dofs), |jacobian, contact| {
+            jacobian.stack(&contact.jacobian(kinematics))
+        })
+}
+
+/// Solve the KKT system of H qdd + C = tau + J^T lambda with the contacts held, J qdd = -dJ/dt qd
+pub fn forward_dynamics(
+    mass_matrix: &Matrix,
+    bias_forces: &[f64],
+    tau: &[f64],
+    contacts: &[Contact],
+    kinematics: &[BodyKinematics],
+) -> Option<(Vec<f64>, Vec<f64>)> {
+    let jacobian = contact_jacobian(contacts, kinematics);
+    let gamma: Vec<f64> = contacts
+        .iter()
+        .flat_map(|contact| contact.bias(kinematics))
+        .map(|b| -b)
+        .collect();
+    constraint::forward_dynamics(mass_matrix, bias_forces, tau, &jacobian, &gamma)
+}
+
+/// Post-impact velocities and contact impulses, with the normal velocity reversed and scaled by
+/// `restitution` and the remaining contact velocities brought to rest
+///
+/// Contacts already separating are left out and get zero impulses, as are contacts that would
+/// have to pull to stay at their target velocity.
+pub fn impact(
+    mass_matrix: &Matrix,
+    contacts: &[Contact],
+    kinematics: &[BodyKinematics],
+    qd: &[f64],
+    restitution: f64,
+) -> Option<(Vec<f64>, Vec<f64>)> {
+    let normals: Vec<f64> = contacts
+        .iter()
+        .map(|contact| contact.velocity(kinematics)[contact.normal_row()])
+        .collect();
+    let rows = contacts.iter().map(Contact::rows).sum();
+    let zeros = vec![0.0; qd.len()];
+    let momentum = mass_matrix.multiply_vector(qd);
+    let mut active: Vec<bool> = normals.iter().map(|normal| *normal < 0.0).collect();
+    loop {
+        let mut held = Vec::new();
+        let mut target = Vec::new();
+        for ((contact, normal), active) in contacts.iter().zip(&normals).zip(&active) {
+            if !*active {
+                continue;
+            }
+            held.push(contact.clone());
+            target.extend((0..contact.rows()).map(|row| {
+                if row == contact.normal_row() {
+                    -restitution * normal
+                } else {
+                    0.0
+                }
+            }));
+        }
+        if held.is_empty() {
+            return Some((qd.to_vec(), vec![0.0; rows]));
+        }
+        let jacobian = contact_jacobian(&held, kinematics);
+        let (after, solved) =
+            constraint::forward_dynamics(mass_matrix, &zeros, &momentum, &jacobian, &target)?;
+
+        let mut impulses = Vec::with_capacity(rows);
+        let mut solved = solved.into_iter();
+        for (contact, active) in contacts.iter().zip(&active) {
+            if *active {
+                impulses.extend(solved.by_ref().take(contact.rows()));
+            } else {
+                impulses.extend(std::iter::repeat_n(0.0, contact.rows()));
+            }
+        }
+
+        // a contact can only push, so release the one pulling hardest and solve again
+        let mut offset = 0;
+        let mut pulling: Option<(usize, f64)> = None;
+        for (i, contact) in contacts.iter().enumerate() {
+            let normal = impulses[offset + contact.normal_row()];
+            if active[i] && normal < pulling.map_or(0.0, |(_, least)| least) {
+                pulling = Some((i, normal));
+            }
+            offset += contact.rows();
+        }
+        match pulling {
+            Some((i, _)) => active[i] = false,
+            None => return Some((after, impulses)),
+        }
+    }
+}
+
+/// Split stacked contact forces into one world wrench per contact
+pub fn contact_wrenches(
+    contacts: &[Contact],
+    kinematics: &[BodyKinematics],
+    lambda: &[f64],
+) -> Vec<ForceVec6> {
+    let mut offset = 0;
+    contacts
+        .iter()
+        .map(|contact| {
+            let wrench = contact.wrench(kinematics, &lambda[offset..offset + contact.rows()]);
+            offset += contact.rows();
+            wrench
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MotionVec6;
+
+    // a point mass with q = [x, y, z] and the contact point at its center
+    fn particle(qd: [f64; 3]) -> Vec<BodyKinematics> {
+        let mut kin = BodyKinematics::new(TransformationMatrix::identity(), 3);
+        for (k, column) in kin.jacobian.iter_mut().enumerate() {
+            let mut motion = [0.0; 6];
+            motion[3 + k] = 1.0;
+            *column = MotionVec6::from_array(motion);
+        }
+        kin.velocity = MotionVec6::from_array([0.0, 0.0, 0.0, qd[0], qd[1], qd[2]]);
+        vec![kin]
+    }
+
+    fn ground() -> Contact {
+        Contact::point(
+            0,
+            TranslationVector::new(),
+            TranslationVector::from_array([0.0, 0.0, 1.0]),
+        )
+    }
+
+    #[test]
+    fn resting_contact_supports_weight() {
+        let kinematics = particle([0.0; 3]);
+        let mass_matrix = Matrix::from_diagonal(&[3.0; 3]);
+        let bias_forces = [0.0, 0.0, 3.0 * 9.81];
+        let (qdd, lambda) = forward_dynamics(
+            &mass_matrix,
+            &bias_forces,
+            &[0.0; 3],
+            &[ground()],
+            &kinematics,
+        )
+        .unwrap();
+
+        assert!(qdd.iter().all(|a| a.abs() < 1e-12));
+        let wrench = contact_wrenches(&[ground()], &kinematics, &lambda)[0];
+        assert!((wrench.translational_force()[2] - 3.0 * 9.81).abs() < 1e-12);
+    }
+
+    #[test]
+    fn impact_with_restitution() {
+        let kinematics = particle([1.0, 0.5, -2.0]);
+        let mass_matrix = Matrix::from_diagonal(&[3.0; 3]);
+        let (qd, impulse) = impact(
+            &mass_matrix,
+            &[ground()],
+            &kinematics,
+            &[1.0, 0.5, -2.0],
+            0.5,
+        )
+        .unwrap();
+
+        let expected = [0.0, 0.0, 1.0];
+        assert!(qd.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-12));
+        assert!((impulse[0] - 3.0 * 3.0).abs() < 1e-12);
+    }
+
+    // two point masses with q = [x0, y0, z0, x1, y1, z1], each touching the ground
+    fn two_particles(qd: [f64; 6]) -> (Vec<BodyKinematics>, [Contact; 2]) {
+        let mut kinematics = Vec::new();
+        for body in 0..2 {
+            let mut kin = BodyKinematics::new(TransformationMatrix::identity(), 6);
+            let mut velocity = [0.0; 6];
+            for k in 0..3 {
+                let mut motion = [0.0; 6];
+                motion[3 + k] = 1.0;
+                kin.jacobian[3 * body + k] = MotionVec6::from_array(motion);
+                velocity[3 + k] = qd[3 * body + k];
+            }
+            kin.velocity = MotionVec6::from_array(velocity);
+            kinematics.push(kin);
+        }
+        let normal = TranslationVector::from_array([0.0, 0.0, 1.0]);
+        let contacts = [
+            Contact::point(0, TranslationVector::new(), normal),
+            Contact::point(1, TranslationVector::new(), normal),
+        ];
+        (kinematics, contacts)
+    }
+
+    #[test]
+    fn impact_leaves_separating_contacts_alone() {
+        // the first falling onto the ground and the second lifting off it
+        let qd = [0.0, 0.0, -2.0, 0.0, 0.0, 1.0];
+        let (kinematics, contacts) = two_particles(qd);
+        let mass_matrix = Matrix::from_diagonal(&[3.0; 6]);
+        let (after, impulse) = impact(&mass_matrix, &contacts, &kinematics, &qd, 0.5).unwrap();
+
+        assert!((after[2] - 1.0).abs() < 1e-12);
+        assert!((after[5] - 1.0).abs() < 1e-12);
+        assert!((impulse[0] - 9.0).abs() < 1e-12);
+        assert_eq!(impulse.len(), 6);
+        assert!(impulse[3..].iter().all(|p| *p == 0.0));
+    }
+
+    #[test]
+    fn impact_releases_contacts_that_would_pull() {
+        // both falling, but coupled so that stopping the first throws the second upward
+        let qd = [0.0, 0.0, -2.0, 0.0, 0.0, -0.1];
+        let (kinematics, contacts) = two_particles(qd);
+        let mut mass_matrix = Matrix::from_diagonal(&[1.0; 6]);
+        mass_matrix[(2, 5)] = -0.9;
+        mass_matrix[(5, 2)] = -0.9;
+        let (after, impulse) = impact(&mass_matrix, &contacts, &kinematics, &qd, 0.0).unwrap();
+
+        assert!(after[2].abs() < 1e-9);
+        assert!((after[5] - 1.7).abs() < 1e-9);
+        assert!((impulse[0] - 0.38).abs() < 1e-9);
+        assert_eq!(impulse[3], 0.0);
+    }
+}
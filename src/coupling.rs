@@ -0,0 +1,333 @@
+use crate::{MotionVec6, RotationMatrix, TransformationMatrix, TranslationVector};
+
+/// Natural cubic spline through knots with strictly increasing abscissae, extended linearly
+/// beyond the end knots
+#[derive(Debug, Clone, PartialEq)]
+pub struct NaturalCubicSpline {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    // second derivatives at the knots
+    m: Vec<f64>,
+}
+
+/// Function of one independent coordinate driving a joint transform axis
+pub enum CouplingFunction {
+    Constant(f64),
+    Linear {
+        slope: f64,
+        intercept: f64,
+    },
+    /// coefficients of increasing powers
+    Polynomial(Vec<f64>),
+    Spline(NaturalCubicSpline),
+    /// returns the value and its first and second derivatives
+    Closure(Box<dyn Fn(f64) -> [f64; 3]>),
+}
+
+/// Rotation about, or translation along, a unit axis by a function of one coordinate
+pub struct TransformAxis {
+    pub axis: TranslationVector,
+    /// coordinate the function is evaluated at, `None` for a fixed offset at zero
+    pub coordinate: Option<usize>,
+    pub function: CouplingFunction,
+}
+
+/// Joint whose transform axes are functions of its coordinates, as in OpenSim's CustomJoint
+///
+/// The child frame is translated along the translation axes, given in parent coordinates, and
+/// then rotated about each rotation axis in turn, every axis given in the frame left by the
+/// rotations before it.
+pub struct CustomJoint {
+    pub coordinates: usize,
+    pub rotations: Vec<TransformAxis>,
+    pub translations: Vec<TransformAxis>,
+}
+
+impl NaturalCubicSpline {
+    pub fn new(x: Vec<f64>, y: Vec<f64>) -> Option<Self> {
+        let n = x.len();
+        if n < 2 || y.len() != n || x.windows(2).any(|w| w[1] <= w[0]) {
+            return None;
+        }
+        // tridiagonal system for the interior second derivatives, solved by forward elimination
+        let mut m = vec![0.0; n];
+        let mut diagonal = vec![0.0; n];
+        let mut rhs = vec![0.0; n];
+        for i in 1..n - 1 {
+            let (h0, h1) = (x[i] - x[i - 1], x[i + 1] - x[i]);
+            diagonal[i] = 2.0 * (h0 + h1);
+            rhs[i] = 6.0 * ((y[i + 1] - y[i]) / h1 - (y[i] - y[i - 1]) / h0);
+            if i > 1 {
+                let factor = h0 / diagonal[i - 1];
+                diagonal[i] -= factor * h0;
+                rhs[i] -= factor * rhs[i - 1];
+            }
+        }
+        for i in (1..n - 1).rev() {
+            let upper = if i + 1 < n - 1 {
+                (x[i + 1] - x[i]) * m[i + 1]
+            } else {
+                0.0
+            };
+            m[i] = (rhs[i] - upper) / diagonal[i];
+        }
+        Some(Self { x, y, m })
+    }
+
+    /// Value, first and second derivative at `x`
+    pub fn evaluate(&self, x: f64) -> [f64; 3] {
+        let n = self.x.len();
+        let first = self.interval(0, self.x[0]);
+        let last = self.interval(n - 2, self.x[n - 1]);
+        if x < self.x[0] {
+            return [self.y[0] + first[1] * (x - self.x[0]), first[1], 0.0];
+        }
+        if x > self.x[n - 1] {
+            return [self.y[n - 1] + last[1] * (x - self.x[n - 1]), last[1], 0.0];
+        }
+        let i = self.x.partition_point(|knot| *knot <= x).clamp(1, n - 1) - 1;
+        self.interval(i, x)
+    }
+
+    fn interval(&self, i: usize, x: f64) -> [f64; 3] {
+        let h = self.x[i + 1] - self.x[i];
+        let (a, b) = ((self.x[i + 1] - x) / h, (x - self.x[i]) / h);
+        let (m0, m1) = (self.m[i], self.m[i + 1]);
+        let (y0, y1) = (self.y[i], self.y[i + 1]);
+        [
+            a * y0 + b * y1 + ((a.powi(3) - a) * m0 + (b.powi(3) - b) * m1) * h * h / 6.0,
+            (y1 - y0) / h + ((1.0 - 3.0 * a * a) * m0 + (3.0 * b * b - 1.0) * m1) * h / 6.0,
+            a * m0 + b * m1,
+        ]
+    }
+}
+
+impl CouplingFunction {
+    /// Value, first and second derivative at `x`
+    pub fn evaluate(&self, x: f64) -> [f64; 3] {
+        match self {
+            CouplingFunction::Constant(value) => [*value, 0.0, 0.0],
+            CouplingFunction::Linear { slope, intercept } => [slope * x + intercept, *slope, 0.0],
+            CouplingFunction::Polynomial(coefficients) => {
+                // Horner's scheme carried through both derivatives
+                let mut result = [0.0; 3];
+                for c in coefficients.iter().rev() {
+                    result[2] = result[2] * x + 2.0 * result[1];
+                    result[1] = result[1] * x + result[0];
+                    result[0] = result[0] * x + c;
+                }
+                result
+            }
+            CouplingFunction::Spline(spline) => spline.evaluate(x),
+            CouplingFunction::Closure(function) => function(x),
+        }
+    }
+}
+
+impl TransformAxis {
+    pub fn new(
+        axis: TranslationVector,
+        coordinate: Option<usize>,
+        function: CouplingFunction,
+    ) -> Self {
+        Self {
+            axis: axis.normalize(),
+            coordinate,
+            function,
+        }
+    }
+
+    /// Axis driven one to one by a coordinate
+    pub fn coordinate(axis: TranslationVector, coordinate: usize) -> Self {
+        Self::new(
+            axis,
+            Some(coordinate),
+            CouplingFunction::Linear {
+                slope: 1.0,
+                intercept: 0.0,
+            },
+        )
+    }
+
+    fn evaluate(&self, q: &[f64]) -> [f64; 3] {
+        self.function
+            .evaluate(self.coordinate.map_or(0.0, |coordinate| q[coordinate]))
+    }
+}
+
+// one elementary step of a custom joint: its transform, its axis as a motion in the frame it
+// leads to, and the value and derivatives of its function
+struct Element {
+    transform: TransformationMatrix,
+    axis: MotionVec6,
+    coordinate: Option<usize>,
+    value: [f64; 3],
+}
+
+impl CustomJoint {
+    pub fn new(coordinates: usize) -> Self {
+        Self {
+            coordinates,
+            rotations: Vec::new(),
+            translations: Vec::new(),
+        }
+    }
+
+    pub fn rotation(mut self, axis: TransformAxis) -> Self {
+        self.rotations.push(axis);
+        self
+    }
+
+    pub fn translation(mut self, axis: TransformAxis) -> Self {
+        self.translations.push(axis);
+        self
+    }
+
+    /// Parent to child coordinate transform
+    pub fn transform(&self, q: &[f64]) -> TransformationMatrix {
+        self.elements(q)
+            .iter()
+            .fold(TransformationMatrix::identity(), |total, element| {
+                element.transform * total
+            })
+    }
+
+    /// Motion subspace in child coordinates, one column per coordinate
+    pub fn motion_subspace(&self, q: &[f64]) -> Vec<MotionVec6> {
+        let mut columns = vec![MotionVec6::new(); self.coordinates];
+        for element in self.elements(q) {
+            for column in columns.iter_mut() {
+                *column >>= element.transform;
+            }
+            if let Some(coordinate) = element.coordinate {
+                columns[coordinate] += element.axis * element.value[1];
+            }
+        }
+        columns
+    }
+
+    /// Joint velocity in child coordinates
+    pub fn velocity(&self, q: &[f64], qd: &[f64]) -> MotionVec6 {
+        self.motion_subspace(q)
+            .iter()
+            .zip(qd)
+            .fold(MotionVec6::new(), |total, (column, rate)| {
+                total + *column * *rate
+            })
+    }
+
+    /// The velocity-product acceleration c_J = dS/dt qd in child coordinates, including the
+    /// curvature of the coupling functions
+    pub fn bias_acceleration(&self, q: &[f64], qd: &[f64]) -> MotionVec6 {
+        let mut velocity = MotionVec6::new();
+        let mut acceleration = MotionVec6::new();
+        for element in self.elements(q) {
+            let rate = element.coordinate.map_or(0.0, |coordinate| qd[coordinate]);
+            let [_, slope, curvature] = element.value;
+            velocity = (velocity >> element.transform) + element.axis * (slope * rate);
+            acceleration = (acceleration >> element.transform)
+                + element.axis * (curvature * rate * rate)
+                + (velocity ^ element.axis) * (slope * rate);
+        }
+        acceleration
+    }
+
+    fn elements(&self, q: &[f64]) -> Vec<Element> {
+        let translations = self.translations.iter().map(|axis| {
+            let value = axis.evaluate(q);
+            let [x, y, z] = axis.axis.to_array();
+            Element {
+                transform: (axis.axis * value[0]).as_transform(),
+                axis: MotionVec6::from_array([0.0, 0.0, 0.0, x, y, z]),
+                coordinate: axis.coordinate,
+                value,
+            }
+        });
+        let rotations = self.rotations.iter().map(|axis| {
+            let value = axis.evaluate(q);
+            let [x, y, z] = axis.axis.to_array();
+            Element {
+                transform: RotationMatrix::from_axis_angle(axis.axis, value[0]).as_transform(),
+                axis: MotionVec6::from_array([x, y, z, 0.0, 0.0, 0.0]),
+                coordinate: axis.coordinate,
+                value,
+            }
+        });
+        translations.chain(rotations).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axis(x: f64, y: f64, z: f64) -> TranslationVector {
+        TranslationVector::from_array([x, y, z])
+    }
+
+    // planar knee with flexion q0, anteroposterior translation following a spline of it and an
+    // adduction q1 about a tilted axis
+    fn knee() -> CustomJoint {
+        let spline = NaturalCubicSpline::new(
+            vec![-2.0, -1.2, -0.5, 0.0, 0.5],
+            vec![-0.003, 0.0017, 0.0041, 0.0, -0.0032],
+        )
+        .unwrap();
+        CustomJoint::new(2)
+            .rotation(TransformAxis::coordinate(axis(0.0, 0.0, 1.0), 0))
+            .rotation(TransformAxis::new(
+                axis(1.0, 0.2, 0.0),
+                Some(1),
+                CouplingFunction::Polynomial(vec![0.0, 0.5, 0.3]),
+            ))
+            .translation(TransformAxis::new(
+                axis(1.0, 0.0, 0.0),
+                Some(0),
+                CouplingFunction::Spline(spline),
+            ))
+            .translation(TransformAxis::new(
+                axis(0.0, 1.0, 0.0),
+                None,
+                CouplingFunction::Constant(-0.4),
+            ))
+    }
+
+    #[test]
+    fn spline_interpolates_with_continuous_slope() {
+        let spline = NaturalCubicSpline::new(vec![0.0, 1.0, 3.0], vec![0.0, 2.0, 1.0]).unwrap();
+        assert!((spline.evaluate(1.0)[0] - 2.0).abs() < 1e-12);
+        let (left, right) = (spline.evaluate(1.0 - 1e-9), spline.evaluate(1.0 + 1e-9));
+        assert!((left[1] - right[1]).abs() < 1e-6);
+        assert!(spline.evaluate(0.0)[2].abs() < 1e-12);
+        assert!((spline.evaluate(4.0)[0] - (1.0 + spline.evaluate(3.0)[1])).abs() < 1e-12);
+    }
+
+    #[test]
+    fn subspace_and_bias_match_finite_differences() {
+        let joint = knee();
+        let (q, qd) = ([-0.8, 0.3], [1.5, -0.7]);
+        let subspace = joint.motion_subspace(&q);
+        let h = 1e-6;
+
+        // the linear columns carry the child origin velocity in child coordinates
+        let origin = |q: &[f64]| joint.transform(q).to_translation();
+        let rotation = !joint.transform(&q).to_rotation();
+        for (k, column) in subspace.iter().enumerate() {
+            let mut ahead = q;
+            let mut behind = q;
+            ahead[k] += h;
+            behind[k] -= h;
+            let slope = (origin(&ahead) - origin(&behind)) * (0.5 / h);
+            let linear = rotation * TranslationVector::from_array(column.translational_motion());
+            assert!((slope - linear).norm() < 1e-8);
+        }
+
+        let step = |s: f64| [q[0] + s * qd[0], q[1] + s * qd[1]];
+        let difference =
+            (joint.velocity(&step(h), &qd) - joint.velocity(&step(-h), &qd)) * (0.5 / h);
+        let bias = joint.bias_acceleration(&q, &qd);
+        let error = difference - bias;
+        assert!(error.rotational_motion().iter().all(|e| e.abs() < 1e-7));
+        assert!(error.translational_motion().iter().all(|e| e.abs() < 1e-7));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::body::BodyKinematics;
+use crate::c3d::C3d;
+use crate::{ForceVec6, RotationMatrix, TransformationMatrix, TranslationVector};
+
+/// Channel layout of a force plate, following the C3D FORCE_PLATFORM:TYPE numbering
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlateType {
+    /// Fx, Fy, Fz, COPx, COPy, Tz
+    Type1,
+    /// Fx, Fy, Fz, Mx, My, Mz about the transducer origin
+    Type2,
+    /// Kistler piezoelectric: Fx12, Fx34, Fy14, Fy23, Fz1, Fz2, Fz3, Fz4
+    Type3,
+    /// as type 2 after the calibration matrix is applied to the channels
+    Type4([[f64; 6]; 6]),
+}
+
+impl PlateType {
+    /// Number of analog channels the plate outputs
+    pub fn outputs(&self) -> usize {
+        match self {
+            PlateType::Type3 => 8,
+            _ => 6,
+        }
+    }
+}
+
+/// Force plate fed by analog channels
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForcePlate {
+    pub kind: PlateType,
+    /// analog channel of each plate output, in the order of its type
+    pub channels: Vec<usize>,
+    /// factor bringing each channel to SI units
+    pub scales: Vec<f64>,
+    /// surface corners in world coordinates, in the C3D order: corner 1 on the +x +y quadrant
+    /// of the plate, then counterclockwise
+    pub corners: [TranslationVector; 4],
+    /// for types 2 and 4 the surface center relative to the transducer origin, for type 3 the
+    /// sensor offsets a and b and the surface height az0 above the sensors, in plate coordinates
+    pub origin: TranslationVector,
+    /// vertical force below which the foot is taken to be off the plate
+    pub threshold: f64,
+}
+
+/// Ground reaction on the subject standing on a plate, in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroundReaction {
+    pub force: TranslationVector,
+    pub center_of_pressure: TranslationVector,
+    /// moment about the plate normal through the center of pressure
+    pub free_moment: TranslationVector,
+    /// the whole reaction as a wrench about the world origin
+    pub wrench: ForceVec6,
+}
+
+impl ForcePlate {
+    pub fn new(
+        kind: PlateType,
+        channels: Vec<usize>,
+        corners: [TranslationVector; 4],
+        origin: TranslationVector,
+    ) -> Self {
+        Self {
+            kind,
+            scales: vec![1.0; channels.len()],
+            channels,
+            corners,
+            origin,
+            threshold: 10.0,
+        }
+    }
+
+    pub fn scales(mut self, scales: Vec<f64>) -> Self {
+        self.scales = scales;
+        self
+    }
+
+    pub fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Plates of the FORCE_PLATFORM group, with channel units and lengths brought to SI
+    pub fn from_c3d(c3d: &C3d) -> Vec<Self> {
+        let count = c3d
+            .values("FORCE_PLATFORM", "USED")
+            .first()
+            .map_or(0, |n| *n as usize);
+        let types = c3d.values("FORCE_PLATFORM", "TYPE");
+        let corners = c3d.values("FORCE_PLATFORM", "CORNERS");
+        let origins = c3d.values("FORCE_PLATFORM", "ORIGIN");
+        let channels = c3d.values("FORCE_PLATFORM", "CHANNEL");
+        let calibration = c3d.values("FORCE_PLATFORM", "CAL_MATRIX");
+        let width = c3d
+            .parameter("FORCE_PLATFORM", "CHANNEL")
+            .and_then(|parameter| parameter.dimensions.first().copied())
+            .unwrap_or(6);
+        let length = c3d.length_scale();
+        let analog_scales = c3d.analog_scales();
+        let point = |values: &[f64], at: usize| {
+            TranslationVector::from_array([values[at], values[at + 1], values[at + 2]]) * length
+        };
+
+        let mut plates = Vec::new();
+        for plate in 0..count {
+            let kind = match types.get(plate).map(|t| *t as i32) {
+                Some(1) => PlateType::Type1,
+                Some(2) => PlateType::Type2,
+                Some(3) => PlateType::Type3,
+                Some(4) => {
+                    // stored column by column
+                    let mut matrix = [[0.0; 6]; 6];
+                    for (k, value) in calibration.iter().skip(36 * plate).take(36).enumerate() {
+                        matrix[k % 6][k / 6] = *value;
+                    }
+                    PlateType::Type4(matrix)
+                }
+                _ => continue,
+            };
+            let outputs = kind.outputs();
+            if corners.len() < 12 * (plate + 1)
+                || origins.len() < 3 * (plate + 1)
+                || channels.len() < width * plate + outputs
+            {
+                continue;
+            }
+            // channel numbers count from one
+            let plate_channels: Vec<usize> = channels[width * plate..width * plate + outputs]
+                .iter()
+                .map(|channel| (*channel as usize).saturating_sub(1))
+                .collect();
+            let scales = plate_channels
+                .iter()
+                .map(|channel| analog_scales.get(*channel).copied().unwrap_or(1.0))
+                .collect();
+            let corner = |k: usize| point(&corners, 12 * plate + 3 * k);
+            plates.push(
+                ForcePlate::new(
+                    kind,
+                    plate_channels,
+                    [corner(0), corner(1), corner(2), corner(3)],
+                    point(&origins, 3 * plate),
+                )
+                .scales(scales),
+            );
+        }
+        plates
+    }
+
+    /// Coordinate transform from world to the plate frame at the surface center
+    pub fn pose(&self) -> TransformationMatrix {
+        let [c1, c2, c3, c4] = self.corners;
+        let x = (c1 - c2).normalize();
+        let z = x.cross(c1 - c4).normalize();
+        let y = z.cross(x);
+        let [x, y, z] = [x.to_array(), y.to_array(), z.to_array()];
+        let rotation =
+            RotationMatrix::from_array([x[0], x[1], x[2], y[0], y[1], y[2], z[0], z[1], z[2]]);
+        rotation + (c1 + c2 + c3 + c4) * 0.25
+    }
+
+    /// Load applied to the plate as a wrench about the surface center in plate coordinates
+    ///
+    /// Returns `None` if the plate has fewer channels or scales than its type outputs, or if a
+    /// channel is missing from `sample`.
+    pub fn load(&self, sample: &[f64]) -> Option<ForceVec6> {
+        let mut values: Vec<f64> = self
+            .channels
+            .iter()
+            .zip(&self.scales)
+            .map(|(channel, scale)| sample.get(*channel).map(|value| value * scale))
+            .collect::<Option<_>>()?;
+        if values.len() < self.kind.outputs() {
+            return None;
+        }
+        if let PlateType::Type4(matrix) = self.kind {
+            values = matrix
+                .iter()
+                .map(|row| row.iter().zip(&values).map(|(c, v)| c * v).sum())
+                .collect();
+        }
+        let [a, b, az0] = self.origin.to_array();
+        let (force, moment) = match self.kind {
+            PlateType::Type1 => {
+                let force = TranslationVector::from_array([values[0], values[1], values[2]]);
+                let pressure = TranslationVector::from_array([values[3], values[4], 0.0]);
+                let free = TranslationVector::from_array([0.0, 0.0, values[5]]);
+                (force, pressure.cross(force) + free)
+            }
+            PlateType::Type2 | PlateType::Type4(_) => {
+                let force = TranslationVector::from_array([values[0], values[1], values[2]]);
+                let moment = TranslationVector::from_array([values[3], values[4], values[5]]);
+                (force, moment - self.origin.cross(force))
+            }
+            PlateType::Type3 => {
+                let [fx12, fx34, fy14, fy23, fz1, fz2, fz3, fz4] = values[..8] else {
+                    unreachable!()
+                };
+                let (fx, fy) = (fx12 + fx34, fy14 + fy23);
+                let force = TranslationVector::from_array([fx, fy, fz1 + fz2 + fz3 + fz4]);
+                // sensor moments carried up from the sensor plane to the surface
+                let moment = TranslationVector::from_array([
+                    b * (fz1 + fz2 - fz3 - fz4) + az0 * fy,
+                    a * (-fz1 + fz2 + fz3 - fz4) - az0 * fx,
+                    b * (fx34 - fx12) + a * (fy14 - fy23),
+                ]);
+                (force, moment)
+            }
+        };
+        let [mx, my, mz] = moment.to_array();
+        let [fx, fy, fz] = force.to_array();
+        Some(ForceVec6::from_array([mx, my, mz, fx, fy, fz]))
+    }
+
+    /// Reaction on the subject for one analog sample, `None` while the plate is unloaded or when
+    /// the sample cannot be read
+    pub fn reaction(&self, sample: &[f64]) -> Option<GroundReaction> {
+        let load = self.load(sample)?;
+        let [mx, my, mz] = load.rotational_force();
+        let [fx, fy, fz] = load.translational_force();
+        if fz.abs() < self.threshold {
+            return None;
+        }
+        let (x, y) = (-my / fz, mx / fz);
+        let torque = mz - (x * fy - y * fx);
+
+        let to_world = self.pose().inverse_transform();
+        let rotation = !self.pose().to_rotation();
+        Some(GroundReaction {
+            force: -(rotation * TranslationVector::from_array([fx, fy, fz])),
+            center_of_pressure: to_world
+                .transform_point(TranslationVector::from_array([x, y, 0.0])),
+            free_moment: -(rotation * TranslationVector::from_array([0.0, 0.0, torque])),
+            wrench: -load >> to_world,
+        })
+    }
+}
+
+/// Wrench of each loaded plate on the foot closest to its center of pressure, with every foot
+/// given as a body and a point on it such as the midfoot
+pub fn assign_nearest(
+    reactions: &[Option<GroundReaction>],
+    feet: &[(usize, TranslationVector)],
+    kinematics: &[BodyKinematics],
+) -> Vec<(usize, ForceVec6)> {
+    let mapping: Vec<Option<usize>> = reactions
+        .iter()
+        .map(|reaction| {
+            let reaction = reaction.as_ref()?;
+            let distance = |(body, point): &(usize, TranslationVector)| {
+                (kinematics[*body].point_position(*point) - reaction.center_of_pressure).norm()
+            };
+            feet.iter()
+                .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+                .map(|(body, _)| *body)
+        })
+        .collect();
+    assign_mapped(reactions, &mapping)
+}
+
+/// Wrench of each loaded plate on the body mapped to it
+pub fn assign_mapped(
+    reactions: &[Option<GroundReaction>],
+    mapping: &[Option<usize>],
+) -> Vec<(usize, ForceVec6)> {
+    let mut wrenches: Vec<(usize, ForceVec6)> = Vec::new();
+    for (reaction, body) in reactions.iter().zip(mapping) {
+        let (Some(reaction), Some(body)) = (reaction, body) else {
+            continue;
+        };
+        match wrenches.iter_mut().find(|(b, _)| b == body) {
+            Some((_, total)) => *total += reaction.wrench,
+            None => wrenches.push((*body, reaction.wrench)),
+        }
+    }
+    wrenches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(x: f64, y: f64, z: f64) -> TranslationVector {
+        TranslationVector::from_array([x, y, z])
+    }
+
+    #[test]
+    fn plate_types_agree_on_the_reaction() {
+        // plate z points down into the floor, its y axis against the world y axis
+        let center = vector(0.3, 0.2, 0.0);
+        let corners = [
+            center + vector(0.2, -0.3, 0.0),
+            center + vector(-0.2, -0.3, 0.0),
+            center + vector(-0.2, 0.3, 0.0),
+            center + vector(0.2, 0.3, 0.0),
+        ];
+        // load in plate coordinates with its center of pressure and free moment
+        let force = vector(30.0, 20.0, 700.0);
+        let pressure = vector(0.05, -0.08, 0.0);
+        let torque = 3.0;
+        let moment = pressure.cross(force) + vector(0.0, 0.0, torque);
+        let [fx, fy, fz] = force.to_array();
+
+        let origin = vector(0.001, -0.002, -0.04);
+        let [mx, my, mz] = (moment + origin.cross(force)).to_array();
+        let type2 = [fx, fy, fz, mx, my, mz];
+
+        let (a, b, az0) = (0.12, 0.2, -0.04);
+        let [sx, sy, sz] = (moment - vector(az0 * fy, -az0 * fx, 0.0)).to_array();
+        let (u, v) = (sx / b, sy / a);
+        let type3 = [
+            fx / 2.0 - sz / (2.0 * b),
+            fx / 2.0 + sz / (2.0 * b),
+            fy / 2.0,
+            fy / 2.0,
+            (fz + u - v) / 4.0,
+            (fz + u + v) / 4.0,
+            (fz - u + v) / 4.0,
+            (fz - u - v) / 4.0,
+        ];
+
+        let mut calibration = [[0.0; 6]; 6];
+        (0..6).for_each(|i| calibration[i][i] = 2.0);
+        let plates = [
+            (
+                ForcePlate::new(
+                    PlateType::Type1,
+                    (0..6).collect(),
+                    corners,
+                    vector(0.0, 0.0, 0.0),
+                ),
+                vec![fx, fy, fz, 0.05, -0.08, torque],
+            ),
+            (
+                ForcePlate::new(PlateType::Type2, (0..6).collect(), corners, origin),
+                type2.to_vec(),
+            ),
+            (
+                ForcePlate::new(
+                    PlateType::Type3,
+                    (0..8).collect(),
+                    corners,
+                    vector(a, b, az0),
+                ),
+                type3.to_vec(),
+            ),
+            (
+                ForcePlate::new(
+                    PlateType::Type4(calibration),
+                    (0..6).collect(),
+                    corners,
+                    origin,
+                ),
+                type2.iter().map(|value| value / 2.0).collect(),
+            ),
+        ];
+        let expected_force = vector(-30.0, 20.0, 700.0);
+        let expected_pressure = vector(0.35, 0.28, 0.0);
+        let expected_free = vector(0.0, 0.0, 3.0);
+        for (plate, sample) in &plates {
+            let reaction = plate.reaction(sample).unwrap();
+            assert!((reaction.force - expected_force).norm() < 1e-9);
+            assert!((reaction.center_of_pressure - expected_pressure).norm() < 1e-9);
+            assert!((reaction.free_moment - expected_free).norm() < 1e-9);
+            let [nx, ny, nz] = expected_free.to_array();
+            let expected = ForceVec6::from_point_force(expected_pressure, expected_force)
+                + ForceVec6::from_array([nx, ny, nz, 0.0, 0.0, 0.0]);
+            let error = reaction.wrench - expected;
+            assert!(error.rotational_force().iter().all(|e| e.abs() < 1e-9));
+            assert!(error.translational_force().iter().all(|e| e.abs() < 1e-9));
+        }
+
+        // the reaction goes to the foot standing on the plate
+        let feet = [
+            BodyKinematics::new(vector(1.0, 0.0, 0.05).as_transform(), 0),
+            BodyKinematics::new(vector(0.3, 0.3, 0.05).as_transform(), 0),
+        ];
+        let reaction = plates[1].0.reaction(&plates[1].1);
+        let points = [(0, vector(0.0, 0.0, 0.0)), (1, vector(0.0, 0.0, 0.0))];
+        let wrenches = assign_nearest(&[reaction, None], &points, &feet);
+        assert_eq!(wrenches.len(), 1);
+        assert_eq!(wrenches[0].0, 1);
+    }
+
+    #[test]
+    fn malformed_plates_have_no_load() {
+        let corners = [
+            vector(0.2, -0.3, 0.0),
+            vector(-0.2, -0.3, 0.0),
+            vector(-0.2, 0.3, 0.0),
+            vector(0.2, 0.3, 0.0),
+        ];
+        let sample = [0.0, 0.0, 700.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+
+        let short = ForcePlate::new(
+            PlateType::Type2,
+            (0..5).collect(),
+            corners,
+            vector(0.0, 0.0, 0.0),
+        );
+        assert!(short.load(&sample).is_none());
+        let kistler = ForcePlate::new(
+            PlateType::Type3,
+            (0..6).collect(),
+            corners,
+            vector(0.0, 0.0, 0.0),
+        );
+        assert!(kistler.load(&sample).is_none());
+        let plate = ForcePlate::new(
+            PlateType::Type1,
+            (0..6).collect(),
+            corners,
+            vector(0.0, 0.0, 0.0),
+        );
+        assert!(plate.load(&sample).is_some());
+        assert!(plate.reaction(&sample[..4]).is_none());
+    }
+}
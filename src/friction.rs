@@ -0,0 +1,287 @@
+use crate::body::BodyKinematics;
+use crate::constraint;
+use crate::contact::{contact_jacobian, tangent_basis, Contact};
+use crate::linalg::Matrix;
+use crate::ForceVec6;
+
+/// Approximation of the Coulomb friction cone used by the solver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrictionCone {
+    /// each tangential impulse is bounded by mu times the normal impulse
+    Pyramid,
+    /// the tangential impulse magnitude is bounded by mu times the normal impulse
+    Cone,
+}
+
+/// Projected Gauss-Seidel solver for frictional point contacts at the velocity level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrictionSolver {
+    cone: FrictionCone,
+    iterations: usize,
+    tolerance: f64,
+}
+
+/// World wrenches about the origin exerted on a body at one contact
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactWrench {
+    pub normal: ForceVec6,
+    pub tangential: ForceVec6,
+}
+
+impl FrictionSolver {
+    pub fn new() -> Self {
+        Self {
+            cone: FrictionCone::Cone,
+            iterations: 200,
+            tolerance: 1e-12,
+        }
+    }
+
+    pub fn cone(mut self, cone: FrictionCone) -> Self {
+        self.cone = cone;
+        self
+    }
+
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Largest change of any impulse in a sweep at which the iterations stop
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Contact impulses, normal then two tangents per contact, for the contact velocities
+    /// `delassus * impulses + free_velocity`, or `None` unless there is one friction coefficient
+    /// per contact
+    pub fn solve_impulses(
+        &self,
+        delassus: &Matrix,
+        free_velocity: &[f64],
+        friction: &[f64],
+    ) -> Option<Vec<f64>> {
+        let rows = free_velocity.len();
+        if 3 * friction.len() != rows || delassus.rows() != rows || delassus.cols() != rows {
+            return None;
+        }
+        let mut impulses = vec![0.0; free_velocity.len()];
+        let velocity = |impulses: &[f64], row: usize| {
+            free_velocity[row]
+                + delassus
+                    .row(row)
+                    .iter()
+                    .zip(impulses)
+                    .map(|(w, l)| w * l)
+                    .sum::<f64>()
+        };
+
+        for _ in 0..self.iterations {
+            let mut change = 0.0_f64;
+            for (contact, mu) in friction.iter().enumerate() {
+                let n = 3 * contact;
+                let previous = [impulses[n], impulses[n + 1], impulses[n + 2]];
+
+                if delassus[(n, n)] > 0.0 {
+                    let normal = impulses[n] - velocity(&impulses, n) / delassus[(n, n)];
+                    impulses[n] = normal.max(0.0);
+                }
+                for t in n + 1..n + 3 {
+                    if delassus[(t, t)] > 0.0 {
+                        impulses[t] -= velocity(&impulses, t) / delassus[(t, t)];
+                    }
+                }
+
+                let limit = mu * impulses[n];
+                match self.cone {
+                    FrictionCone::Pyramid => {
+                        for impulse in &mut impulses[n + 1..n + 3] {
+                            *impulse = impulse.clamp(-limit, limit);
+                        }
+                    }
+                    FrictionCone::Cone => {
+                        let magnitude = impulses[n + 1].hypot(impulses[n + 2]);
+                        if magnitude > limit {
+                            let scale = if magnitude > 0.0 {
+                                limit / magnitude
+                            } else {
+                                0.0
+                            };
+                            impulses[n + 1] *= scale;
+                            impulses[n + 2] *= scale;
+                        }
+                    }
+                }
+
+                for (i, old) in previous.iter().enumerate() {
+                    change = change.max((impulses[n + i] - old).abs());
+                }
+            }
+            if change <= self.tolerance {
+                break;
+            }
+        }
+        Some(impulses)
+    }
+
+    /// Advance `qd` over `dt` with the point `contacts` resolved under Coulomb friction, returning
+    /// the new velocities and the average contact wrenches over the step
+    ///
+    /// Returns `None` for frame contacts, which the solver does not handle, or without one
+    /// friction coefficient per contact.
+    #[allow(clippy::too_many_arguments)]
+    pub fn step(
+        &self,
+        mass_matrix: &Matrix,
+        bias_forces: &[f64],
+        tau: &[f64],
+        contacts: &[Contact],
+        friction: &[f64],
+        kinematics: &[BodyKinematics],
+        qd: &[f64],
+        dt: f64,
+    ) -> Option<(Vec<f64>, Vec<ContactWrench>)> {
+        if friction.len() != contacts.len()
+            || contacts
+                .iter()
+                .any(|contact| matches!(contact, Contact::Frame { .. }))
+        {
+            return None;
+        }
+        let rhs: Vec<f64> = tau.iter().zip(bias_forces).map(|(t, c)| t - c).collect();
+        let acceleration = mass_matrix.cholesky_solve(&rhs)?;
+        let free: Vec<f64> = qd
+            .iter()
+            .zip(acceleration)
+            .map(|(v, a)| v + dt * a)
+            .collect();
+
+        let jacobian = contact_jacobian(contacts, kinematics);
+        let mobility = constraint::mobility(mass_matrix, &jacobian)?;
+        let delassus = &jacobian * &mobility;
+        let impulses =
+            self.solve_impulses(&delassus, &jacobian.multiply_vector(&free), friction)?;
+
+        let qd = free
+            .iter()
+            .zip(mobility.multiply_vector(&impulses))
+            .map(|(v, dv)| v + dv)
+            .collect();
+        let wrenches = contacts
+            .iter()
+            .enumerate()
+            .map(|(i, contact)| match contact {
+                Contact::Point {
+                    body,
+                    point,
+                    normal,
+                } => {
+                    let [n, t1, t2] = tangent_basis(*normal);
+                    let position = kinematics[*body].point_position(*point);
+                    let force = |direction| ForceVec6::from_point_force(position, direction);
+                    let lambda = &impulses[3 * i..3 * i + 3];
+                    ContactWrench {
+                        normal: force(n * (lambda[0] / dt)),
+                        tangential: force((t1 * lambda[1] + t2 * lambda[2]) * (1.0 / dt)),
+                    }
+                }
+                Contact::Frame { .. } => unreachable!(),
+            })
+            .collect();
+        Some((qd, wrenches))
+    }
+}
+
+impl Default for FrictionSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MotionVec6, TransformationMatrix, TranslationVector};
+
+    const MASS: f64 = 2.0;
+    const GRAVITY: f64 = 9.81;
+
+    // a point mass with q = [x, y, z] resting on the ground at its center
+    fn block() -> (BodyKinematics, Contact) {
+        let mut kin = BodyKinematics::new(TransformationMatrix::identity(), 3);
+        for (k, column) in kin.jacobian.iter_mut().enumerate() {
+            let mut motion = [0.0; 6];
+            motion[3 + k] = 1.0;
+            *column = MotionVec6::from_array(motion);
+        }
+        let contact = Contact::point(
+            0,
+            TranslationVector::new(),
+            TranslationVector::from_array([0.0, 0.0, 1.0]),
+        );
+        (kin, contact)
+    }
+
+    fn step(
+        solver: FrictionSolver,
+        contact: Contact,
+        friction: &[f64],
+        speed: f64,
+    ) -> Option<(Vec<f64>, Vec<ContactWrench>)> {
+        solver.step(
+            &Matrix::from_diagonal(&[MASS; 3]),
+            &[0.0, 0.0, MASS * GRAVITY],
+            &[0.0; 3],
+            &[contact],
+            friction,
+            &[block().0],
+            &[speed, 0.0, 0.0],
+            0.01,
+        )
+    }
+
+    fn sliding_block(cone: FrictionCone, speed: f64) -> (Vec<f64>, ContactWrench) {
+        let (qd, wrenches) =
+            step(FrictionSolver::new().cone(cone), block().1, &[0.5], speed).unwrap();
+        (qd, wrenches[0])
+    }
+
+    #[test]
+    fn sliding_block_decelerates() {
+        for cone in [FrictionCone::Pyramid, FrictionCone::Cone] {
+            let (qd, wrench) = sliding_block(cone, 2.0);
+            assert!((qd[0] - (2.0 - 0.5 * GRAVITY * 0.01)).abs() < 1e-9);
+            assert!(qd[2].abs() < 1e-9);
+            assert!((wrench.normal.translational_force()[2] - MASS * GRAVITY).abs() < 1e-9);
+            let friction = wrench.tangential.translational_force();
+            assert!((friction[0] + 0.5 * MASS * GRAVITY).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn slow_block_sticks() {
+        let (qd, wrench) = sliding_block(FrictionCone::Cone, 0.01);
+        assert!(qd.iter().all(|v| v.abs() < 1e-9));
+        let friction = wrench.tangential.translational_force();
+        assert!((friction[0] + MASS * 0.01 / 0.01).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_frame_contacts_and_missing_coefficients() {
+        let solver = FrictionSolver::new();
+        assert!(step(solver, block().1, &[], 1.0).is_none());
+        assert!(step(solver, block().1, &[0.5, 0.5], 1.0).is_none());
+        let frame = Contact::frame(0, TransformationMatrix::identity());
+        assert!(step(solver, frame, &[0.5], 1.0).is_none());
+
+        let delassus = Matrix::from_diagonal(&[1.0; 3]);
+        assert!(solver
+            .solve_impulses(&delassus, &[0.0, 0.0, -1.0], &[])
+            .is_none());
+        let impulses = solver
+            .solve_impulses(&delassus, &[-1.0, 0.0, 0.0], &[0.5])
+            .unwrap();
+        assert!((impulses[0] - 1.0).abs() < 1e-12);
+    }
+}
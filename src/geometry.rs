@@ -0,0 +1,643 @@
+use crate::body::BodyKinematics;
+use crate::{RotationMatrix, TransformationMatrix, TranslationVector};
+
+const MAX_ITERATIONS: usize = 128;
+const TOLERANCE: f64 = 1e-10;
+const EPA_TOLERANCE: f64 = 1e-8;
+
+/// Collision shape in its own frame
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Sphere {
+        radius: f64,
+    },
+    /// segment along the z axis swept by a sphere
+    Capsule {
+        radius: f64,
+        half_length: f64,
+    },
+    Box {
+        half_extents: TranslationVector,
+    },
+    /// axis along z
+    Cylinder {
+        radius: f64,
+        half_length: f64,
+    },
+    Ellipsoid {
+        radii: TranslationVector,
+    },
+    /// the region z <= 0
+    HalfSpace,
+    /// convex hull of the vertices, with the triangles kept for export
+    ConvexMesh {
+        vertices: Vec<TranslationVector>,
+        triangles: Vec<[usize; 3]>,
+    },
+}
+
+/// Shape attached to a body, or to the world when `body` is `None`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collider {
+    pub body: Option<usize>,
+    /// body to shape transform
+    pub offset: TransformationMatrix,
+    pub shape: Shape,
+}
+
+/// Result of a distance query between two shapes a and b, in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Proximity {
+    /// separation distance, negative when the shapes overlap
+    pub distance: f64,
+    /// closest point on a, or its deepest point inside b
+    pub point_a: TranslationVector,
+    pub point_b: TranslationVector,
+    /// unit contact normal pointing from a to b
+    pub normal: TranslationVector,
+}
+
+impl Shape {
+    /// Convex hull of `vertices`, or `None` without vertices or with triangles indexing past
+    /// them
+    pub fn convex_mesh(
+        vertices: Vec<TranslationVector>,
+        triangles: Vec<[usize; 3]>,
+    ) -> Option<Self> {
+        if vertices.is_empty() || triangles.iter().flatten().any(|i| *i >= vertices.len()) {
+            return None;
+        }
+        Some(Shape::ConvexMesh {
+            vertices,
+            triangles,
+        })
+    }
+
+    // farthest point of the shape along `direction`, in shape coordinates, for shapes other
+    // than half-spaces
+    pub(crate) fn support(&self, direction: TranslationVector) -> TranslationVector {
+        let margin = self.margin();
+        let core = self.core_support(direction);
+        let length = direction.norm();
+        if margin > 0.0 && length > 0.0 {
+            core + direction * (margin / length)
+        } else {
+            core
+        }
+    }
+
+    // spheres and capsules are a point and a segment inflated by their radius
+    fn margin(&self) -> f64 {
+        match self {
+            Shape::Sphere { radius } | Shape::Capsule { radius, .. } => *radius,
+            _ => 0.0,
+        }
+    }
+
+    fn core_support(&self, direction: TranslationVector) -> TranslationVector {
+        let d = direction.to_array();
+        let sign = |x: f64| if x < 0.0 { -1.0 } else { 1.0 };
+        match self {
+            Shape::Sphere { .. } => TranslationVector::new(),
+            Shape::Capsule { half_length, .. } => {
+                TranslationVector::from_array([0.0, 0.0, sign(d[2]) * half_length])
+            }
+            Shape::Box { half_extents } => {
+                let h = half_extents.to_array();
+                TranslationVector::from_array([
+                    sign(d[0]) * h[0],
+                    sign(d[1]) * h[1],
+                    sign(d[2]) * h[2],
+                ])
+            }
+            Shape::Cylinder {
+                radius,
+                half_length,
+            } => {
+                let radial = d[0].hypot(d[1]);
+                let (x, y) = if radial > 0.0 {
+                    (radius * d[0] / radial, radius * d[1] / radial)
+                } else {
+                    (0.0, 0.0)
+                };
+                TranslationVector::from_array([x, y, sign(d[2]) * half_length])
+            }
+            Shape::Ellipsoid { radii } => {
+                let r = radii.to_array();
+                let scaled = [r[0] * r[0] * d[0], r[1] * r[1] * d[1], r[2] * r[2] * d[2]];
+                let length = (scaled[0] * d[0] + scaled[1] * d[1] + scaled[2] * d[2]).sqrt();
+                if length > 0.0 {
+                    TranslationVector::from_array(scaled) * (1.0 / length)
+                } else {
+                    TranslationVector::from_array([r[0], 0.0, 0.0])
+                }
+            }
+            Shape::HalfSpace => panic!("a half-space has no support point"),
+            // an empty mesh collapses to its origin
+            Shape::ConvexMesh { vertices, .. } => vertices
+                .iter()
+                .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+                .copied()
+                .unwrap_or_else(TranslationVector::new),
+        }
+    }
+}
+
+impl Collider {
+    pub fn new(body: Option<usize>, shape: Shape, offset: TransformationMatrix) -> Self {
+        Self {
+            body,
+            offset,
+            shape,
+        }
+    }
+
+    /// World to shape transform
+    pub fn pose(&self, kinematics: &[BodyKinematics]) -> TransformationMatrix {
+        match self.body {
+            Some(i) => self.offset * kinematics[i].pose,
+            None => self.offset,
+        }
+    }
+
+    pub fn distance(&self, other: &Collider, kinematics: &[BodyKinematics]) -> Option<Proximity> {
+        distance(
+            &self.shape,
+            self.pose(kinematics),
+            &other.shape,
+            other.pose(kinematics),
+        )
+    }
+}
+
+/// Signed distance, closest points and normal between two shapes given their world to shape
+/// transforms, or `None` for a pair of half-spaces or a mesh without vertices
+pub fn distance(
+    shape_a: &Shape,
+    pose_a: TransformationMatrix,
+    shape_b: &Shape,
+    pose_b: TransformationMatrix,
+) -> Option<Proximity> {
+    let empty =
+        |shape: &Shape| matches!(shape, Shape::ConvexMesh { vertices, .. } if vertices.is_empty());
+    if empty(shape_a) || empty(shape_b) {
+        return None;
+    }
+    let a = Placed::new(shape_a, pose_a);
+    let b = Placed::new(shape_b, pose_b);
+    match (shape_a, shape_b) {
+        (Shape::HalfSpace, Shape::HalfSpace) => None,
+        (Shape::HalfSpace, _) => Some(half_space_distance(&a, &b)),
+        (_, Shape::HalfSpace) => {
+            let flipped = half_space_distance(&b, &a);
+            Some(Proximity {
+                distance: flipped.distance,
+                point_a: flipped.point_b,
+                point_b: flipped.point_a,
+                normal: -flipped.normal,
+            })
+        }
+        _ => Some(convex_distance(&a, &b)),
+    }
+}
+
+/// Distance from a world point to a shape, negative inside, with `point_b` the query point
+pub fn point_distance(
+    shape: &Shape,
+    pose: TransformationMatrix,
+    point: TranslationVector,
+) -> Option<Proximity> {
+    distance(
+        shape,
+        pose,
+        &Shape::Sphere { radius: 0.0 },
+        point.as_transform(),
+    )
+}
+
+// shape with its world placement
+struct Placed<'a> {
+    shape: &'a Shape,
+    // world to shape rotation
+    rotation: RotationMatrix,
+    origin: TranslationVector,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SupportPoint {
+    w: TranslationVector,
+    a: TranslationVector,
+    b: TranslationVector,
+}
+
+impl<'a> Placed<'a> {
+    fn new(shape: &'a Shape, pose: TransformationMatrix) -> Self {
+        Self {
+            shape,
+            rotation: pose.to_rotation(),
+            origin: pose.to_translation(),
+        }
+    }
+
+    fn to_world(&self, point: TranslationVector) -> TranslationVector {
+        !self.rotation * point + self.origin
+    }
+
+    fn support(&self, direction: TranslationVector, core: bool) -> TranslationVector {
+        let local = self.rotation * direction;
+        if core {
+            self.to_world(self.shape.core_support(local))
+        } else {
+            self.to_world(self.shape.support(local))
+        }
+    }
+}
+
+fn support_point(a: &Placed, b: &Placed, direction: TranslationVector, core: bool) -> SupportPoint {
+    let point_a = a.support(direction, core);
+    let point_b = b.support(-direction, core);
+    SupportPoint {
+        w: point_a - point_b,
+        a: point_a,
+        b: point_b,
+    }
+}
+
+fn half_space_distance(plane: &Placed, other: &Placed) -> Proximity {
+    let normal = !plane.rotation * TranslationVector::from_array([0.0, 0.0, 1.0]);
+    let deepest = other.support(-normal, false);
+    let distance = normal.dot(deepest - plane.origin);
+    Proximity {
+        distance,
+        point_a: deepest - normal * distance,
+        point_b: deepest,
+        normal,
+    }
+}
+
+fn convex_distance(a: &Placed, b: &Placed) -> Proximity {
+    let margin = a.shape.margin() + b.shape.margin();
+    if margin > 0.0 {
+        // separated cores give the exact distance of the inflated shapes
+        if let Some((distance, simplex, weights)) = gjk(a, b, true) {
+            let (point_a, point_b) = witness_points(&simplex, &weights);
+            let normal = (point_b - point_a).normalize();
+            return Proximity {
+                distance: distance - margin,
+                point_a: point_a + normal * a.shape.margin(),
+                point_b: point_b - normal * b.shape.margin(),
+                normal,
+            };
+        }
+    }
+    match gjk(a, b, false) {
+        Some((distance, simplex, weights)) if distance > TOLERANCE => {
+            let (point_a, point_b) = witness_points(&simplex, &weights);
+            Proximity {
+                distance,
+                point_a,
+                point_b,
+                normal: (point_b - point_a) * (1.0 / distance),
+            }
+        }
+        Some((_, simplex, _)) => epa(a, b, simplex),
+        None => {
+            let simplex = vec![support_point(a, b, a.origin - b.origin, false)];
+            epa(a, b, simplex)
+        }
+    }
+}
+
+// distance between the shapes with the final simplex and its barycentric weights, or None
+// when they intersect
+fn gjk(a: &Placed, b: &Placed, core: bool) -> Option<(f64, Vec<SupportPoint>, Vec<f64>)> {
+    let mut direction = b.origin - a.origin;
+    if direction.norm() < TOLERANCE {
+        direction = TranslationVector::from_array([1.0, 0.0, 0.0]);
+    }
+    let mut simplex = vec![support_point(a, b, -direction, core)];
+    let mut weights = vec![1.0];
+    let mut v = simplex[0].w;
+
+    for _ in 0..MAX_ITERATIONS {
+        let vv = v.dot(v);
+        if vv < TOLERANCE * TOLERANCE {
+            return None;
+        }
+        let w = support_point(a, b, -v, core);
+        if vv - v.dot(w.w) <= TOLERANCE * vv
+            || simplex.iter().any(|s| (s.w - w.w).norm() < TOLERANCE)
+        {
+            break;
+        }
+        simplex.push(w);
+        let (closest, lambda) = closest_on_simplex(&simplex);
+        if lambda.len() == 4 && lambda.iter().all(|l| *l > 0.0) {
+            return None;
+        }
+        let kept: Vec<usize> = (0..simplex.len()).filter(|i| lambda[*i] > 0.0).collect();
+        simplex = kept.iter().map(|i| simplex[*i]).collect();
+        weights = kept.iter().map(|i| lambda[*i]).collect();
+        v = closest;
+    }
+    Some((v.norm(), simplex, weights))
+}
+
+// closest point to the origin on the hull of up to four points, by checking the projection of
+// the origin onto every face of the simplex
+fn closest_on_simplex(simplex: &[SupportPoint]) -> (TranslationVector, Vec<f64>) {
+    let n = simplex.len();
+    let mut best: Option<(f64, TranslationVector, Vec<f64>)> = None;
+    for mask in 1..(1usize << n) {
+        let members: Vec<usize> = (0..n).filter(|i| mask & (1 << i) != 0).collect();
+        let points: Vec<TranslationVector> = members.iter().map(|i| simplex[*i].w).collect();
+        let Some(lambda) = project_origin(&points) else {
+            continue;
+        };
+        if lambda.iter().any(|l| *l < 0.0) {
+            continue;
+        }
+        let closest = points
+            .iter()
+            .zip(&lambda)
+            .fold(TranslationVector::new(), |sum, (p, l)| sum + *p * *l);
+        let norm = closest.norm();
+        if best.as_ref().is_none_or(|(d, _, _)| norm < *d) {
+            let mut weights = vec![0.0; n];
+            for (i, l) in members.iter().zip(lambda) {
+                weights[*i] = l;
+            }
+            best = Some((norm, closest, weights));
+        }
+    }
+    let (_, closest, weights) = best.expect("a vertex is always a candidate");
+    (closest, weights)
+}
+
+// barycentric coordinates of the projection of the origin onto the affine hull of the points
+fn project_origin(points: &[TranslationVector]) -> Option<Vec<f64>> {
+    let k = points.len() - 1;
+    if k == 0 {
+        return Some(vec![1.0]);
+    }
+    let edges: Vec<TranslationVector> = points[1..].iter().map(|p| *p - points[0]).collect();
+    let mut gram = crate::linalg::Matrix::new(k, k);
+    let mut rhs = vec![0.0; k];
+    for i in 0..k {
+        for j in 0..k {
+            gram[(i, j)] = edges[i].dot(edges[j]);
+        }
+        rhs[i] = -edges[i].dot(points[0]);
+    }
+    let mu = gram.solve(&rhs)?;
+    let mut lambda = vec![1.0 - mu.iter().sum::<f64>()];
+    lambda.extend(mu);
+    Some(lambda)
+}
+
+fn witness_points(
+    simplex: &[SupportPoint],
+    weights: &[f64],
+) -> (TranslationVector, TranslationVector) {
+    simplex.iter().zip(weights).fold(
+        (TranslationVector::new(), TranslationVector::new()),
+        |(a, b), (s, l)| (a + s.a * *l, b + s.b * *l),
+    )
+}
+
+struct Face {
+    vertices: [usize; 3],
+    normal: TranslationVector,
+    distance: f64,
+}
+
+// expanding polytope algorithm for the penetration of overlapping shapes
+fn epa(a: &Placed, b: &Placed, simplex: Vec<SupportPoint>) -> Proximity {
+    let mut vertices = simplex;
+    complete_tetrahedron(a, b, &mut vertices);
+    let interior = vertices
+        .iter()
+        .fold(TranslationVector::new(), |sum, v| sum + v.w)
+        * 0.25;
+
+    let make_face = |vertices: &[SupportPoint], indices: [usize; 3]| -> Option<Face> {
+        let [p0, p1, p2] = indices.map(|i| vertices[i].w);
+        let normal = (p1 - p0).cross(p2 - p0);
+        let area = normal.norm();
+        if area < TOLERANCE * TOLERANCE {
+            return None;
+        }
+        let mut normal = normal * (1.0 / area);
+        if normal.dot(p0 - interior) < 0.0 {
+            normal = -normal;
+        }
+        Some(Face {
+            vertices: indices,
+            normal,
+            distance: normal.dot(p0),
+        })
+    };
+
+    let mut faces: Vec<Face> = [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]]
+        .into_iter()
+        .filter_map(|indices| make_face(&vertices, indices))
+        .collect();
+
+    let closest_face = |faces: &[Face]| {
+        (0..faces.len())
+            .min_by(|i, j| faces[*i].distance.total_cmp(&faces[*j].distance))
+            .expect("the polytope always has faces")
+    };
+    for _ in 0..MAX_ITERATIONS {
+        let closest = closest_face(&faces);
+        let normal = faces[closest].normal;
+        let w = support_point(a, b, normal, false);
+        if normal.dot(w.w) - faces[closest].distance <= EPA_TOLERANCE {
+            break;
+        }
+
+        let index = vertices.len();
+        vertices.push(w);
+        let mut edges: Vec<[usize; 2]> = Vec::new();
+        faces.retain(|face| {
+            let visible = face.normal.dot(w.w - vertices[face.vertices[0]].w) > 0.0;
+            if visible {
+                let [i, j, k] = face.vertices;
+                for edge in [[i, j], [j, k], [k, i]] {
+                    if let Some(shared) = edges
+                        .iter()
+                        .position(|e| (e[0] == edge[1] && e[1] == edge[0]) || *e == edge)
+                    {
+                        edges.swap_remove(shared);
+                    } else {
+                        edges.push(edge);
+                    }
+                }
+            }
+            !visible
+        });
+        faces.extend(
+            edges
+                .into_iter()
+                .filter_map(|[i, j]| make_face(&vertices, [i, j, index])),
+        );
+    }
+
+    let face = &faces[closest_face(&faces)];
+    let [p0, p1, p2] = face.vertices.map(|i| vertices[i].w);
+    let lambda =
+        project_origin(&[p0, p1, p2]).unwrap_or_else(|| vec![1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]);
+    let simplex: Vec<SupportPoint> = face.vertices.iter().map(|i| vertices[*i]).collect();
+    let (point_a, point_b) = witness_points(&simplex, &lambda);
+    Proximity {
+        distance: -face.distance,
+        point_a,
+        point_b,
+        normal: face.normal,
+    }
+}
+
+// grow a simplex that touches or contains the origin into a tetrahedron with volume
+fn complete_tetrahedron(a: &Placed, b: &Placed, vertices: &mut Vec<SupportPoint>) {
+    let axes =
+        [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]].map(TranslationVector::from_array);
+    let push_farthest =
+        |vertices: &mut Vec<SupportPoint>,
+         directions: &[TranslationVector],
+         spread: &dyn Fn(&[SupportPoint], TranslationVector) -> f64| {
+            let candidates = directions
+                .iter()
+                .flat_map(|d| [*d, -*d])
+                .map(|d| support_point(a, b, d, false));
+            if let Some(best) =
+                candidates.max_by(|p, q| spread(vertices, p.w).total_cmp(&spread(vertices, q.w)))
+            {
+                vertices.push(best);
+            }
+        };
+
+    if vertices.len() == 1 {
+        push_farthest(vertices, &axes, &|v, w| (w - v[0].w).norm());
+    }
+    if vertices.len() == 2 {
+        let edge = vertices[1].w - vertices[0].w;
+        let directions = axes.map(|axis| edge.cross(axis));
+        push_farthest(vertices, &directions, &|v, w| {
+            (v[1].w - v[0].w).cross(w - v[0].w).norm()
+        });
+    }
+    if vertices.len() == 3 {
+        let normal = (vertices[1].w - vertices[0].w).cross(vertices[2].w - vertices[0].w);
+        push_farthest(vertices, &[normal], &|v, w| {
+            let n = (v[1].w - v[0].w).cross(v[2].w - v[0].w);
+            n.dot(w - v[0].w).abs()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f64, y: f64, z: f64) -> TransformationMatrix {
+        TranslationVector::from_array([x, y, z]).as_transform()
+    }
+
+    fn assert_close(proximity: Proximity, distance: f64, normal: [f64; 3], tolerance: f64) {
+        assert!(
+            (proximity.distance - distance).abs() < tolerance,
+            "{} != {}",
+            proximity.distance,
+            distance
+        );
+        let normal = TranslationVector::from_array(normal);
+        assert!((proximity.normal - normal).norm() < tolerance.sqrt());
+    }
+
+    #[test]
+    fn spheres_and_boxes() {
+        let sphere = Shape::Sphere { radius: 0.5 };
+        let apart = distance(&sphere, at(0.0, 0.0, 0.0), &sphere, at(2.0, 0.0, 0.0)).unwrap();
+        assert_close(apart, 1.0, [1.0, 0.0, 0.0], 1e-9);
+        assert!((apart.point_a.to_array()[0] - 0.5).abs() < 1e-9);
+        let overlap = distance(&sphere, at(0.0, 0.0, 0.0), &sphere, at(0.0, 0.8, 0.0)).unwrap();
+        assert_close(overlap, -0.2, [0.0, 1.0, 0.0], 1e-9);
+
+        let cube = Shape::Box {
+            half_extents: TranslationVector::from_array([1.0, 1.0, 1.0]),
+        };
+        let apart = distance(&cube, at(0.0, 0.0, 0.0), &cube, at(2.5, 0.3, 0.0)).unwrap();
+        assert_close(apart, 0.5, [1.0, 0.0, 0.0], 1e-9);
+        let overlap = distance(&cube, at(0.0, 0.0, 0.0), &cube, at(0.2, 0.1, 1.7)).unwrap();
+        assert_close(overlap, -0.3, [0.0, 0.0, 1.0], 1e-6);
+
+        let rotated = RotationMatrix::from_z_rotation(core::f64::consts::FRAC_PI_4)
+            + TranslationVector::from_array([0.0, 0.0, 0.0]);
+        let corner = distance(&cube, rotated, &sphere, at(2.0, 0.0, 0.0)).unwrap();
+        assert_close(corner, 2.0 - 2.0_f64.sqrt() - 0.5, [1.0, 0.0, 0.0], 1e-9);
+    }
+
+    #[test]
+    fn curved_shapes_and_half_space() {
+        let ground = Shape::HalfSpace;
+        let ellipsoid = Shape::Ellipsoid {
+            radii: TranslationVector::from_array([1.0, 2.0, 3.0]),
+        };
+        let resting = distance(&ellipsoid, at(0.0, 0.0, 2.5), &ground, at(0.0, 0.0, 0.0)).unwrap();
+        assert_close(resting, -0.5, [0.0, 0.0, -1.0], 1e-12);
+
+        let capsule = Shape::Capsule {
+            radius: 0.1,
+            half_length: 0.4,
+        };
+        let tilted = RotationMatrix::from_x_rotation(core::f64::consts::FRAC_PI_2)
+            + TranslationVector::from_array([0.0, 0.0, 0.3]);
+        let lying = distance(&ground, at(0.0, 0.0, 0.0), &capsule, tilted).unwrap();
+        assert_close(lying, 0.2, [0.0, 0.0, 1.0], 1e-12);
+
+        let cylinder = Shape::Cylinder {
+            radius: 0.5,
+            half_length: 1.0,
+        };
+        let sphere = Shape::Sphere { radius: 0.25 };
+        let side = distance(&cylinder, at(0.0, 0.0, 0.0), &sphere, at(0.0, 0.7, 0.2)).unwrap();
+        assert_close(side, -0.05, [0.0, 1.0, 0.0], 1e-9);
+        let inside = point_distance(
+            &cylinder,
+            at(0.0, 0.0, 0.0),
+            TranslationVector::from_array([0.0, 0.0, 0.9]),
+        )
+        .unwrap();
+        assert_close(inside, -0.1, [0.0, 0.0, 1.0], 1e-6);
+    }
+
+    #[test]
+    fn convex_mesh_against_box() {
+        let vertex = |x, y, z| TranslationVector::from_array([x, y, z]);
+        let tetrahedron = Shape::convex_mesh(
+            vec![
+                vertex(0.0, 0.0, 0.0),
+                vertex(1.0, 0.0, 0.0),
+                vertex(0.0, 1.0, 0.0),
+                vertex(0.0, 0.0, 1.0),
+            ],
+            vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+        )
+        .unwrap();
+        assert!(Shape::convex_mesh(Vec::new(), Vec::new()).is_none());
+        assert!(Shape::convex_mesh(vec![vertex(0.0, 0.0, 0.0)], vec![[0, 0, 1]]).is_none());
+        let slab = Shape::Box {
+            half_extents: TranslationVector::from_array([2.0, 2.0, 0.5]),
+        };
+        let above = distance(&slab, at(0.0, 0.0, -0.75), &tetrahedron, at(0.0, 0.0, 0.0)).unwrap();
+        assert_close(above, 0.25, [0.0, 0.0, 1.0], 1e-9);
+        let sunk = distance(&slab, at(0.0, 0.0, -0.35), &tetrahedron, at(0.0, 0.0, 0.0)).unwrap();
+        assert_close(sunk, -0.15, [0.0, 0.0, 1.0], 1e-6);
+
+        let empty = Shape::ConvexMesh {
+            vertices: Vec::new(),
+            triangles: Vec::new(),
+        };
+        assert!(distance(&slab, at(0.0, 0.0, 0.0), &empty, at(0.0, 0.0, 0.0)).is_none());
+    }
+}
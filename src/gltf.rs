@@ -0,0 +1,539 @@
+use std::fmt::{self, Write};
+use std::path::Path;
+
+use crate::body::{Body, BodyKinematics};
+use crate::geometry::{Collider, Shape};
+use crate::TransformationMatrix;
+
+// divisions of the round shapes around their axis and from pole to pole
+const SEGMENTS: usize = 24;
+const STACKS: usize = 12;
+// half width of the square drawn for a half-space
+const HALF_SPACE_EXTENT: f64 = 5.0;
+
+/// glTF 2.0 scene of a model's shapes animated along a recorded trajectory
+///
+/// Every body becomes a node animated with its world transform, carrying its shapes as static
+/// children; world shapes sit beside them under a root turning z up into the y up of glTF.
+/// The binary data is embedded in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gltf {
+    pub names: Vec<String>,
+    /// collision and visual shapes alike
+    pub shapes: Vec<Collider>,
+    pub times: Vec<f64>,
+    /// world to body transforms per recorded frame
+    pub poses: Vec<Vec<TransformationMatrix>>,
+}
+
+// triangle mesh in f32 as glTF stores it
+struct Mesh {
+    positions: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+}
+
+impl Gltf {
+    pub fn new(bodies: &[Body], shapes: Vec<Collider>) -> Self {
+        Self {
+            names: bodies.iter().map(|body| body.name.clone()).collect(),
+            shapes,
+            times: Vec::new(),
+            poses: Vec::new(),
+        }
+    }
+
+    /// Records the body poses of one frame
+    pub fn push(&mut self, time: f64, kinematics: &[BodyKinematics]) {
+        self.times.push(time);
+        self.poses
+            .push(kinematics.iter().map(|kin| kin.pose).collect());
+    }
+
+    /// Records a whole trajectory through a forward kinematics closure
+    pub fn record(
+        mut self,
+        times: &[f64],
+        trajectory: &[Vec<f64>],
+        kinematics: impl Fn(&[f64]) -> Vec<BodyKinematics>,
+    ) -> Self {
+        for (time, q) in times.iter().zip(trajectory) {
+            self.push(*time, &kinematics(q));
+        }
+        self
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    fn json(&self) -> Result<String, fmt::Error> {
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut views = String::new();
+        let mut accessors = String::new();
+        let mut count = 0;
+        // appends a tightly packed block of f32 or u32 data with its accessor
+        let mut accessor = |buffer: &mut Vec<u8>,
+                            data: Vec<u8>,
+                            component: u32,
+                            kind: &str,
+                            elements: usize,
+                            bounds: String|
+         -> Result<usize, fmt::Error> {
+            let separator = if count == 0 { "" } else { "," };
+            write!(
+                views,
+                "{separator}{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{}}}",
+                buffer.len(),
+                data.len()
+            )?;
+            write!(
+                accessors,
+                "{separator}{{\"bufferView\":{count},\"componentType\":{component},\"count\":{elements},\"type\":\"{kind}\"{bounds}}}"
+            )?;
+            buffer.extend(data);
+            count += 1;
+            Ok(count - 1)
+        };
+
+        let mut meshes = String::new();
+        for (i, collider) in self.shapes.iter().enumerate() {
+            let mesh = tessellate(&collider.shape);
+            let mut min = [f32::INFINITY; 3];
+            let mut max = [f32::NEG_INFINITY; 3];
+            for position in &mesh.positions {
+                for k in 0..3 {
+                    min[k] = min[k].min(position[k]);
+                    max[k] = max[k].max(position[k]);
+                }
+            }
+            let positions = accessor(
+                &mut buffer,
+                mesh.positions
+                    .iter()
+                    .flatten()
+                    .flat_map(|v| v.to_le_bytes())
+                    .collect(),
+                5126,
+                "VEC3",
+                mesh.positions.len(),
+                format!(",\"min\":{},\"max\":{}", list(&min), list(&max)),
+            )?;
+            let indices = accessor(
+                &mut buffer,
+                mesh.indices.iter().flat_map(|v| v.to_le_bytes()).collect(),
+                5125,
+                "SCALAR",
+                mesh.indices.len(),
+                String::new(),
+            )?;
+            let separator = if i == 0 { "" } else { "," };
+            write!(
+                meshes,
+                "{separator}{{\"primitives\":[{{\"attributes\":{{\"POSITION\":{positions}}},\"indices\":{indices}}}]}}"
+            )?;
+        }
+
+        // body nodes first, then one node per shape
+        let bodies = self.names.len();
+        let mut nodes = Vec::new();
+        for (b, name) in self.names.iter().enumerate() {
+            let children: Vec<String> = (0..self.shapes.len())
+                .filter(|s| self.shapes[*s].body == Some(b))
+                .map(|s| (bodies + s).to_string())
+                .collect();
+            let transform = self
+                .poses
+                .first()
+                .map_or(String::new(), |poses| node_transform(poses[b]));
+            nodes.push(format!(
+                "{{\"name\":{},\"children\":[{}]{transform}}}",
+                quote(name),
+                children.join(",")
+            ));
+        }
+        for (s, collider) in self.shapes.iter().enumerate() {
+            nodes.push(format!(
+                "{{\"mesh\":{s}{}}}",
+                node_transform(collider.offset)
+            ));
+        }
+        let roots: Vec<String> = (0..bodies)
+            .chain(
+                (0..self.shapes.len())
+                    .filter(|s| self.shapes[*s].body.is_none())
+                    .map(|s| bodies + s),
+            )
+            .map(|node| node.to_string())
+            .collect();
+        // glTF viewers expect y up, the simulation has z up
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        nodes.push(format!(
+            "{{\"name\":\"world\",\"rotation\":[{},0,0,{half}],\"children\":[{}]}}",
+            -half,
+            roots.join(",")
+        ));
+
+        let mut animation = String::new();
+        if !self.times.is_empty() {
+            let times: Vec<f32> = self.times.iter().map(|t| *t as f32).collect();
+            let (first, last) = (times[0], times[times.len() - 1]);
+            let input = accessor(
+                &mut buffer,
+                times.iter().flat_map(|t| t.to_le_bytes()).collect(),
+                5126,
+                "SCALAR",
+                times.len(),
+                format!(",\"min\":[{first}],\"max\":[{last}]"),
+            )?;
+            let mut samplers = Vec::new();
+            let mut channels = Vec::new();
+            for b in 0..bodies {
+                let mut translations = Vec::new();
+                let mut rotations = Vec::new();
+                let mut previous = [0.0, 0.0, 0.0, 1.0];
+                for poses in &self.poses {
+                    let (translation, mut rotation) = trs(poses[b]);
+                    // keep to one hemisphere so the interpolation takes the short way
+                    if rotation
+                        .iter()
+                        .zip(previous)
+                        .map(|(a, b)| a * b)
+                        .sum::<f64>()
+                        < 0.0
+                    {
+                        rotation = rotation.map(|v| -v);
+                    }
+                    previous = rotation;
+                    translations.extend(translation.map(|v| v as f32));
+                    rotations.extend(rotation.map(|v| v as f32));
+                }
+                for (path, values, kind) in [
+                    ("translation", translations, "VEC3"),
+                    ("rotation", rotations, "VEC4"),
+                ] {
+                    let output = accessor(
+                        &mut buffer,
+                        values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+                        5126,
+                        kind,
+                        times.len(),
+                        String::new(),
+                    )?;
+                    channels.push(format!(
+                        "{{\"sampler\":{},\"target\":{{\"node\":{b},\"path\":\"{path}\"}}}}",
+                        samplers.len()
+                    ));
+                    samplers.push(format!(
+                        "{{\"input\":{input},\"output\":{output},\"interpolation\":\"LINEAR\"}}"
+                    ));
+                }
+            }
+            animation = format!(
+                ",\"animations\":[{{\"samplers\":[{}],\"channels\":[{}]}}]",
+                samplers.join(","),
+                channels.join(",")
+            );
+        }
+
+        let mut json = String::new();
+        write!(
+            json,
+            "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"onager\"}},\"scene\":0,\"scenes\":[{{\"nodes\":[{}]}}],\"nodes\":[{}],\"meshes\":[{meshes}],\"accessors\":[{accessors}],\"bufferViews\":[{views}],\"buffers\":[{{\"byteLength\":{},\"uri\":\"data:application/octet-stream;base64,{}\"}}]{animation}}}",
+            nodes.len() - 1,
+            nodes.join(","),
+            buffer.len(),
+            base64(&buffer)
+        )?;
+        Ok(json)
+    }
+}
+
+impl fmt::Display for Gltf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.json()?)
+    }
+}
+
+// node translation and rotation quaternion (x, y, z, w) placing a frame given by its coordinate
+// transform from the parent
+fn trs(transform: TransformationMatrix) -> ([f64; 3], [f64; 4]) {
+    let e = transform.to_rotation();
+    // rotation of the frame, the transpose of the coordinate transform
+    let r = |row: usize, column: usize| e.data[column * 3 + row];
+    let trace = r(0, 0) + r(1, 1) + r(2, 2);
+    let quaternion = if trace > 0.0 {
+        let s = 2.0 * (trace + 1.0).sqrt();
+        [
+            (r(2, 1) - r(1, 2)) / s,
+            (r(0, 2) - r(2, 0)) / s,
+            (r(1, 0) - r(0, 1)) / s,
+            0.25 * s,
+        ]
+    } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+        let s = 2.0 * (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt();
+        [
+            0.25 * s,
+            (r(0, 1) + r(1, 0)) / s,
+            (r(0, 2) + r(2, 0)) / s,
+            (r(2, 1) - r(1, 2)) / s,
+        ]
+    } else if r(1, 1) > r(2, 2) {
+        let s = 2.0 * (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt();
+        [
+            (r(0, 1) + r(1, 0)) / s,
+            0.25 * s,
+            (r(1, 2) + r(2, 1)) / s,
+            (r(0, 2) - r(2, 0)) / s,
+        ]
+    } else {
+        let s = 2.0 * (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt();
+        [
+            (r(0, 2) + r(2, 0)) / s,
+            (r(1, 2) + r(2, 1)) / s,
+            0.25 * s,
+            (r(1, 0) - r(0, 1)) / s,
+        ]
+    };
+    (transform.to_translation().to_array(), quaternion)
+}
+
+fn node_transform(transform: TransformationMatrix) -> String {
+    let (translation, rotation) = trs(transform);
+    format!(
+        ",\"translation\":{},\"rotation\":{}",
+        list(&translation),
+        list(&rotation)
+    )
+}
+
+fn list<T: fmt::Display>(values: &[T]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (i, byte)| {
+            value | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(value >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+// triangles wound counterclockwise seen from outside, in shape coordinates
+fn tessellate(shape: &Shape) -> Mesh {
+    let angles =
+        |count: usize, range: f64| (0..=count).map(move |i| range * i as f64 / count as f64);
+    let pi = std::f64::consts::PI;
+    match shape {
+        Shape::Sphere { radius } => {
+            lathe(angles(STACKS, pi).map(|t| (radius * t.sin(), -radius * t.cos())))
+        }
+        Shape::Ellipsoid { radii } => {
+            let [a, b, c] = radii.to_array();
+            let mut mesh = lathe(angles(STACKS, pi).map(|t| (t.sin(), -t.cos())));
+            for position in &mut mesh.positions {
+                *position = [
+                    position[0] * a as f32,
+                    position[1] * b as f32,
+                    position[2] * c as f32,
+                ];
+            }
+            mesh
+        }
+        Shape::Capsule {
+            radius,
+            half_length,
+        } => {
+            // the bottom hemisphere, then the top one shifted up by the whole length
+            let half = STACKS / 2;
+            let bottom =
+                angles(half, pi / 2.0).map(|t| (radius * t.sin(), -half_length - radius * t.cos()));
+            let top =
+                angles(half, pi / 2.0).map(|t| (radius * t.cos(), half_length + radius * t.sin()));
+            lathe(bottom.chain(top))
+        }
+        Shape::Cylinder {
+            radius,
+            half_length,
+        } => lathe([
+            (0.0, -half_length),
+            (*radius, -half_length),
+            (*radius, *half_length),
+            (0.0, *half_length),
+        ]),
+        Shape::Box { half_extents } => {
+            let h = half_extents.to_array();
+            let mut mesh = Mesh {
+                positions: Vec::new(),
+                indices: Vec::new(),
+            };
+            for k in 0..3 {
+                let (u, v) = ((k + 1) % 3, (k + 2) % 3);
+                for sign in [1.0, -1.0] {
+                    let start = mesh.positions.len() as u32;
+                    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+                    for (a, b) in corners {
+                        let mut position = [0.0; 3];
+                        position[k] = (sign * h[k]) as f32;
+                        // mirrored faces walk the corners the other way round
+                        position[u] = (sign * a * h[u]) as f32;
+                        position[v] = (b * h[v]) as f32;
+                        mesh.positions.push(position);
+                    }
+                    mesh.indices.extend([0, 1, 2, 0, 2, 3].map(|i| start + i));
+                }
+            }
+            mesh
+        }
+        Shape::HalfSpace => {
+            let e = HALF_SPACE_EXTENT as f32;
+            Mesh {
+                positions: vec![[-e, -e, 0.0], [e, -e, 0.0], [e, e, 0.0], [-e, e, 0.0]],
+                indices: vec![0, 1, 2, 0, 2, 3],
+            }
+        }
+        Shape::ConvexMesh {
+            vertices,
+            triangles,
+        } => Mesh {
+            positions: vertices
+                .iter()
+                .map(|v| v.to_array().map(|x| x as f32))
+                .collect(),
+            indices: triangles.iter().flat_map(|t| t.map(|i| i as u32)).collect(),
+        },
+    }
+}
+
+// surface of revolution about z through a profile of (radius, z) points from bottom to top
+fn lathe(profile: impl IntoIterator<Item = (f64, f64)>) -> Mesh {
+    let profile: Vec<(f64, f64)> = profile.into_iter().collect();
+    let mut positions = Vec::new();
+    for (radius, z) in &profile {
+        for j in 0..SEGMENTS {
+            let angle = 2.0 * std::f64::consts::PI * j as f64 / SEGMENTS as f64;
+            positions.push([
+                (radius * angle.cos()) as f32,
+                (radius * angle.sin()) as f32,
+                *z as f32,
+            ]);
+        }
+    }
+    let mut indices = Vec::new();
+    for i in 0..profile.len() - 1 {
+        for j in 0..SEGMENTS {
+            let vertex = |i: usize, j: usize| (i * SEGMENTS + j % SEGMENTS) as u32;
+            let (a, b) = (vertex(i, j), vertex(i, j + 1));
+            let (c, d) = (vertex(i + 1, j + 1), vertex(i + 1, j));
+            indices.extend([a, b, c, a, c, d]);
+        }
+    }
+    Mesh { positions, indices }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Inertia, RotationMatrix, TranslationVector};
+
+    // signed volume enclosed by a mesh, positive when it is wound outward
+    fn volume(mesh: &Mesh) -> f64 {
+        mesh.indices
+            .chunks(3)
+            .map(|t| {
+                let p = |i: u32| {
+                    TranslationVector::from_array(mesh.positions[i as usize].map(|x| x as f64))
+                };
+                p(t[0]).dot(p(t[1]).cross(p(t[2]))) / 6.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn meshes_enclose_their_shapes() {
+        let pi = std::f64::consts::PI;
+        let shapes = [
+            (
+                Shape::Box {
+                    half_extents: TranslationVector::from_array([0.1, 0.2, 0.3]),
+                },
+                0.048,
+            ),
+            (Shape::Sphere { radius: 0.5 }, 4.0 / 3.0 * pi * 0.125),
+            (
+                Shape::Cylinder {
+                    radius: 0.2,
+                    half_length: 0.5,
+                },
+                pi * 0.04,
+            ),
+            (
+                Shape::Capsule {
+                    radius: 0.2,
+                    half_length: 0.5,
+                },
+                pi * 0.04 + 4.0 / 3.0 * pi * 0.008,
+            ),
+        ];
+        for (shape, expected) in shapes {
+            let volume = volume(&tessellate(&shape));
+            assert!((volume - expected).abs() < 0.03 * expected);
+        }
+
+        // a quarter turn about z is the quaternion (0, 0, sin(pi/4), cos(pi/4))
+        let (translation, rotation) = trs(RotationMatrix::from_z_rotation(pi / 2.0)
+            + TranslationVector::from_array([1.0, 2.0, 3.0]));
+        assert_eq!(translation, [1.0, 2.0, 3.0]);
+        let half = (pi / 4.0).sin();
+        assert!(rotation
+            .iter()
+            .zip([0.0, 0.0, half, half])
+            .all(|(a, b)| (a - b).abs() < 1e-12));
+        assert_eq!(base64(b"onager!"), "b25hZ2VyIQ==");
+    }
+
+    #[test]
+    fn exports_nodes_and_animation() {
+        let inertia = Inertia::new(1.0, 0.1, 0.1, 0.1, 0.0, 0.0, 0.0);
+        let bodies = [Body::new("link", None, TranslationVector::new(), inertia)];
+        let shapes = vec![
+            Collider::new(
+                Some(0),
+                Shape::Sphere { radius: 0.1 },
+                TransformationMatrix::identity(),
+            ),
+            Collider::new(None, Shape::HalfSpace, TransformationMatrix::identity()),
+        ];
+        let kinematics = |q: &[f64]| {
+            vec![BodyKinematics::new(
+                RotationMatrix::from_z_rotation(q[0]).as_transform(),
+                1,
+            )]
+        };
+        let gltf = Gltf::new(&bodies, shapes).record(
+            &[0.0, 0.5, 1.0],
+            &[vec![0.0], vec![1.0], vec![2.0]],
+            kinematics,
+        );
+        let json = gltf.to_string();
+        assert!(json.contains("\"scenes\":[{\"nodes\":[3]}]"));
+        assert!(json.contains("\"children\":[0,2]"));
+        assert!(json.contains("\"children\":[1]"));
+        assert!(json.contains("\"path\":\"rotation\""));
+        assert!(json.contains("\"count\":3,\"type\":\"SCALAR\",\"min\":[0],\"max\":[1]"));
+    }
+}
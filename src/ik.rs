@@ -0,0 +1,686 @@
+use crate::body::{Body, BodyKinematics};
+use crate::linalg::Matrix;
+use crate::momentum;
+use crate::{MotionVec6, TransformationMatrix, TranslationVector};
+
+/// Motion-capture marker fixed on a body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub name: String,
+    pub body: usize,
+    /// in body coordinates
+    pub offset: TranslationVector,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    GaussNewton,
+    LevenbergMarquardt,
+}
+
+/// Weighted least-squares fit of generalized coordinates to measured marker positions
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerIk {
+    method: Method,
+    max_iterations: usize,
+    tolerance: f64,
+    lower: Vec<f64>,
+    upper: Vec<f64>,
+}
+
+/// Kinematic objective of task-space inverse kinematics, with targets in world coordinates
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Task {
+    /// world to frame `target` of a frame fixed on a body at the body to frame `offset`
+    Frame {
+        body: usize,
+        offset: TransformationMatrix,
+        target: TransformationMatrix,
+    },
+    Point {
+        body: usize,
+        point: TranslationVector,
+        target: TranslationVector,
+    },
+    CenterOfMass {
+        target: TranslationVector,
+    },
+    /// regularization toward a reference configuration
+    Posture {
+        target: Vec<f64>,
+    },
+}
+
+/// How tasks of different priority levels are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prioritization {
+    /// each level is solved in the null space of the levels above it
+    NullSpace,
+    /// all tasks are solved together as one weighted least-squares problem
+    Weighted,
+}
+
+/// Damped least-squares inverse kinematics over prioritized tasks
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskIk {
+    tasks: Vec<(Task, usize, f64)>,
+    prioritization: Prioritization,
+    damping: f64,
+    max_iterations: usize,
+    tolerance: f64,
+}
+
+/// Result of task-space inverse kinematics
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskSolution {
+    pub q: Vec<f64>,
+    /// norm of the remaining error of each task
+    pub errors: Vec<f64>,
+    pub iterations: usize,
+    pub converged: bool,
+}
+
+/// Result of fitting one frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct IkSolution {
+    pub q: Vec<f64>,
+    /// distance between each model marker and its measurement, `None` for missing markers
+    pub residuals: Vec<Option<f64>>,
+    /// root mean square of the present marker distances
+    pub rms: f64,
+    pub iterations: usize,
+    pub converged: bool,
+}
+
+impl Marker {
+    pub fn new(name: &str, body: usize, offset: TranslationVector) -> Self {
+        Self {
+            name: name.to_string(),
+            body,
+            offset,
+            weight: 1.0,
+        }
+    }
+
+    pub fn weight(mut self, weight: f64) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn position(&self, kinematics: &[BodyKinematics]) -> TranslationVector {
+        kinematics[self.body].point_position(self.offset)
+    }
+}
+
+impl MarkerIk {
+    pub fn new() -> Self {
+        Self {
+            method: Method::LevenbergMarquardt,
+            max_iterations: 100,
+            tolerance: 1e-10,
+            lower: Vec::new(),
+            upper: Vec::new(),
+        }
+    }
+
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Size of the coordinate update at which the iterations stop
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Bounds on each coordinate, infinite for unlimited coordinates
+    pub fn limits(mut self, lower: Vec<f64>, upper: Vec<f64>) -> Self {
+        self.lower = lower;
+        self.upper = upper;
+        self
+    }
+
+    /// Fit one frame of `measured` marker positions, `None` for markers that are missing in the
+    /// frame, starting from `q`
+    pub fn solve(
+        &self,
+        markers: &[Marker],
+        measured: &[Option<TranslationVector>],
+        q: &[f64],
+        kinematics: impl Fn(&[f64]) -> Vec<BodyKinematics>,
+    ) -> IkSolution {
+        let mut q = self.clamp(q.to_vec());
+        let mut kin = kinematics(&q);
+        let mut cost = weighted_cost(markers, measured, &kin);
+        let mut damping = 1e-3;
+        let mut iterations = 0;
+        let mut converged = false;
+
+        while iterations < self.max_iterations {
+            iterations += 1;
+            let (jacobian, residual) = linearize(markers, measured, &kin, q.len());
+            let mut normal = &jacobian.transpose() * &jacobian;
+            let gradient = jacobian.transpose_multiply_vector(&residual);
+            let scale = (0..q.len()).fold(0.0_f64, |max, i| max.max(normal[(i, i)]));
+            for i in 0..q.len() {
+                normal[(i, i)] += match self.method {
+                    Method::GaussNewton => 1e-12 * scale.max(1.0),
+                    Method::LevenbergMarquardt => damping * normal[(i, i)].max(1e-12),
+                };
+            }
+            let Some(step) = normal.solve(&gradient) else {
+                break;
+            };
+
+            // Gauss-Newton backtracks along the step, Levenberg-Marquardt adapts its damping
+            let mut fraction = 1.0;
+            let accepted = loop {
+                let trial: Vec<f64> = q.iter().zip(&step).map(|(x, s)| x - fraction * s).collect();
+                let trial = self.clamp(trial);
+                let trial_kin = kinematics(&trial);
+                let trial_cost = weighted_cost(markers, measured, &trial_kin);
+                if trial_cost <= cost {
+                    let change = q
+                        .iter()
+                        .zip(&trial)
+                        .map(|(a, b)| (a - b) * (a - b))
+                        .sum::<f64>()
+                        .sqrt();
+                    q = trial;
+                    kin = trial_kin;
+                    cost = trial_cost;
+                    break Some(change);
+                }
+                match self.method {
+                    Method::GaussNewton if fraction > 1e-6 => fraction *= 0.5,
+                    _ => break None,
+                }
+            };
+            match (accepted, self.method) {
+                (Some(change), _) => {
+                    damping = (damping * 0.1).max(1e-12);
+                    if change < self.tolerance {
+                        converged = true;
+                        break;
+                    }
+                }
+                (None, Method::LevenbergMarquardt) if damping < 1e12 => damping *= 10.0,
+                (None, _) => {
+                    // no descent left along the step, q is a (constrained) minimum
+                    converged = true;
+                    break;
+                }
+            }
+        }
+
+        let residuals: Vec<Option<f64>> = markers
+            .iter()
+            .zip(measured)
+            .map(|(marker, target)| target.map(|target| (marker.position(&kin) - target).norm()))
+            .collect();
+        let present: Vec<f64> = residuals.iter().flatten().copied().collect();
+        let rms = if present.is_empty() {
+            0.0
+        } else {
+            (present.iter().map(|r| r * r).sum::<f64>() / present.len() as f64).sqrt()
+        };
+        IkSolution {
+            q,
+            residuals,
+            rms,
+            iterations,
+            converged,
+        }
+    }
+
+    /// Fit a sequence of frames, each warm-started from the solution of the previous one
+    pub fn track(
+        &self,
+        markers: &[Marker],
+        frames: &[Vec<Option<TranslationVector>>],
+        q: &[f64],
+        kinematics: impl Fn(&[f64]) -> Vec<BodyKinematics>,
+    ) -> Vec<IkSolution> {
+        let mut q = q.to_vec();
+        frames
+            .iter()
+            .map(|measured| {
+                let solution = self.solve(markers, measured, &q, &kinematics);
+                q.clone_from(&solution.q);
+                solution
+            })
+            .collect()
+    }
+
+    fn clamp(&self, mut q: Vec<f64>) -> Vec<f64> {
+        for (i, value) in q.iter_mut().enumerate() {
+            let lower = self.lower.get(i).copied().unwrap_or(f64::NEG_INFINITY);
+            let upper = self.upper.get(i).copied().unwrap_or(f64::INFINITY);
+            *value = value.clamp(lower, upper);
+        }
+        q
+    }
+}
+
+impl Default for MarkerIk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn weighted_cost(
+    markers: &[Marker],
+    measured: &[Option<TranslationVector>],
+    kinematics: &[BodyKinematics],
+) -> f64 {
+    markers
+        .iter()
+        .zip(measured)
+        .filter_map(|(marker, target)| {
+            let error = marker.position(kinematics) - (*target)?;
+            Some(marker.weight * error.dot(error))
+        })
+        .sum()
+}
+
+// stacked weighted marker errors and their Jacobian, three rows per present marker
+fn linearize(
+    markers: &[Marker],
+    measured: &[Option<TranslationVector>],
+    kinematics: &[BodyKinematics],
+    dofs: usize,
+) -> (Matrix, Vec<f64>) {
+    let mut jacobian = Matrix::new(0, dofs);
+    let mut residual = Vec::new();
+    for (marker, target) in markers.iter().zip(measured) {
+        let Some(target) = target else { continue };
+        let scale = marker.weight.sqrt();
+        let error = (marker.position(kinematics) - *target).to_array();
+        let columns = kinematics[marker.body].point_jacobian(marker.offset);
+        let mut rows = Matrix::new(3, dofs);
+        for (k, column) in columns.iter().enumerate() {
+            for (row, value) in column.to_array().iter().enumerate() {
+                rows[(row, k)] = scale * value;
+            }
+        }
+        jacobian = jacobian.stack(&rows);
+        residual.extend(error.iter().map(|e| scale * e));
+    }
+    (jacobian, residual)
+}
+
+impl Task {
+    /// Error toward the target and its Jacobian, one row per task dimension
+    pub fn linearize(
+        &self,
+        bodies: &[Body],
+        kinematics: &[BodyKinematics],
+        q: &[f64],
+    ) -> (Vec<f64>, Matrix) {
+        let dofs = q.len();
+        let rows = |columns: &[TranslationVector]| {
+            let mut jacobian = Matrix::new(3, dofs);
+            for (k, column) in columns.iter().enumerate() {
+                for (row, value) in column.to_array().iter().enumerate() {
+                    jacobian[(row, k)] = *value;
+                }
+            }
+            jacobian
+        };
+        match self {
+            Task::Frame {
+                body,
+                offset,
+                target,
+            } => {
+                // the error twist and the Jacobian are both in current frame coordinates
+                let error = pose_error(*offset * kinematics[*body].pose, *target);
+                let mut jacobian = Matrix::new(6, dofs);
+                for (k, column) in kinematics[*body].jacobian.iter().enumerate() {
+                    let column = *column >> *offset;
+                    let values = [column.rotational_motion(), column.translational_motion()];
+                    for (row, value) in values.concat().into_iter().enumerate() {
+                        jacobian[(row, k)] = value;
+                    }
+                }
+                let error = [error.rotational_motion(), error.translational_motion()].concat();
+                (error, jacobian)
+            }
+            Task::Point {
+                body,
+                point,
+                target,
+            } => {
+                let error = *target - kinematics[*body].point_position(*point);
+                (
+                    error.to_array().to_vec(),
+                    rows(&kinematics[*body].point_jacobian(*point)),
+                )
+            }
+            Task::CenterOfMass { target } => {
+                let mass = momentum::total_mass(bodies);
+                let mut columns = vec![TranslationVector::new(); dofs];
+                for (body, kin) in bodies.iter().zip(kinematics) {
+                    let share = body.inertia.mass / mass;
+                    for (total, column) in columns
+                        .iter_mut()
+                        .zip(kin.point_jacobian(body.center_of_mass))
+                    {
+                        *total = *total + column * share;
+                    }
+                }
+                let error = *target - momentum::center_of_mass(bodies, kinematics);
+                (error.to_array().to_vec(), rows(&columns))
+            }
+            Task::Posture { target } => (
+                target.iter().zip(q).map(|(t, x)| t - x).collect(),
+                Matrix::identity(dofs),
+            ),
+        }
+    }
+}
+
+/// Twist in the coordinates of `current` whose exponential carries it onto `target`, the SE(3)
+/// log of the relative transform, with both poses given as world to frame transforms
+pub fn pose_error(current: TransformationMatrix, target: TransformationMatrix) -> MotionVec6 {
+    let rotation = current.to_rotation() * !target.to_rotation();
+    let w = rotation.log();
+    let p = current.transform_point(target.to_translation());
+    let angle = w.norm();
+    let c = if angle < 1e-6 {
+        1.0 / 12.0
+    } else {
+        (1.0 - angle * angle.sin() / (2.0 * (1.0 - angle.cos()))) / (angle * angle)
+    };
+    let v = p - w.cross(p) * 0.5 + w.cross(w.cross(p)) * c;
+    let (w, v) = (w.to_array(), v.to_array());
+    MotionVec6::from_array([w[0], w[1], w[2], v[0], v[1], v[2]])
+}
+
+impl TaskIk {
+    pub fn new() -> Self {
+        Self {
+            tasks: Vec::new(),
+            prioritization: Prioritization::NullSpace,
+            damping: 1e-3,
+            max_iterations: 100,
+            tolerance: 1e-10,
+        }
+    }
+
+    pub fn prioritization(mut self, prioritization: Prioritization) -> Self {
+        self.prioritization = prioritization;
+        self
+    }
+
+    /// Damping of the least-squares inverses, which bounds the steps near singularities
+    pub fn damping(mut self, damping: f64) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Size of the coordinate update at which the iterations stop
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Add a task at `priority`, 0 being the most important, returning its index
+    pub fn add_task(&mut self, task: Task, priority: usize, weight: f64) -> usize {
+        self.tasks.push((task, priority, weight));
+        self.tasks.len() - 1
+    }
+
+    pub fn task_mut(&mut self, index: usize) -> &mut Task {
+        &mut self.tasks[index].0
+    }
+
+    /// One differential inverse kinematics update of `q`
+    pub fn step(&self, bodies: &[Body], kinematics: &[BodyKinematics], q: &[f64]) -> Vec<f64> {
+        let dofs = q.len();
+        let mut levels: Vec<usize> = match self.prioritization {
+            Prioritization::NullSpace => self.tasks.iter().map(|(_, p, _)| *p).collect(),
+            Prioritization::Weighted => vec![0],
+        };
+        levels.sort_unstable();
+        levels.dedup();
+
+        let mut step = vec![0.0; dofs];
+        let mut null_space = Matrix::identity(dofs);
+        for level in levels {
+            let mut jacobian = Matrix::new(0, dofs);
+            let mut error = Vec::new();
+            for (task, priority, weight) in &self.tasks {
+                if self.prioritization == Prioritization::NullSpace && *priority != level {
+                    continue;
+                }
+                let (task_error, task_jacobian) = task.linearize(bodies, kinematics, q);
+                let scale = weight.sqrt();
+                jacobian = jacobian.stack(&task_jacobian.scale(scale));
+                error.extend(task_error.iter().map(|e| scale * e));
+            }
+            // what is left of the error once the higher levels have moved
+            let achieved = jacobian.multiply_vector(&step);
+            let remaining: Vec<f64> = error.iter().zip(achieved).map(|(e, a)| e - a).collect();
+            let projected = &jacobian * &null_space;
+            let (Some(inverse), Some(pseudo_inverse)) = (
+                damped_inverse(&projected, self.damping),
+                damped_inverse(&projected, 1e-6),
+            ) else {
+                break;
+            };
+            for (s, ds) in step.iter_mut().zip(inverse.multiply_vector(&remaining)) {
+                *s += ds;
+            }
+            // the projector comes from an (almost) exact pseudo-inverse, a damped one would leak
+            // lower priority motion into the directions of this level
+            null_space = &null_space - &(&pseudo_inverse * &projected);
+        }
+        step
+    }
+
+    /// Iterate `step` from `q` until the update becomes negligible
+    pub fn solve(
+        &self,
+        bodies: &[Body],
+        q: &[f64],
+        kinematics: impl Fn(&[f64]) -> Vec<BodyKinematics>,
+    ) -> TaskSolution {
+        let mut q = q.to_vec();
+        let mut iterations = 0;
+        let mut converged = false;
+        while iterations < self.max_iterations {
+            iterations += 1;
+            let step = self.step(bodies, &kinematics(&q), &q);
+            for (x, s) in q.iter_mut().zip(&step) {
+                *x += s;
+            }
+            if step.iter().map(|s| s * s).sum::<f64>().sqrt() < self.tolerance {
+                converged = true;
+                break;
+            }
+        }
+        let kin = kinematics(&q);
+        let errors = self
+            .tasks
+            .iter()
+            .map(|(task, _, _)| {
+                let (error, _) = task.linearize(bodies, &kin, &q);
+                error.iter().map(|e| e * e).sum::<f64>().sqrt()
+            })
+            .collect();
+        TaskSolution {
+            q,
+            errors,
+            iterations,
+            converged,
+        }
+    }
+}
+
+impl Default for TaskIk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// J^T (J J^T + damping^2 I)^-1
+fn damped_inverse(jacobian: &Matrix, damping: f64) -> Option<Matrix> {
+    let mut gram = jacobian * &jacobian.transpose();
+    for i in 0..gram.rows() {
+        gram[(i, i)] += damping * damping;
+    }
+    let inverse = gram.solve_matrix(&Matrix::identity(gram.rows()))?;
+    Some(&jacobian.transpose() * &inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Inertia, RotationMatrix};
+
+    const LENGTH: f64 = 0.4;
+
+    // planar two-link arm hinged about z, q = [shoulder, elbow]
+    fn arm(q: &[f64]) -> Vec<BodyKinematics> {
+        let hinge = MotionVec6::from_array([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        let mut upper =
+            BodyKinematics::new(RotationMatrix::from_z_rotation(q[0]).as_transform(), 2);
+        upper.jacobian[0] = hinge;
+        let elbow = RotationMatrix::from_z_rotation(q[1])
+            + TranslationVector::from_array([LENGTH, 0.0, 0.0]);
+        let mut lower = BodyKinematics::new(elbow * upper.pose, 2);
+        lower.jacobian = vec![hinge >> elbow, hinge];
+        vec![upper, lower]
+    }
+
+    fn markers() -> Vec<Marker> {
+        vec![
+            Marker::new(
+                "elbow",
+                0,
+                TranslationVector::from_array([LENGTH, 0.02, 0.0]),
+            ),
+            Marker::new(
+                "forearm",
+                1,
+                TranslationVector::from_array([0.2, 0.0, 0.03]),
+            ),
+            Marker::new(
+                "wrist",
+                1,
+                TranslationVector::from_array([0.35, -0.01, 0.0]),
+            )
+            .weight(4.0),
+        ]
+    }
+
+    fn observe(q: &[f64]) -> Vec<Option<TranslationVector>> {
+        let kin = arm(q);
+        markers().iter().map(|m| Some(m.position(&kin))).collect()
+    }
+
+    #[test]
+    fn recovers_pose_and_tracks_frames() {
+        for method in [Method::GaussNewton, Method::LevenbergMarquardt] {
+            let ik = MarkerIk::new().method(method);
+            let mut measured = observe(&[0.7, 1.1]);
+            measured[1] = None;
+            let solution = ik.solve(&markers(), &measured, &[0.0, 0.3], arm);
+            assert!(solution.converged);
+            assert!((solution.q[0] - 0.7).abs() < 1e-8 && (solution.q[1] - 1.1).abs() < 1e-8);
+            assert!(solution.residuals[1].is_none() && solution.rms < 1e-8);
+
+            let frames: Vec<_> = (0..5).map(|i| observe(&[0.1 * i as f64, 0.5])).collect();
+            let track = ik.track(&markers(), &frames, &[0.0, 0.5], arm);
+            assert!(track.iter().all(|solution| solution.iterations < 20));
+            assert!((track[4].q[0] - 0.4).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn respects_joint_limits() {
+        let ik = MarkerIk::new().limits(vec![-3.0, 0.0], vec![3.0, 0.8]);
+        let solution = ik.solve(&markers(), &observe(&[0.2, 1.2]), &[0.0, 0.0], arm);
+        assert!((solution.q[1] - 0.8).abs() < 1e-12);
+        assert!(solution.rms > 1e-3);
+        assert!(solution.residuals.iter().all(|r| r.is_some()));
+    }
+
+    #[test]
+    fn frame_and_center_of_mass_targets() {
+        let inertia = Inertia::new(2.0, 0.01, 0.01, 0.01, 0.0, 0.0, 0.0);
+        let com = TranslationVector::from_array([0.2, 0.0, 0.0]);
+        let bodies = [
+            Body::new("upper", None, com, inertia),
+            Body::new("lower", Some(0), com, inertia),
+        ];
+        let hand = TranslationVector::from_array([0.3, 0.0, 0.0]).as_transform();
+        let goal = arm(&[0.3, 0.9]);
+
+        let mut ik = TaskIk::new();
+        ik.add_task(
+            Task::Frame {
+                body: 1,
+                offset: hand,
+                target: hand * goal[1].pose,
+            },
+            0,
+            1.0,
+        );
+        let solution = ik.solve(&bodies, &[0.0, 0.2], arm);
+        assert!(solution.converged && solution.errors[0] < 1e-9);
+        assert!((solution.q[0] - 0.3).abs() < 1e-8 && (solution.q[1] - 0.9).abs() < 1e-8);
+
+        *ik.task_mut(0) = Task::CenterOfMass {
+            target: momentum::center_of_mass(&bodies, &goal),
+        };
+        let solution = ik.solve(&bodies, &[0.0, 0.2], arm);
+        assert!((solution.q[0] - 0.3).abs() < 1e-8 && (solution.q[1] - 0.9).abs() < 1e-8);
+    }
+
+    #[test]
+    fn posture_yields_to_higher_priority() {
+        let point = TranslationVector::from_array([LENGTH, 0.0, 0.0]);
+        let target = arm(&[0.5, 1.0])[1].point_position(point);
+        for prioritization in [Prioritization::NullSpace, Prioritization::Weighted] {
+            let mut ik = TaskIk::new().prioritization(prioritization);
+            ik.add_task(
+                Task::Point {
+                    body: 1,
+                    point,
+                    target,
+                },
+                0,
+                1.0,
+            );
+            ik.add_task(
+                Task::Posture {
+                    target: vec![0.0, 0.0],
+                },
+                1,
+                0.01,
+            );
+            let solution = ik.solve(&[], &[0.1, 0.6], arm);
+            // with null-space priorities only the damping lets the posture disturb the point
+            match prioritization {
+                Prioritization::NullSpace => assert!(solution.errors[0] < 1e-6),
+                Prioritization::Weighted => assert!(solution.errors[0] > 1e-4),
+            }
+            assert!(solution.errors[1] > 0.1);
+        }
+    }
+}
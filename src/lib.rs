@@ -1,4 +1,30 @@
+pub mod body;
+pub mod broadphase;
+pub mod bushing;
+pub mod bvh;
+pub mod c3d;
+pub mod cmc;
+pub mod compliant;
+pub mod constraint;
+pub mod contact;
+pub mod coupling;
+pub mod forceplate;
+pub mod friction;
+pub mod geometry;
+pub mod gltf;
+pub mod ik;
+pub mod linalg;
+pub mod momentum;
+pub mod muscle;
 pub mod ops;
+pub mod optimization;
+pub mod passive;
+pub mod path;
+pub mod rotor;
+pub mod scaling;
+pub mod simulator;
+pub mod storage;
+pub mod wrapping;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct ForceVec6 {
@@ -113,4 +139,87 @@ mod tests {
 
         assert_eq!(force.dot(motion), 91.0);
     }
+
+    #[test]
+    fn translation_of_rotated_transform() {
+        let rotation = RotationMatrix::from_x_rotation(0.3) * RotationMatrix::from_z_rotation(1.1);
+        let translation = TranslationVector::from_array([1.0, -2.0, 0.5]);
+        let transform = rotation + translation;
+
+        // used to read back a rotated copy of the translation
+        for (value, expected) in transform.to_translation().data.iter().zip(translation.data) {
+            assert!((value - expected).abs() < 1e-12);
+        }
+
+        // used to compose the inverse rotation and translation in the wrong order
+        let identity = transform.multiply(transform.inverse_transform());
+        let expected = RotationMatrix::from_x_rotation(0.0).as_transform();
+        for (value, expected) in identity.data.iter().zip(expected.data) {
+            assert!((value - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn motion_multiply_couples_center_of_mass() {
+        let inertia = Inertia::new(2.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0);
+
+        // spinning about an axis through the origin moves an offset center of mass,
+        // which used to be missing from both the linear and angular momentum
+        let spin = MotionVec6::from_array([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        let momentum =
+            inertia.motion_multiply(spin, TranslationVector::from_array([1.0, 0.0, 0.0]));
+        assert_eq!(momentum.data, [0.0, 0.0, 3.0, 0.0, 2.0, 0.0]);
+
+        // translating gives linear momentum m v and its moment about the origin
+        let slide = MotionVec6::from_array([0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        let momentum =
+            inertia.motion_multiply(slide, TranslationVector::from_array([0.0, 1.0, 0.0]));
+        assert_eq!(momentum.data, [0.0, 0.0, -2.0, 2.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn inverse_transform() {
+        let rotation = RotationMatrix::from_x_rotation(0.3) * RotationMatrix::from_z_rotation(1.1);
+        let translation = TranslationVector::from_array([1.0, -2.0, 0.5]);
+        let transform = rotation + translation;
+
+        let translation_error = transform.to_translation() - translation;
+        assert!(translation_error.norm() < 1e-12);
+
+        let identity = transform * !transform;
+        for (value, expected) in identity
+            .data
+            .iter()
+            .zip(TransformationMatrix::identity().data)
+        {
+            assert!((value - expected).abs() < 1e-12);
+        }
+
+        let point = TranslationVector::from_array([0.2, 0.4, -0.7]);
+        let round_trip = (!transform).transform_point(transform.transform_point(point));
+        assert!((round_trip - point).norm() < 1e-12);
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        use Basis::{X, Y, Z};
+        for order in [
+            [X, Y, Z],
+            [Z, X, Y],
+            [Y, Z, X],
+            [X, Z, Y],
+            [Z, Y, X],
+            [Y, X, Z],
+        ] {
+            for angles in [[0.3, -0.7, 2.5], [-1.2, std::f64::consts::FRAC_PI_2, 0.4]] {
+                let rotation = RotationMatrix::from_euler(order, angles);
+                let recovered = RotationMatrix::from_euler(order, rotation.to_euler(order));
+                for (value, expected) in recovered.data.iter().zip(rotation.data) {
+                    assert!((value - expected).abs() < 1e-9);
+                }
+            }
+            let angles = RotationMatrix::from_euler(order, [0.3, -0.7, 2.5]).to_euler(order);
+            assert!((angles[0] - 0.3).abs() < 1e-12 && (angles[2] - 2.5).abs() < 1e-12);
+        }
+    }
 }
@@ -0,0 +1,292 @@
+use core::ops::{Add, Index, IndexMut, Mul, Neg, Sub};
+
+/// Dense row-major matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            rows,
+            cols,
+            data: vec![0.0; rows * cols],
+        }
+    }
+
+    pub fn from_vec(rows: usize, cols: usize, data: Vec<f64>) -> Self {
+        assert_eq!(data.len(), rows * cols);
+        Self { rows, cols, data }
+    }
+
+    pub fn identity(size: usize) -> Self {
+        Self::from_diagonal(&vec![1.0; size])
+    }
+
+    pub fn from_diagonal(diagonal: &[f64]) -> Self {
+        let mut matrix = Self::new(diagonal.len(), diagonal.len());
+        for (i, value) in diagonal.iter().enumerate() {
+            matrix[(i, i)] = *value;
+        }
+        matrix
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn row(&self, row: usize) -> &[f64] {
+        &self.data[row * self.cols..(row + 1) * self.cols]
+    }
+
+    pub fn column(&self, col: usize) -> Vec<f64> {
+        (0..self.rows).map(|row| self[(row, col)]).collect()
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut transposed = Self::new(self.cols, self.rows);
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                transposed[(j, i)] = self[(i, j)];
+            }
+        }
+        transposed
+    }
+
+    pub fn multiply(&self, rhs: &Matrix) -> Self {
+        assert_eq!(self.cols, rhs.rows);
+        let mut product = Self::new(self.rows, rhs.cols);
+        for i in 0..self.rows {
+            for k in 0..self.cols {
+                let a = self[(i, k)];
+                if a == 0.0 {
+                    continue;
+                }
+                for j in 0..rhs.cols {
+                    product.data[i * rhs.cols + j] += a * rhs.data[k * rhs.cols + j];
+                }
+            }
+        }
+        product
+    }
+
+    pub fn multiply_vector(&self, rhs: &[f64]) -> Vec<f64> {
+        assert_eq!(self.cols, rhs.len());
+        (0..self.rows)
+            .map(|i| self.row(i).iter().zip(rhs).map(|(a, b)| a * b).sum())
+            .collect()
+    }
+
+    /// The product of the transpose of this matrix with `rhs`
+    pub fn transpose_multiply_vector(&self, rhs: &[f64]) -> Vec<f64> {
+        assert_eq!(self.rows, rhs.len());
+        let mut product = vec![0.0; self.cols];
+        for (i, b) in rhs.iter().enumerate() {
+            for (p, a) in product.iter_mut().zip(self.row(i)) {
+                *p += a * b;
+            }
+        }
+        product
+    }
+
+    pub fn scale(&self, rhs: f64) -> Self {
+        Self::from_vec(
+            self.rows,
+            self.cols,
+            self.data.iter().map(|value| value * rhs).collect(),
+        )
+    }
+
+    /// Copy of the rows selected by `rows`, in that order
+    pub fn select_rows(&self, rows: &[usize]) -> Self {
+        let mut selected = Self::new(rows.len(), self.cols);
+        for (i, row) in rows.iter().enumerate() {
+            selected.data[i * self.cols..(i + 1) * self.cols].copy_from_slice(self.row(*row));
+        }
+        selected
+    }
+
+    /// Stack the rows of `other` below the rows of this matrix
+    pub fn stack(&self, other: &Matrix) -> Self {
+        if self.rows == 0 {
+            return other.clone();
+        }
+        assert_eq!(self.cols, other.cols);
+        let mut data = self.data.clone();
+        data.extend_from_slice(&other.data);
+        Self::from_vec(self.rows + other.rows, self.cols, data)
+    }
+
+    /// Solve `self * x = rhs` by LU decomposition with partial pivoting
+    pub fn solve(&self, rhs: &[f64]) -> Option<Vec<f64>> {
+        assert_eq!(self.rows, self.cols);
+        assert_eq!(self.rows, rhs.len());
+        let n = self.rows;
+        let mut lu = self.data.clone();
+        let mut x = rhs.to_vec();
+        let scale = lu.iter().fold(0.0_f64, |max, value| max.max(value.abs()));
+
+        for k in 0..n {
+            let pivot = (k..n)
+                .max_by(|a, b| lu[a * n + k].abs().total_cmp(&lu[b * n + k].abs()))
+                .unwrap();
+            if lu[pivot * n + k].abs() <= f64::EPSILON * scale.max(1.0) * n as f64 {
+                return None;
+            }
+            if pivot != k {
+                for j in 0..n {
+                    lu.swap(k * n + j, pivot * n + j);
+                }
+                x.swap(k, pivot);
+            }
+            for i in k + 1..n {
+                let factor = lu[i * n + k] / lu[k * n + k];
+                if factor == 0.0 {
+                    continue;
+                }
+                for j in k..n {
+                    lu[i * n + j] -= factor * lu[k * n + j];
+                }
+                x[i] -= factor * x[k];
+            }
+        }
+        for k in (0..n).rev() {
+            let sum: f64 = (k + 1..n).map(|j| lu[k * n + j] * x[j]).sum();
+            x[k] = (x[k] - sum) / lu[k * n + k];
+        }
+        Some(x)
+    }
+
+    /// Solve `self * X = rhs` column by column
+    pub fn solve_matrix(&self, rhs: &Matrix) -> Option<Matrix> {
+        let mut solution = Matrix::new(self.cols, rhs.cols);
+        for j in 0..rhs.cols {
+            let column = self.solve(&rhs.column(j))?;
+            for (i, value) in column.into_iter().enumerate() {
+                solution[(i, j)] = value;
+            }
+        }
+        Some(solution)
+    }
+
+    /// Solve `self * x = rhs` for a symmetric positive definite matrix by Cholesky decomposition
+    pub fn cholesky_solve(&self, rhs: &[f64]) -> Option<Vec<f64>> {
+        assert_eq!(self.rows, self.cols);
+        let n = self.rows;
+        let mut l = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..=i {
+                let sum: f64 = (0..j).map(|k| l[i * n + k] * l[j * n + k]).sum();
+                if i == j {
+                    let diagonal = self[(i, i)] - sum;
+                    if diagonal <= 0.0 {
+                        return None;
+                    }
+                    l[i * n + i] = diagonal.sqrt();
+                } else {
+                    l[i * n + j] = (self[(i, j)] - sum) / l[j * n + j];
+                }
+            }
+        }
+        let mut y = rhs.to_vec();
+        for i in 0..n {
+            let sum: f64 = (0..i).map(|k| l[i * n + k] * y[k]).sum();
+            y[i] = (y[i] - sum) / l[i * n + i];
+        }
+        for i in (0..n).rev() {
+            let sum: f64 = (i + 1..n).map(|k| l[k * n + i] * y[k]).sum();
+            y[i] = (y[i] - sum) / l[i * n + i];
+        }
+        Some(y)
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = f64;
+
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        &self.data[index.0 * self.cols + index.1]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.data[index.0 * self.cols + index.1]
+    }
+}
+
+impl Mul<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: &Matrix) -> Self::Output {
+        self.multiply(rhs)
+    }
+}
+
+impl Add<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn add(self, rhs: &Matrix) -> Self::Output {
+        assert_eq!((self.rows, self.cols), (rhs.rows, rhs.cols));
+        Matrix::from_vec(
+            self.rows,
+            self.cols,
+            self.data
+                .iter()
+                .zip(&rhs.data)
+                .map(|(a, b)| a + b)
+                .collect(),
+        )
+    }
+}
+
+impl Sub<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn sub(self, rhs: &Matrix) -> Self::Output {
+        assert_eq!((self.rows, self.cols), (rhs.rows, rhs.cols));
+        Matrix::from_vec(
+            self.rows,
+            self.cols,
+            self.data
+                .iter()
+                .zip(&rhs.data)
+                .map(|(a, b)| a - b)
+                .collect(),
+        )
+    }
+}
+
+impl Neg for &Matrix {
+    type Output = Matrix;
+
+    fn neg(self) -> Self::Output {
+        self.scale(-1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solve() {
+        let a = Matrix::from_vec(3, 3, vec![4.0, 1.0, 2.0, 1.0, 3.0, 0.5, 2.0, 0.5, 5.0]);
+        let b = [1.0, -2.0, 3.0];
+
+        for x in [a.solve(&b).unwrap(), a.cholesky_solve(&b).unwrap()] {
+            let residual = a.multiply_vector(&x);
+            for (r, b) in residual.iter().zip(b) {
+                assert!((r - b).abs() < 1e-12);
+            }
+        }
+        assert!(Matrix::new(2, 2).solve(&[1.0, 1.0]).is_none());
+    }
+}
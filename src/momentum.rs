@@ -0,0 +1,192 @@
+use crate::body::{Body, BodyKinematics};
+use crate::{ForceVec6, TranslationVector};
+
+pub fn total_mass(bodies: &[Body]) -> f64 {
+    bodies.iter().map(|body| body.inertia.mass).sum()
+}
+
+pub fn kinetic_energy(bodies: &[Body], kinematics: &[BodyKinematics]) -> f64 {
+    bodies
+        .iter()
+        .zip(kinematics)
+        .map(|(body, kin)| {
+            let momentum = body
+                .inertia
+                .motion_multiply(kin.velocity, body.center_of_mass);
+            0.5 * momentum.dot(kin.velocity)
+        })
+        .sum()
+}
+
+/// Gravitational potential energy relative to the world origin, with `gravity` in world coordinates
+pub fn potential_energy(
+    bodies: &[Body],
+    kinematics: &[BodyKinematics],
+    gravity: TranslationVector,
+) -> f64 {
+    bodies
+        .iter()
+        .zip(kinematics)
+        .map(|(body, kin)| {
+            -body.inertia.mass * gravity.dot(kin.point_position(body.center_of_mass))
+        })
+        .sum()
+}
+
+pub fn center_of_mass(bodies: &[Body], kinematics: &[BodyKinematics]) -> TranslationVector {
+    let weighted = bodies
+        .iter()
+        .zip(kinematics)
+        .fold(TranslationVector::new(), |sum, (body, kin)| {
+            sum + kin.point_position(body.center_of_mass) * body.inertia.mass
+        });
+    weighted * (1.0 / total_mass(bodies))
+}
+
+pub fn center_of_mass_velocity(
+    bodies: &[Body],
+    kinematics: &[BodyKinematics],
+) -> TranslationVector {
+    let momentum = spatial_momentum(bodies, kinematics);
+    TranslationVector::from_array(momentum.translational_force()) * (1.0 / total_mass(bodies))
+}
+
+/// Total spatial momentum in world coordinates, taken about the world origin
+pub fn spatial_momentum(bodies: &[Body], kinematics: &[BodyKinematics]) -> ForceVec6 {
+    bodies
+        .iter()
+        .zip(kinematics)
+        .fold(ForceVec6::new(), |sum, (body, kin)| {
+            let momentum = body
+                .inertia
+                .motion_multiply(kin.velocity, body.center_of_mass);
+            sum + (momentum >> kin.pose.inverse_transform())
+        })
+}
+
+/// Total spatial momentum about the center of mass, in world-aligned coordinates
+pub fn centroidal_momentum(bodies: &[Body], kinematics: &[BodyKinematics]) -> ForceVec6 {
+    let to_centroid = center_of_mass(bodies, kinematics).as_transform();
+    spatial_momentum(bodies, kinematics) >> to_centroid
+}
+
+/// Centroidal momentum matrix A_G(q), one column per generalized velocity
+pub fn centroidal_momentum_matrix(
+    bodies: &[Body],
+    kinematics: &[BodyKinematics],
+) -> Vec<ForceVec6> {
+    let to_centroid = center_of_mass(bodies, kinematics).as_transform();
+    let dofs = kinematics.first().map_or(0, |kin| kin.jacobian.len());
+    let mut columns = vec![ForceVec6::new(); dofs];
+    for (body, kin) in bodies.iter().zip(kinematics) {
+        let to_world = kin.pose.inverse_transform();
+        for (column, motion) in columns.iter_mut().zip(&kin.jacobian) {
+            let momentum = body.inertia.motion_multiply(*motion, body.center_of_mass);
+            *column += momentum >> to_world >> to_centroid;
+        }
+    }
+    columns
+}
+
+/// The dA_G/dt qd term, so that the rate of centroidal momentum is A_G qdd + dA_G/dt qd
+pub fn centroidal_momentum_bias(bodies: &[Body], kinematics: &[BodyKinematics]) -> ForceVec6 {
+    let to_centroid = center_of_mass(bodies, kinematics).as_transform();
+    bodies
+        .iter()
+        .zip(kinematics)
+        .fold(ForceVec6::new(), |sum, (body, kin)| {
+            let inertia = |motion| body.inertia.motion_multiply(motion, body.center_of_mass);
+            let rate = inertia(kin.bias_acceleration) + (kin.velocity ^ inertia(kin.velocity));
+            sum + (rate >> kin.pose.inverse_transform() >> to_centroid)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Inertia, MotionVec6, RotationMatrix};
+
+    fn spinning_link(angle: f64, rate: f64) -> (Body, BodyKinematics) {
+        let body = Body::new(
+            "link",
+            None,
+            TranslationVector::from_array([1.0, 0.0, 0.0]),
+            Inertia::new(2.0, 0.1, 0.2, 0.3, 0.0, 0.0, 0.0),
+        );
+        let mut kin = BodyKinematics::new(RotationMatrix::from_z_rotation(angle).as_transform(), 1);
+        kin.jacobian[0] = MotionVec6::from_array([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        kin.velocity = kin.jacobian[0] * rate;
+        (body, kin)
+    }
+
+    #[test]
+    fn energy_of_spinning_link() {
+        let (body, kin) = spinning_link(0.5, 3.0);
+        let (bodies, kinematics) = ([body], [kin]);
+
+        // parallel axis: (i_zz + m c^2) w^2 / 2
+        let expected = 0.5 * (0.3 + 2.0) * 9.0;
+        assert!((kinetic_energy(&bodies, &kinematics) - expected).abs() < 1e-12);
+
+        let gravity = TranslationVector::from_array([0.0, -9.81, 0.0]);
+        let height = 0.5_f64.sin();
+        let expected = 2.0 * 9.81 * height;
+        assert!((potential_energy(&bodies, &kinematics, gravity) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn centroidal_momentum_of_spinning_link() {
+        let (body, kin) = spinning_link(0.5, 3.0);
+        let (bodies, kinematics) = ([body], [kin]);
+
+        let com = center_of_mass(&bodies, &kinematics).to_array();
+        assert!((com[0] - 0.5_f64.cos()).abs() < 1e-12);
+        assert!((com[1] - 0.5_f64.sin()).abs() < 1e-12);
+
+        let h_g = centroidal_momentum(&bodies, &kinematics);
+        let expected = [
+            0.0,
+            0.0,
+            0.3 * 3.0,
+            -2.0 * 3.0 * 0.5_f64.sin(),
+            2.0 * 3.0 * 0.5_f64.cos(),
+            0.0,
+        ];
+        let a_g = centroidal_momentum_matrix(&bodies, &kinematics)[0] * 3.0;
+        for ((h, a), e) in h_g.data.iter().zip(a_g.data).zip(expected) {
+            assert!((h - e).abs() < 1e-12);
+            assert!((a - e).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn centroidal_bias_matches_rate_of_momentum() {
+        // a fixed base keeps the system center of mass away from the link's own
+        let base = Body::new(
+            "base",
+            None,
+            TranslationVector::new(),
+            Inertia::new(3.0, 0.1, 0.1, 0.1, 0.0, 0.0, 0.0),
+        );
+        let system = |angle: f64| {
+            let (link, kin) = spinning_link(angle, 3.0);
+            let base_kin = BodyKinematics::new(RotationMatrix::identity().as_transform(), 1);
+            ([base.clone(), link], [base_kin, kin])
+        };
+        let momentum = |angle: f64| {
+            let (bodies, kinematics) = system(angle);
+            centroidal_momentum_matrix(&bodies, &kinematics)[0] * 3.0
+        };
+
+        // with qdd = 0 the bias is the time derivative of A_G qd along the motion
+        let dt = 1e-6;
+        let ahead = momentum(0.5 + 3.0 * dt);
+        let behind = momentum(0.5 - 3.0 * dt);
+        let (bodies, kinematics) = system(0.5);
+        let bias = centroidal_momentum_bias(&bodies, &kinematics);
+        assert!(bias.data.iter().any(|value| value.abs() > 1.0));
+        for ((a, b), value) in ahead.data.iter().zip(behind.data).zip(bias.data) {
+            assert!(((a - b) / (2.0 * dt) - value).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use crate::linalg::Matrix;
+
+/// Published Hill-type muscle formulation providing the force curves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MuscleModel {
+    /// Thelen (2003), exponential curves and an undamped fiber
+    Thelen2003,
+    /// Closed-form curves with a damped fiber, which keeps the fiber velocity defined at zero
+    /// activation; the curve parameters approximate the defaults of Millard et al. (2012) but not
+    /// its quintic Bézier curves
+    Damped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tendon {
+    /// inextensible tendon, only activation is a state
+    Rigid,
+    /// elastic tendon, the fiber length is a second state
+    Compliant,
+}
+
+/// Hill-type muscle-tendon unit with a pennated fiber of constant thickness
+///
+/// The state vector is `[activation]` with a rigid tendon and `[activation, fiber_length]` with a
+/// compliant one. Both are first order and are integrated from `derivatives` next to the
+/// multibody state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Muscle {
+    pub max_isometric_force: f64,
+    pub optimal_fiber_length: f64,
+    pub tendon_slack_length: f64,
+    /// pennation angle at the optimal fiber length
+    pub pennation_angle: f64,
+    /// in optimal fiber lengths per second
+    pub max_contraction_velocity: f64,
+    pub activation_time_constant: f64,
+    pub deactivation_time_constant: f64,
+    pub min_activation: f64,
+    pub model: MuscleModel,
+    pub tendon: Tendon,
+}
+
+/// Fiber and tendon quantities of a muscle at one instant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FiberState {
+    pub activation: f64,
+    pub fiber_length: f64,
+    /// positive when lengthening
+    pub fiber_velocity: f64,
+    pub pennation_angle: f64,
+    pub tendon_length: f64,
+    /// active fiber force along the fiber
+    pub active_force: f64,
+    /// passive elastic and damping fiber force along the fiber
+    pub passive_force: f64,
+    /// force along the muscle path
+    pub tendon_force: f64,
+}
+
+// normalized curve parameters of a muscle model
+struct Curves {
+    active_width: f64,
+    passive_strain: f64,
+    passive_shape: f64,
+    tendon_strain: f64,
+    toe_force: f64,
+    toe_shape: f64,
+    eccentric_force: f64,
+    concentric_shape: f64,
+    damping: f64,
+}
+
+impl MuscleModel {
+    fn curves(&self) -> Curves {
+        match self {
+            MuscleModel::Thelen2003 => Curves {
+                active_width: 0.45,
+                passive_strain: 0.6,
+                passive_shape: 4.0,
+                tendon_strain: 0.04,
+                toe_force: 0.33,
+                toe_shape: 3.0,
+                eccentric_force: 1.8,
+                concentric_shape: 0.25,
+                damping: 0.0,
+            },
+            MuscleModel::Damped => Curves {
+                active_width: 0.3,
+                passive_strain: 0.7,
+                passive_shape: 4.0,
+                tendon_strain: 0.049,
+                toe_force: 2.0 / 3.0,
+                toe_shape: 3.0,
+                eccentric_force: 1.4,
+                concentric_shape: 0.25,
+                damping: 0.1,
+            },
+        }
+    }
+}
+
+impl Curves {
+    fn active_force_length(&self, length: f64) -> f64 {
+        (-(length - 1.0).powi(2) / self.active_width).exp()
+    }
+
+    fn passive_force_length(&self, length: f64) -> f64 {
+        if length <= 1.0 {
+            return 0.0;
+        }
+        let k = self.passive_shape;
+        ((k * (length - 1.0) / self.passive_strain).exp() - 1.0) / (k.exp() - 1.0)
+    }
+
+    // exponential toe region joined with C1 continuity to a linear region reaching 1 at
+    // `tendon_strain`
+    fn tendon_force_strain(&self, strain: f64) -> f64 {
+        if strain <= 0.0 {
+            return 0.0;
+        }
+        let k = self.toe_shape;
+        let slope = self.toe_force * k * k.exp() / (k.exp() - 1.0);
+        let toe_strain = slope * self.tendon_strain / (1.0 - self.toe_force + slope);
+        if strain <= toe_strain {
+            self.toe_force * ((k * strain / toe_strain).exp() - 1.0) / (k.exp() - 1.0)
+        } else {
+            self.toe_force + slope / toe_strain * (strain - toe_strain)
+        }
+    }
+
+    // Hill hyperbola when shortening and its eccentric counterpart from Thelen (2003), with the
+    // velocity normalized by the maximum contraction velocity
+    fn force_velocity(&self, velocity: f64) -> f64 {
+        if velocity <= -1.0 {
+            0.0
+        } else if velocity <= 0.0 {
+            (1.0 + velocity) / (1.0 - velocity / self.concentric_shape)
+        } else {
+            let c = 2.0 + 2.0 / self.concentric_shape;
+            let f = self.eccentric_force - 1.0;
+            (f + c * self.eccentric_force * velocity) / (f + c * velocity)
+        }
+    }
+}
+
+impl Muscle {
+    pub fn new(
+        max_isometric_force: f64,
+        optimal_fiber_length: f64,
+        tendon_slack_length: f64,
+        pennation_angle: f64,
+    ) -> Self {
+        Self {
+            max_isometric_force,
+            optimal_fiber_length,
+            tendon_slack_length,
+            pennation_angle,
+            max_contraction_velocity: 10.0,
+            activation_time_constant: 0.01,
+            deactivation_time_constant: 0.04,
+            min_activation: 0.01,
+            model: MuscleModel::Thelen2003,
+            tendon: Tendon::Rigid,
+        }
+    }
+
+    pub fn model(mut self, model: MuscleModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    pub fn tendon(mut self, tendon: Tendon) -> Self {
+        self.tendon = tendon;
+        self
+    }
+
+    pub fn time_constants(mut self, activation: f64, deactivation: f64) -> Self {
+        self.activation_time_constant = activation;
+        self.deactivation_time_constant = deactivation;
+        self
+    }
+
+    pub fn max_contraction_velocity(mut self, velocity: f64) -> Self {
+        self.max_contraction_velocity = velocity;
+        self
+    }
+
+    pub fn states(&self) -> usize {
+        match self.tendon {
+            Tendon::Rigid => 1,
+            Tendon::Compliant => 2,
+        }
+    }
+
+    /// States at `activation` with the fiber in static equilibrium at musculotendon `length`
+    pub fn initial_states(&self, activation: f64, length: f64) -> Option<Vec<f64>> {
+        match self.tendon {
+            Tendon::Rigid => Some(vec![activation]),
+            Tendon::Compliant => {
+                let curves = self.model.curves();
+                let activation = activation.clamp(self.min_activation, 1.0);
+                // tendon force minus fiber force along the tendon falls as the fiber lengthens
+                let imbalance = |fiber_length: f64| {
+                    let (cos, tendon_length) = self.pennation(fiber_length, length);
+                    let normalized = fiber_length / self.optimal_fiber_length;
+                    let fiber = activation * curves.active_force_length(normalized)
+                        + curves.passive_force_length(normalized);
+                    curves.tendon_force_strain(tendon_length / self.tendon_slack_length - 1.0)
+                        - fiber * cos
+                };
+                let mut low = self.min_fiber_length();
+                let mut high = self.min_fiber_length().max(length);
+                if imbalance(low) < 0.0 || imbalance(high) > 0.0 {
+                    return None;
+                }
+                for _ in 0..100 {
+                    let middle = 0.5 * (low + high);
+                    if imbalance(middle) > 0.0 {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+                Some(vec![activation, 0.5 * (low + high)])
+            }
+        }
+    }
+
+    /// Fiber and tendon state for musculotendon `length` changing at `lengthening_speed`
+    pub fn fiber(&self, states: &[f64], length: f64, lengthening_speed: f64) -> FiberState {
+        let curves = self.model.curves();
+        let activation = states[0].clamp(self.min_activation, 1.0);
+        let speed_scale = self.max_contraction_velocity * self.optimal_fiber_length;
+        let (fiber_length, cos, tendon_length, velocity) = match self.tendon {
+            Tendon::Rigid => {
+                let along = length - self.tendon_slack_length;
+                let fiber_length = along.hypot(self.thickness()).max(self.min_fiber_length());
+                let cos = (along / fiber_length).clamp(0.0, 1.0);
+                (
+                    fiber_length,
+                    cos,
+                    self.tendon_slack_length,
+                    lengthening_speed * cos / speed_scale,
+                )
+            }
+            Tendon::Compliant => {
+                let fiber_length = states[1].max(self.min_fiber_length());
+                let (cos, tendon_length) = self.pennation(fiber_length, length);
+                let normalized = fiber_length / self.optimal_fiber_length;
+                let tendon =
+                    curves.tendon_force_strain(tendon_length / self.tendon_slack_length - 1.0);
+                let velocity = self.equilibrium_velocity(
+                    &curves,
+                    activation * curves.active_force_length(normalized),
+                    curves.passive_force_length(normalized),
+                    tendon / cos,
+                );
+                (fiber_length, cos, tendon_length, velocity)
+            }
+        };
+
+        let normalized = fiber_length / self.optimal_fiber_length;
+        let active =
+            activation * curves.active_force_length(normalized) * curves.force_velocity(velocity);
+        let passive = curves.passive_force_length(normalized) + curves.damping * velocity;
+        let fiber_force = self.max_isometric_force * (active + passive);
+        FiberState {
+            activation,
+            fiber_length,
+            fiber_velocity: velocity * speed_scale,
+            pennation_angle: cos.acos(),
+            tendon_length,
+            active_force: self.max_isometric_force * active,
+            passive_force: self.max_isometric_force * passive,
+            tendon_force: fiber_force * cos,
+        }
+    }
+
+    /// Force the muscle exerts along its path
+    pub fn force(&self, states: &[f64], length: f64, lengthening_speed: f64) -> f64 {
+        self.fiber(states, length, lengthening_speed).tendon_force
+    }
+
+    /// Time derivatives of the states under neural `excitation`
+    pub fn derivatives(
+        &self,
+        states: &[f64],
+        excitation: f64,
+        length: f64,
+        lengthening_speed: f64,
+    ) -> Vec<f64> {
+        let activation = states[0].clamp(self.min_activation, 1.0);
+        let excitation = excitation.clamp(self.min_activation, 1.0);
+        let scale = 0.5 + 1.5 * activation;
+        let time_constant = if excitation > activation {
+            self.activation_time_constant * scale
+        } else {
+            self.deactivation_time_constant / scale
+        };
+        let mut derivatives = vec![(excitation - activation) / time_constant];
+        if self.tendon == Tendon::Compliant {
+            derivatives.push(self.fiber(states, length, lengthening_speed).fiber_velocity);
+        }
+        derivatives
+    }
+
+    // the fiber keeps the height of its pennated projection, l sin(alpha)
+    fn thickness(&self) -> f64 {
+        self.optimal_fiber_length * self.pennation_angle.sin()
+    }
+
+    // pennation stays below acos(0.1) as the fiber shortens
+    fn min_fiber_length(&self) -> f64 {
+        let max_sin = (1.0 - 0.1_f64.powi(2)).sqrt();
+        (self.thickness() / max_sin).max(0.01 * self.optimal_fiber_length)
+    }
+
+    // cosine of the pennation angle and tendon length for a fiber length
+    fn pennation(&self, fiber_length: f64, length: f64) -> (f64, f64) {
+        let sin = (self.thickness() / fiber_length).min(1.0);
+        let cos = (1.0 - sin * sin).sqrt();
+        (cos, length - fiber_length * cos)
+    }
+
+    // normalized fiber velocity at which the fiber force matches `force`, all normalized by the
+    // max isometric force
+    fn equilibrium_velocity(&self, curves: &Curves, active: f64, passive: f64, force: f64) -> f64 {
+        let imbalance =
+            |v: f64| active * curves.force_velocity(v) + passive + curves.damping * v - force;
+        let mut low = -1.0;
+        let mut high = 1.0;
+        if imbalance(low) >= 0.0 {
+            // only reachable without damping, the fiber shortens as fast as it can
+            return low;
+        }
+        while imbalance(high) < 0.0 {
+            if high > 1e3 {
+                return high;
+            }
+            high *= 2.0;
+        }
+        for _ in 0..100 {
+            let middle = 0.5 * (low + high);
+            if imbalance(middle) < 0.0 {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        0.5 * (low + high)
+    }
+}
+
+/// Joint torques from muscle forces, given moment arms with one row per muscle and one column
+/// per generalized coordinate
+pub fn joint_torques(moment_arms: &Matrix, forces: &[f64]) -> Vec<f64> {
+    moment_arms.transpose_multiply_vector(forces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn soleus() -> Muscle {
+        Muscle::new(3549.0, 0.05, 0.25, 25_f64.to_radians())
+    }
+
+    #[test]
+    fn isometric_force_at_optimal_length() {
+        let muscle = soleus();
+        let length = 0.25 + 0.05 * 25_f64.to_radians().cos();
+        let force = muscle.force(&[1.0], length, 0.0);
+        assert!((force - 3549.0 * 25_f64.to_radians().cos()).abs() < 1e-9);
+
+        // shortening lowers the force, lengthening raises it
+        assert!(muscle.force(&[1.0], length, -0.1) < force);
+        assert!(muscle.force(&[1.0], length, 0.1) > force);
+
+        let moment_arms = Matrix::from_vec(1, 2, vec![0.05, -0.02]);
+        let torques = joint_torques(&moment_arms, &[force]);
+        assert!((torques[0] - 0.05 * force).abs() < 1e-12);
+        assert!((torques[1] + 0.02 * force).abs() < 1e-12);
+    }
+
+    #[test]
+    fn compliant_tendon_starts_in_equilibrium() {
+        for model in [MuscleModel::Thelen2003, MuscleModel::Damped] {
+            let muscle = soleus().model(model).tendon(Tendon::Compliant);
+            let states = muscle.initial_states(0.5, 0.31).unwrap();
+            let fiber = muscle.fiber(&states, 0.31, 0.0);
+            assert!(fiber.fiber_velocity.abs() < 1e-6);
+            let along = (fiber.active_force + fiber.passive_force) * fiber.pennation_angle.cos();
+            assert!((fiber.tendon_force - along).abs() < 1e-6);
+            assert!(fiber.tendon_length > muscle.tendon_slack_length);
+
+            let derivatives = muscle.derivatives(&states, 1.0, 0.31, 0.0);
+            assert!(derivatives[0] > 0.0);
+            assert!(derivatives[1].abs() < 1e-6);
+        }
+    }
+}
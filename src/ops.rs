@@ -16,6 +16,19 @@ impl ForceVec6 {
         Self { data }
     }
 
+    /// Wrench about the origin of a linear force acting at `point`
+    pub fn from_point_force(point: TranslationVector, force: TranslationVector) -> Self {
+        let moment = point.cross(force);
+        ForceVec6::from_array([
+            moment.data[0],
+            moment.data[1],
+            moment.data[2],
+            force.data[0],
+            force.data[1],
+            force.data[2],
+        ])
+    }
+
     pub fn rotational_force(&self) -> [f64; 3] {
         [self.data[0], self.data[1], self.data[2]]
     }
@@ -613,12 +626,29 @@ impl TransformationMatrix {
         }
     }
 
+    pub fn identity() -> Self {
+        RotationMatrix::identity().as_transform()
+    }
+
+    // the lower left block is -E rx, so rx = -E^T * lower left
     pub fn to_translation(&self) -> TranslationVector {
+        let rotation = self.to_rotation();
+        let lower_left = |i: usize, j: usize| self.data[(i + 3) * 6 + j];
+        let rx = |i: usize, j: usize| {
+            -(rotation.data[i] * lower_left(0, j)
+                + rotation.data[3 + i] * lower_left(1, j)
+                + rotation.data[6 + i] * lower_left(2, j))
+        };
         TranslationVector {
-            data: [self.data[26], self.data[30], self.data[19]],
+            data: [rx(2, 1), rx(0, 2), rx(1, 0)],
         }
     }
 
+    /// Coordinates of a point in the destination frame given its coordinates in the source frame
+    pub fn transform_point(&self, point: TranslationVector) -> TranslationVector {
+        self.to_rotation().rotate(point - self.to_translation())
+    }
+
     pub fn multiply(&self, rhs: TransformationMatrix) -> Self {
         let mut data = [0.0; 36];
         for i in 0..6 {
@@ -632,7 +662,7 @@ impl TransformationMatrix {
     }
 
     pub fn inverse_transform(&self) -> Self {
-        !self.to_rotation() + !self.to_translation()
+        !self.to_translation() + !self.to_rotation()
     }
 }
 
@@ -706,6 +736,59 @@ impl RotationMatrix {
         }
     }
 
+    pub fn identity() -> Self {
+        Self::from_array([1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0])
+    }
+
+    /// Coordinate transform into a frame rotated by `angle` about the unit `axis`, the general
+    /// form of `from_angle`
+    pub fn from_axis_angle(axis: TranslationVector, angle: f64) -> Self {
+        let [x, y, z] = axis.data;
+        let (s, c) = angle.sin_cos();
+        let t = 1.0 - c;
+        Self::from_array([
+            c + t * x * x,
+            t * x * y + s * z,
+            t * x * z - s * y,
+            t * x * y - s * z,
+            c + t * y * y,
+            t * y * z + s * x,
+            t * x * z + s * y,
+            t * y * z - s * x,
+            c + t * z * z,
+        ])
+    }
+
+    /// Coordinate transform into a frame rotated by `angles[0]` about `order[0]`, then about the
+    /// rotated `order[1]` and `order[2]` axes in turn
+    pub fn from_euler(order: [Basis; 3], angles: [f64; 3]) -> Self {
+        order
+            .iter()
+            .zip(angles)
+            .fold(Self::identity(), |total, (axis, angle)| {
+                Self::from_angle(*axis, angle) * total
+            })
+    }
+
+    /// Angles recovering this transform through `from_euler` for an order of three distinct
+    /// axes, the middle one in [-pi/2, pi/2]; at gimbal lock the last angle is set to zero
+    pub fn to_euler(&self, order: [Basis; 3]) -> [f64; 3] {
+        let [i, j, k] = order.map(|axis| axis as usize);
+        // entries of the rotation of the frame, the transpose of the coordinate transform
+        let r = |row: usize, column: usize| self.data[column * 3 + row];
+        let s = if (j + 3 - i) % 3 == 1 { 1.0 } else { -1.0 };
+        let sin_middle = (s * r(i, k)).clamp(-1.0, 1.0);
+        let middle = sin_middle.asin();
+        if sin_middle.abs() > 1.0 - 1e-12 {
+            return [(s * r(k, j)).atan2(r(j, j)), middle, 0.0];
+        }
+        [
+            (-s * r(j, k)).atan2(r(k, k)),
+            middle,
+            (-s * r(i, j)).atan2(r(i, i)),
+        ]
+    }
+
     pub fn from_x_rotation(angle: f64) -> Self {
         RotationMatrix::from_angle(Basis::X, angle)
     }
@@ -775,6 +858,44 @@ impl RotationMatrix {
         ])
     }
 
+    pub fn rotate(&self, vector: TranslationVector) -> TranslationVector {
+        TranslationVector::from_array([
+            self.data[0] * vector.data[0]
+                + self.data[1] * vector.data[1]
+                + self.data[2] * vector.data[2],
+            self.data[3] * vector.data[0]
+                + self.data[4] * vector.data[1]
+                + self.data[5] * vector.data[2],
+            self.data[6] * vector.data[0]
+                + self.data[7] * vector.data[1]
+                + self.data[8] * vector.data[2],
+        ])
+    }
+
+    /// Rotation vector (axis times angle) whose skew matrix exponentiates to this matrix
+    pub fn log(&self) -> TranslationVector {
+        let d = &self.data;
+        let cos = ((d[0] + d[4] + d[8] - 1.0) / 2.0).clamp(-1.0, 1.0);
+        let angle = cos.acos();
+        let vee = TranslationVector::from_array([d[7] - d[5], d[2] - d[6], d[3] - d[1]]);
+        if angle < 1e-8 {
+            vee * 0.5
+        } else if core::f64::consts::PI - angle < 1e-6 {
+            // near a half turn the skew part vanishes, so take the axis from the diagonal
+            let i = (0..3).max_by(|a, b| d[a * 4].total_cmp(&d[b * 4])).unwrap();
+            let mut axis = [0.0; 3];
+            axis[i] = ((d[i * 4] + 1.0) / 2.0).max(0.0).sqrt();
+            for j in (0..3).filter(|j| *j != i) {
+                axis[j] = (d[i * 3 + j] + d[j * 3 + i]) / (4.0 * axis[i]);
+            }
+            let axis = TranslationVector::from_array(axis);
+            let sign = if axis.dot(vee) < 0.0 { -1.0 } else { 1.0 };
+            axis * (sign * angle / axis.norm())
+        } else {
+            vee * (angle / (2.0 * angle.sin()))
+        }
+    }
+
     pub fn transpose(&self) -> Self {
         RotationMatrix::from_array([
             self.data[0],
@@ -804,6 +925,14 @@ impl Mul<RotationMatrix> for RotationMatrix {
     }
 }
 
+impl Mul<TranslationVector> for RotationMatrix {
+    type Output = TranslationVector;
+
+    fn mul(self, rhs: TranslationVector) -> Self::Output {
+        self.rotate(rhs)
+    }
+}
+
 impl Not for RotationMatrix {
     type Output = Self;
 
@@ -815,6 +944,7 @@ impl Not for RotationMatrix {
 impl Add<TranslationVector> for RotationMatrix {
     type Output = TransformationMatrix;
 
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn add(self, rhs: TranslationVector) -> Self::Output {
         self.as_transform() * rhs.as_transform()
     }
@@ -829,6 +959,30 @@ impl TranslationVector {
         Self { data }
     }
 
+    pub fn to_array(&self) -> [f64; 3] {
+        self.data
+    }
+
+    pub fn dot(&self, rhs: TranslationVector) -> f64 {
+        self.data[0] * rhs.data[0] + self.data[1] * rhs.data[1] + self.data[2] * rhs.data[2]
+    }
+
+    pub fn cross(&self, rhs: TranslationVector) -> Self {
+        TranslationVector::from_array([
+            self.data[1] * rhs.data[2] - self.data[2] * rhs.data[1],
+            self.data[2] * rhs.data[0] - self.data[0] * rhs.data[2],
+            self.data[0] * rhs.data[1] - self.data[1] * rhs.data[0],
+        ])
+    }
+
+    pub fn norm(&self) -> f64 {
+        self.dot(*self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Self {
+        *self * (1.0 / self.norm())
+    }
+
     pub fn as_transform(&self) -> TransformationMatrix {
         TransformationMatrix {
             data: [
@@ -930,6 +1084,7 @@ impl Not for TranslationVector {
 impl Add<RotationMatrix> for TranslationVector {
     type Output = TransformationMatrix;
 
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn add(self, rhs: RotationMatrix) -> Self::Output {
         self.as_transform() * rhs.as_transform()
     }
@@ -956,24 +1111,28 @@ impl Inertia {
         }
     }
 
+    /// Spatial momentum of a body moving with `motion`, both expressed at the body origin
     pub fn motion_multiply(
         &self,
         motion: MotionVec6,
         center_of_mass: TranslationVector,
     ) -> ForceVec6 {
+        let c = center_of_mass.data;
+        let w = motion.rotational_motion();
+        let v = motion.translational_motion();
+        // linear momentum m (v + w x c)
+        let l = [
+            self.mass * (v[0] + w[1] * c[2] - w[2] * c[1]),
+            self.mass * (v[1] + w[2] * c[0] - w[0] * c[2]),
+            self.mass * (v[2] + w[0] * c[1] - w[1] * c[0]),
+        ];
         ForceVec6::from_array([
-            self.i_xx * motion.data[0] + self.i_xy * motion.data[1] + self.i_xz * motion.data[2],
-            self.i_xy * motion.data[0] + self.i_yy * motion.data[1] + self.i_yz * motion.data[2],
-            self.i_xz * motion.data[0] + self.i_yz * motion.data[1] + self.i_zz * motion.data[2],
-            self.mass
-                * (motion.data[4] * -center_of_mass.data[2]
-                    + motion.data[5] * center_of_mass.data[1]),
-            self.mass
-                * (motion.data[3] * center_of_mass.data[2]
-                    + motion.data[5] * -center_of_mass.data[0]),
-            self.mass
-                * (motion.data[3] * -center_of_mass.data[1]
-                    + motion.data[4] * center_of_mass.data[0]),
+            self.i_xx * w[0] + self.i_xy * w[1] + self.i_xz * w[2] + c[1] * l[2] - c[2] * l[1],
+            self.i_xy * w[0] + self.i_yy * w[1] + self.i_yz * w[2] + c[2] * l[0] - c[0] * l[2],
+            self.i_xz * w[0] + self.i_yz * w[1] + self.i_zz * w[2] + c[0] * l[1] - c[1] * l[0],
+            l[0],
+            l[1],
+            l[2],
         ])
     }
 }
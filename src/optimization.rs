@@ -0,0 +1,325 @@
+use crate::linalg::Matrix;
+use crate::muscle::{Muscle, Tendon};
+
+/// Quadratic program with a diagonal Hessian, equality constraints and bounds:
+/// minimize ½ Σ h_i x_i² − g·x subject to C x = d and lower ≤ x ≤ upper
+///
+/// Solved by semismooth Newton iterations on the concave dual, where each primal variable is
+/// its unconstrained minimizer clamped to its bounds. Bounds may be infinite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundedQp {
+    /// positive diagonal of the Hessian
+    pub hessian: Vec<f64>,
+    pub linear: Vec<f64>,
+    pub constraints: Matrix,
+    pub rhs: Vec<f64>,
+    pub lower: Vec<f64>,
+    pub upper: Vec<f64>,
+}
+
+/// Muscle activations sharing joint torques at minimum Σ aᵖ, with optional residual actuators
+/// paying a quadratic cost on their normalized torque
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticOptimization {
+    /// exponent p of the activation cost, greater than one
+    pub exponent: f64,
+    /// coordinate and optimal torque of each residual actuator
+    pub residuals: Vec<(usize, f64)>,
+    pub max_iterations: usize,
+    pub tolerance: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticSolution {
+    pub activations: Vec<f64>,
+    pub forces: Vec<f64>,
+    /// torque of each residual actuator, in the order they were added
+    pub residuals: Vec<f64>,
+    pub iterations: usize,
+}
+
+impl BoundedQp {
+    /// Minimizer, or `None` when the constraints cannot be met within the bounds
+    pub fn solve(&self, max_iterations: usize, tolerance: f64) -> Option<Vec<f64>> {
+        let (m, n) = (self.constraints.rows(), self.constraints.cols());
+        let unclamped = |multipliers: &[f64]| -> Vec<f64> {
+            let pull = self.constraints.transpose_multiply_vector(multipliers);
+            (0..n)
+                .map(|i| (self.linear[i] + pull[i]) / self.hessian[i])
+                .collect()
+        };
+        let primal = |multipliers: &[f64]| -> Vec<f64> {
+            unclamped(multipliers)
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x.clamp(self.lower[i], self.upper[i]))
+                .collect()
+        };
+        let violation = |x: &[f64]| -> Vec<f64> {
+            let cx = self.constraints.multiply_vector(x);
+            self.rhs.iter().zip(cx).map(|(d, c)| d - c).collect()
+        };
+        let dual = |multipliers: &[f64]| -> f64 {
+            let x = primal(multipliers);
+            let objective: f64 = (0..n)
+                .map(|i| 0.5 * self.hessian[i] * x[i] * x[i] - self.linear[i] * x[i])
+                .sum();
+            let violation = violation(&x);
+            objective
+                + multipliers
+                    .iter()
+                    .zip(violation)
+                    .map(|(l, v)| l * v)
+                    .sum::<f64>()
+        };
+        let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let scale = 1.0 + norm(&self.rhs);
+
+        let mut multipliers = vec![0.0; m];
+        for _ in 0..max_iterations {
+            let x = primal(&multipliers);
+            let gradient = violation(&x);
+            if norm(&gradient) <= tolerance * scale {
+                return Some(x);
+            }
+            // generalized Hessian of the dual over the variables off their bounds, regularized
+            // relative to the one with every variable free
+            let free = unclamped(&multipliers);
+            let mut newton = Matrix::new(m, m);
+            let mut full = vec![0.0; m];
+            for (i, x) in free.iter().enumerate() {
+                let inside = *x > self.lower[i] && *x < self.upper[i];
+                for j in 0..m {
+                    full[j] += self.constraints[(j, i)].powi(2) / self.hessian[i];
+                    if !inside {
+                        continue;
+                    }
+                    for k in 0..m {
+                        newton[(j, k)] +=
+                            self.constraints[(j, i)] * self.constraints[(k, i)] / self.hessian[i];
+                    }
+                }
+            }
+            let largest = full.iter().fold(0.0_f64, |max, d| max.max(*d));
+            for j in 0..m {
+                newton[(j, j)] += 1e-8 * (1.0 + largest);
+            }
+            let step = newton.cholesky_solve(&gradient)?;
+
+            // backtrack until the dual rises enough, unless the rise expected from the step is
+            // already lost to rounding
+            let current = dual(&multipliers);
+            let slope: f64 = step.iter().zip(&gradient).map(|(s, g)| s * g).sum();
+            let mut length = 1.0;
+            let mut trial = multipliers.clone();
+            for _ in 0..60 {
+                trial = multipliers
+                    .iter()
+                    .zip(&step)
+                    .map(|(l, s)| l + length * s)
+                    .collect();
+                if slope <= 1e-12 * (1.0 + current.abs())
+                    || dual(&trial) >= current + 1e-4 * length * slope
+                {
+                    break;
+                }
+                length *= 0.5;
+            }
+            multipliers = trial;
+        }
+        None
+    }
+}
+
+impl StaticOptimization {
+    pub fn new() -> Self {
+        Self {
+            exponent: 2.0,
+            residuals: Vec::new(),
+            max_iterations: 50,
+            tolerance: 1e-8,
+        }
+    }
+
+    pub fn exponent(mut self, exponent: f64) -> Self {
+        self.exponent = exponent;
+        self
+    }
+
+    /// Residual actuator on `coordinate` producing `optimal_torque` at unit control
+    pub fn residual(mut self, coordinate: usize, optimal_torque: f64) -> Self {
+        self.residuals.push((coordinate, optimal_torque));
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Activations reproducing `torques` for one frame, with the musculotendon lengths and
+    /// lengthening speeds of the frame and moment arms with one row per muscle
+    ///
+    /// Fibers are taken at the lengths a rigid tendon gives them, so each muscle force is affine
+    /// in its activation. Returns `None` when the torques are out of reach.
+    pub fn solve(
+        &self,
+        muscles: &[Muscle],
+        lengths: &[f64],
+        speeds: &[f64],
+        moment_arms: &Matrix,
+        torques: &[f64],
+    ) -> Option<StaticSolution> {
+        let (count, dofs) = (muscles.len(), torques.len());
+        let p = self.exponent;
+        // force at full activation above the passive force, and the passive force
+        let (active, passive): (Vec<f64>, Vec<f64>) = muscles
+            .iter()
+            .zip(lengths.iter().zip(speeds))
+            .map(|(muscle, (length, speed))| {
+                let rigid = Muscle {
+                    tendon: Tendon::Rigid,
+                    ..*muscle
+                };
+                let fiber = rigid.fiber(&[1.0], *length, *speed);
+                let cos = fiber.pennation_angle.cos();
+                (fiber.active_force * cos, fiber.passive_force * cos)
+            })
+            .unzip();
+
+        let mut constraints = Matrix::new(dofs, count + self.residuals.len());
+        let mut rhs = torques.to_vec();
+        for i in 0..count {
+            for j in 0..dofs {
+                constraints[(j, i)] = moment_arms[(i, j)] * active[i];
+                rhs[j] -= moment_arms[(i, j)] * passive[i];
+            }
+        }
+        for (k, (coordinate, optimal_torque)) in self.residuals.iter().enumerate() {
+            constraints[(*coordinate, count + k)] = *optimal_torque;
+        }
+        let mut lower: Vec<f64> = muscles.iter().map(|muscle| muscle.min_activation).collect();
+        let mut upper = vec![1.0; count];
+        lower.resize(count + self.residuals.len(), f64::NEG_INFINITY);
+        upper.resize(count + self.residuals.len(), f64::INFINITY);
+
+        // sequential quadratic programming on the activation cost
+        let mut x: Vec<f64> = lower.iter().map(|l| l.max(0.1)).collect();
+        x[count..].fill(0.0);
+        let mut iterations = 0;
+        while iterations < self.max_iterations {
+            iterations += 1;
+            let mut hessian = vec![2.0; x.len()];
+            let mut linear = vec![0.0; x.len()];
+            for i in 0..count {
+                let a = x[i].max(0.01);
+                hessian[i] = p * (p - 1.0) * a.powf(p - 2.0);
+                linear[i] = hessian[i] * x[i] - p * x[i].powf(p - 1.0);
+            }
+            let qp = BoundedQp {
+                hessian,
+                linear,
+                constraints: constraints.clone(),
+                rhs: rhs.clone(),
+                lower: lower.clone(),
+                upper: upper.clone(),
+            };
+            let next = qp.solve(100, 1e-12)?;
+            let change = next
+                .iter()
+                .zip(&x)
+                .fold(0.0_f64, |max, (a, b)| max.max((a - b).abs()));
+            x = next;
+            if change < self.tolerance {
+                break;
+            }
+        }
+
+        Some(StaticSolution {
+            forces: (0..count).map(|i| x[i] * active[i] + passive[i]).collect(),
+            residuals: self
+                .residuals
+                .iter()
+                .enumerate()
+                .map(|(k, (_, optimal_torque))| x[count + k] * optimal_torque)
+                .collect(),
+            activations: x[..count].to_vec(),
+            iterations,
+        })
+    }
+}
+
+impl Default for StaticOptimization {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muscle::joint_torques;
+
+    // two unpennated muscles crossing one joint, held at their optimal fiber length
+    fn setup() -> ([Muscle; 2], [f64; 2], Matrix) {
+        let muscles = [
+            Muscle::new(1000.0, 0.1, 0.2, 0.0),
+            Muscle::new(600.0, 0.08, 0.25, 0.0),
+        ];
+        let lengths = [0.3, 0.33];
+        (muscles, lengths, Matrix::from_vec(2, 1, vec![0.05, 0.03]))
+    }
+
+    #[test]
+    fn shares_torque_by_strength() {
+        let (muscles, lengths, arms) = setup();
+        let torque = [30.0];
+        let solution = StaticOptimization::new()
+            .solve(&muscles, &lengths, &[0.0; 2], &arms, &torque)
+            .unwrap();
+        // with p = 2 each activation is proportional to the muscle's torque capacity
+        let capacity = [50.0, 18.0];
+        let norm = capacity[0] * capacity[0] + capacity[1] * capacity[1];
+        for (a, c) in solution.activations.iter().zip(capacity) {
+            assert!((a - c * torque[0] / norm).abs() < 1e-6);
+        }
+        let forces: Vec<f64> = muscles
+            .iter()
+            .zip(lengths.iter().zip(&solution.activations))
+            .map(|(muscle, (length, a))| muscle.force(&[*a], *length, 0.0))
+            .collect();
+        assert!((joint_torques(&arms, &forces)[0] - torque[0]).abs() < 1e-6);
+
+        // with p = 3 they go as the square root of the capacity
+        let cubic = StaticOptimization::new()
+            .exponent(3.0)
+            .solve(&muscles, &lengths, &[0.0; 2], &arms, &torque)
+            .unwrap();
+        let ratio = cubic.activations[0] / cubic.activations[1];
+        assert!((ratio - (capacity[0] / capacity[1]).sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn residuals_cover_what_muscles_cannot() {
+        let (muscles, lengths, arms) = setup();
+        let torque = [90.0];
+        let optimization = StaticOptimization::new();
+        assert!(optimization
+            .solve(&muscles, &lengths, &[0.0; 2], &arms, &torque)
+            .is_none());
+
+        let solution = optimization
+            .residual(0, 5.0)
+            .solve(&muscles, &lengths, &[0.0; 2], &arms, &torque)
+            .unwrap();
+        assert!(solution.activations.iter().all(|a| *a <= 1.0));
+        let muscle_torque = joint_torques(&arms, &solution.forces)[0];
+        assert!((muscle_torque + solution.residuals[0] - torque[0]).abs() < 1e-6);
+        assert!(solution.residuals[0] > 20.0);
+    }
+}
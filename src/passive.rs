@@ -0,0 +1,391 @@
+/// Passive torque or force acting on a single generalized coordinate
+#[derive(Debug, Clone, PartialEq)]
+pub enum PassiveElement {
+    /// -c qd
+    Damping { coefficient: f64 },
+    /// Coulomb friction smoothed as -F tanh(qd / v)
+    Friction {
+        torque: f64,
+        smoothing_velocity: f64,
+    },
+    /// -k (q - q0)
+    Spring { stiffness: f64, rest: f64 },
+    /// -Σ c_i (q - q0)^(i + 1), so the first coefficient is the linear stiffness
+    NonlinearSpring { coefficients: Vec<f64>, rest: f64 },
+    /// Ligament-like torques growing as exp(distance past a bound / decay), with the given
+    /// stiffness at the bound itself, and damping that fades in over the same distance
+    ExponentialLimit {
+        lower: f64,
+        upper: f64,
+        lower_stiffness: f64,
+        upper_stiffness: f64,
+        decay: f64,
+        damping: f64,
+    },
+    /// OpenSim's coordinate limit: zero between the bounds, then stiffness and damping that
+    /// ramp smoothly from zero to full over `transition` past a bound, after which the torque
+    /// grows linearly
+    CoordinateLimit {
+        lower: f64,
+        upper: f64,
+        lower_stiffness: f64,
+        upper_stiffness: f64,
+        transition: f64,
+        damping: f64,
+    },
+}
+
+/// Passive elements of a model, each attached to one generalized coordinate
+///
+/// Their generalized forces add to the actuator torques in forward dynamics, and come off the
+/// torques inverse dynamics asks of the actuators.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PassiveForces {
+    pub elements: Vec<(usize, PassiveElement)>,
+}
+
+impl PassiveElement {
+    pub fn torque(&self, q: f64, qd: f64) -> f64 {
+        match self {
+            Self::Damping { coefficient } => -coefficient * qd,
+            Self::Friction {
+                torque,
+                smoothing_velocity,
+            } => -torque * (qd / smoothing_velocity).tanh(),
+            Self::Spring { stiffness, rest } => -stiffness * (q - rest),
+            Self::NonlinearSpring { coefficients, rest } => {
+                let stretch = q - rest;
+                -coefficients
+                    .iter()
+                    .rev()
+                    .fold(0.0, |sum, c| (sum + c) * stretch)
+            }
+            Self::ExponentialLimit {
+                lower,
+                upper,
+                lower_stiffness,
+                upper_stiffness,
+                decay,
+                damping,
+            } => {
+                let below = ((lower - q) / decay).exp();
+                let above = ((q - upper) / decay).exp();
+                lower_stiffness * decay * below
+                    - upper_stiffness * decay * above
+                    - damping * (below + above).min(1.0) * qd
+            }
+            Self::CoordinateLimit {
+                lower,
+                upper,
+                lower_stiffness,
+                upper_stiffness,
+                transition,
+                damping,
+            } => {
+                let below = step(lower - q, *transition);
+                let above = step(q - upper, *transition);
+                lower_stiffness * below * (lower - q)
+                    - upper_stiffness * above * (q - upper)
+                    - damping * (below + above) * qd
+            }
+        }
+    }
+}
+
+// quintic step from 0 at `past` = 0 to 1 at `past` = `width`, with flat ends
+fn step(past: f64, width: f64) -> f64 {
+    if past <= 0.0 {
+        return 0.0;
+    }
+    if past >= width {
+        return 1.0;
+    }
+    let x = past / width;
+    x * x * x * (10.0 + x * (6.0 * x - 15.0))
+}
+
+impl PassiveForces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn element(mut self, coordinate: usize, element: PassiveElement) -> Self {
+        self.elements.push((coordinate, element));
+        self
+    }
+
+    /// Elements keyed by coordinate name, as the importers return them, placed on the
+    /// coordinates of `names`. Elements naming no coordinate are dropped.
+    pub fn named(mut self, names: &[&str], elements: Vec<(String, PassiveElement)>) -> Self {
+        for (name, element) in elements {
+            if let Some(coordinate) = names.iter().position(|n| *n == name) {
+                self.elements.push((coordinate, element));
+            }
+        }
+        self
+    }
+
+    pub fn generalized_forces(&self, q: &[f64], qd: &[f64]) -> Vec<f64> {
+        let mut forces = vec![0.0; q.len()];
+        for (coordinate, element) in &self.elements {
+            forces[*coordinate] += element.torque(q[*coordinate], qd[*coordinate]);
+        }
+        forces
+    }
+
+    /// Actuator torques once the passive elements have done their part of `torques`, the output
+    /// of inverse dynamics
+    pub fn actuator_torques(&self, q: &[f64], qd: &[f64], torques: &[f64]) -> Vec<f64> {
+        torques
+            .iter()
+            .zip(self.generalized_forces(q, qd))
+            .map(|(t, p)| t - p)
+            .collect()
+    }
+}
+
+/// Damping, friction and limit elements of the joints of a URDF document, keyed by joint name
+///
+/// Friction is smoothed over 0.01 rad/s. URDF gives no stiffness for its limits, so limits get
+/// `limit_stiffness` at the bounds and grow over `decay`.
+pub fn from_urdf(text: &str, limit_stiffness: f64, decay: f64) -> Vec<(String, PassiveElement)> {
+    let mut elements = Vec::new();
+    for joint in tags(text, "joint") {
+        let Some(name) = attribute(joint, "name") else {
+            continue;
+        };
+        let kind = attribute(joint, "type").unwrap_or("");
+        let number = |tag: &str, name: &str| {
+            tags(joint, tag)
+                .first()
+                .and_then(|tag| attribute(tag, name)?.parse::<f64>().ok())
+                .filter(|value| *value != 0.0)
+        };
+        if let Some(coefficient) = number("dynamics", "damping") {
+            elements.push((name.to_string(), PassiveElement::Damping { coefficient }));
+        }
+        if let Some(torque) = number("dynamics", "friction") {
+            let smoothing_velocity = 0.01;
+            elements.push((
+                name.to_string(),
+                PassiveElement::Friction {
+                    torque,
+                    smoothing_velocity,
+                },
+            ));
+        }
+        if kind == "revolute" || kind == "prismatic" {
+            let lower = number("limit", "lower").unwrap_or(0.0);
+            let upper = number("limit", "upper").unwrap_or(0.0);
+            if upper > lower {
+                elements.push((
+                    name.to_string(),
+                    PassiveElement::ExponentialLimit {
+                        lower,
+                        upper,
+                        lower_stiffness: limit_stiffness,
+                        upper_stiffness: limit_stiffness,
+                        decay,
+                        damping: 0.0,
+                    },
+                ));
+            }
+        }
+    }
+    elements
+}
+
+/// Limits from the CoordinateLimitForce components of an OpenSim model, keyed by coordinate
+/// name
+///
+/// The coordinates are taken as rotational, so limits and transitions are in degrees and
+/// stiffness and damping per degree.
+pub fn from_opensim(text: &str) -> Vec<(String, PassiveElement)> {
+    let per_degree = 180.0 / std::f64::consts::PI;
+    tags(text, "CoordinateLimitForce")
+        .into_iter()
+        .filter_map(|force| {
+            let value = |tag: &str| -> Option<f64> { content(force, tag)?.parse().ok() };
+            let coordinate = content(force, "coordinate")?;
+            Some((
+                coordinate.to_string(),
+                PassiveElement::CoordinateLimit {
+                    lower: value("lower_limit")?.to_radians(),
+                    upper: value("upper_limit")?.to_radians(),
+                    lower_stiffness: value("lower_stiffness")? * per_degree,
+                    upper_stiffness: value("upper_stiffness")? * per_degree,
+                    transition: value("transition").unwrap_or(1.0).to_radians(),
+                    damping: value("damping").unwrap_or(0.0) * per_degree,
+                },
+            ))
+        })
+        .collect()
+}
+
+// every element `<tag ...>...</tag>` or `<tag .../>` in `text`, without handling nesting of the
+// same tag
+fn tags<'a>(text: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+    let mut found = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        if !after.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            rest = after;
+            continue;
+        }
+        let Some(head) = after.find('>') else { break };
+        let end = if after[..head].ends_with('/') {
+            head + 1
+        } else {
+            after.find(&close).map_or(head + 1, |end| end + close.len())
+        };
+        found.push(&rest[start..start + open.len() + end]);
+        rest = &after[end..];
+    }
+    found
+}
+
+// value of `name="..."` in the opening tag of an element
+fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    let head = &element[..element.find('>')?];
+    let mut rest = head;
+    loop {
+        let at = rest.find(name)?;
+        let before = rest[..at].chars().last();
+        let after = rest[at + name.len()..].trim_start();
+        rest = &rest[at + name.len()..];
+        if before.is_some_and(char::is_whitespace) && after.starts_with('=') {
+            let value = after[1..].trim_start();
+            let quote = value.chars().next()?;
+            let value = &value[1..];
+            return Some(&value[..value.find(quote)?]);
+        }
+    }
+}
+
+// trimmed text inside the first `<tag>` child of an element
+fn content<'a>(element: &'a str, tag: &str) -> Option<&'a str> {
+    let child = tags(element, tag).into_iter().next()?;
+    let inner = &child[child.find('>')? + 1..];
+    Some(inner[..inner.rfind("</")?].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn element_torques_oppose_motion_and_stretch() {
+        let damping = PassiveElement::Damping { coefficient: 2.0 };
+        assert_eq!(damping.torque(0.3, 1.5), -3.0);
+        let friction = PassiveElement::Friction {
+            torque: 4.0,
+            smoothing_velocity: 0.01,
+        };
+        assert!((friction.torque(0.0, 1.0) + 4.0).abs() < 1e-9);
+        assert_eq!(friction.torque(0.0, 0.0), 0.0);
+        let spring = PassiveElement::NonlinearSpring {
+            coefficients: vec![10.0, 0.0, 100.0],
+            rest: 0.1,
+        };
+        assert!((spring.torque(0.3, 0.0) + (10.0 * 0.2 + 100.0 * 0.008)).abs() < 1e-12);
+
+        let limit = PassiveElement::ExponentialLimit {
+            lower: -0.5,
+            upper: 1.0,
+            lower_stiffness: 50.0,
+            upper_stiffness: 20.0,
+            decay: 0.05,
+            damping: 1.0,
+        };
+        // the slope at a bound is its stiffness, and the torque pushes back inside
+        let slope = (limit.torque(1.0 + 1e-6, 0.0) - limit.torque(1.0 - 1e-6, 0.0)) / 2e-6;
+        assert!((slope + 20.0).abs() < 1e-3);
+        assert!(limit.torque(-0.6, 0.0) > 0.0);
+        assert!(limit.torque(0.25, 0.0).abs() < 1e-5);
+        assert!(limit.torque(1.2, 0.0) - limit.torque(1.2, 1.0) > 0.99);
+
+        let forces = PassiveForces::new().element(1, damping).element(
+            1,
+            PassiveElement::Spring {
+                stiffness: 5.0,
+                rest: 0.0,
+            },
+        );
+        assert_eq!(
+            forces.generalized_forces(&[0.0, 0.2], &[0.0, 1.0]),
+            [0.0, -3.0]
+        );
+        assert_eq!(
+            forces.actuator_torques(&[0.0, 0.2], &[0.0, 1.0], &[1.0, 1.0]),
+            [1.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn imports_urdf_and_opensim_limits() {
+        let urdf = r#"<robot name="arm">
+  <joint name="shoulder" type="revolute">
+    <parent link="base"/><child link="upper"/>
+    <limit lower="-1.5" upper="2.0" effort="30" velocity="3"/>
+    <dynamics damping="0.7" friction="0.2"/>
+  </joint>
+  <joint name="wrist" type="continuous">
+    <dynamics damping="0.1"/>
+  </joint>
+  <transmission name="t"><joint name="shoulder"/></transmission>
+</robot>"#;
+        let elements = from_urdf(urdf, 100.0, 0.05);
+        let names: Vec<&str> = elements.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["shoulder", "shoulder", "shoulder", "wrist"]);
+        assert_eq!(elements[0].1, PassiveElement::Damping { coefficient: 0.7 });
+        assert!(matches!(
+            elements[2].1,
+            PassiveElement::ExponentialLimit {
+                lower: -1.5,
+                upper: 2.0,
+                ..
+            }
+        ));
+
+        let osim = r#"<ForceSet><objects>
+  <CoordinateLimitForce name="knee_limit">
+    <coordinate> knee_angle </coordinate>
+    <upper_stiffness>2</upper_stiffness>
+    <upper_limit>0</upper_limit>
+    <lower_stiffness>3</lower_stiffness>
+    <lower_limit>-120</lower_limit>
+    <damping>0.01</damping>
+    <transition>5</transition>
+  </CoordinateLimitForce>
+</objects></ForceSet>"#;
+        let forces = PassiveForces::new().named(&["hip", "knee_angle"], from_opensim(osim));
+        let (coordinate, limit) = &forces.elements[0];
+        assert_eq!(*coordinate, 1);
+        let PassiveElement::CoordinateLimit {
+            lower,
+            upper_stiffness,
+            transition,
+            ..
+        } = limit
+        else {
+            panic!("expected a coordinate limit");
+        };
+        assert!((lower + 120f64.to_radians()).abs() < 1e-12);
+        assert!((upper_stiffness - 2.0 * 180.0 / std::f64::consts::PI).abs() < 1e-9);
+        assert!((transition - 5f64.to_radians()).abs() < 1e-12);
+
+        // 2 N m per degree: nothing at the bound and 60 N m at 30 degrees past it, where the
+        // stiffness has long reached full
+        assert_eq!(limit.torque(0.0, 0.0), 0.0);
+        assert!((limit.torque(30f64.to_radians(), 0.0) + 60.0).abs() < 1e-9);
+        assert!(limit.torque(-60f64.to_radians(), 1.0) == 0.0);
+        let halfway = limit.torque(2.5f64.to_radians(), 0.0);
+        assert!((halfway + 0.5 * 2.0 * 2.5).abs() < 1e-9);
+        // the damping ramps in with the stiffness
+        let damped = limit.torque(30f64.to_radians(), 1.0) - limit.torque(30f64.to_radians(), 0.0);
+        assert!((damped + 0.01 * 180.0 / std::f64::consts::PI).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::body::BodyKinematics;
+use crate::wrapping::WrapSurface;
+use crate::{ForceVec6, TranslationVector};
+
+const MIN_LENGTH: f64 = 1e-12;
+
+/// Point of a muscle path fixed on a body, or on the world when `body` is `None`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathPoint {
+    pub body: Option<usize>,
+    /// in body coordinates
+    pub location: TranslationVector,
+    /// the point is only part of the path while this coordinate stays inside the range
+    pub condition: Option<(usize, f64, f64)>,
+}
+
+/// Muscle line of action through an ordered list of attachment and via points
+///
+/// Each straight segment between consecutive points wraps over the first of `surfaces` it would
+/// otherwise cut through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MusclePath {
+    pub points: Vec<PathPoint>,
+    pub surfaces: Vec<WrapSurface>,
+}
+
+// point of the path pulled along `direction` per unit tension
+struct Pull {
+    body: Option<usize>,
+    point: TranslationVector,
+    direction: TranslationVector,
+}
+
+impl PathPoint {
+    pub fn new(body: Option<usize>, location: TranslationVector) -> Self {
+        Self {
+            body,
+            location,
+            condition: None,
+        }
+    }
+
+    /// Via point used while coordinate `coordinate` of q lies in `[min, max]`
+    pub fn conditional(
+        body: Option<usize>,
+        location: TranslationVector,
+        coordinate: usize,
+        min: f64,
+        max: f64,
+    ) -> Self {
+        Self {
+            body,
+            location,
+            condition: Some((coordinate, min, max)),
+        }
+    }
+
+    pub fn is_active(&self, q: &[f64]) -> bool {
+        self.condition
+            .is_none_or(|(coordinate, min, max)| (min..=max).contains(&q[coordinate]))
+    }
+
+    pub fn position(&self, kinematics: &[BodyKinematics]) -> TranslationVector {
+        match self.body {
+            Some(body) => kinematics[body].point_position(self.location),
+            None => self.location,
+        }
+    }
+
+    pub fn velocity(&self, kinematics: &[BodyKinematics]) -> TranslationVector {
+        match self.body {
+            Some(body) => kinematics[body].point_velocity(self.location),
+            None => TranslationVector::new(),
+        }
+    }
+
+    /// World-frame linear Jacobian, empty for world points
+    pub fn jacobian(&self, kinematics: &[BodyKinematics]) -> Vec<TranslationVector> {
+        match self.body {
+            Some(body) => kinematics[body].point_jacobian(self.location),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl MusclePath {
+    pub fn new() -> Self {
+        Self {
+            points: Vec::new(),
+            surfaces: Vec::new(),
+        }
+    }
+
+    pub fn point(mut self, body: Option<usize>, location: TranslationVector) -> Self {
+        self.points.push(PathPoint::new(body, location));
+        self
+    }
+
+    pub fn conditional_point(
+        mut self,
+        body: Option<usize>,
+        location: TranslationVector,
+        coordinate: usize,
+        min: f64,
+        max: f64,
+    ) -> Self {
+        self.points
+            .push(PathPoint::conditional(body, location, coordinate, min, max));
+        self
+    }
+
+    /// Points making up the path at configuration `q`
+    pub fn active_points(&self, q: &[f64]) -> Vec<PathPoint> {
+        self.points
+            .iter()
+            .filter(|point| point.is_active(q))
+            .copied()
+            .collect()
+    }
+
+    pub fn wrap(mut self, surface: WrapSurface) -> Self {
+        self.surfaces.push(surface);
+        self
+    }
+
+    /// Musculotendon length
+    pub fn length(&self, kinematics: &[BodyKinematics], q: &[f64]) -> f64 {
+        self.pulls(kinematics, q).0
+    }
+
+    /// Rate of change of the musculotendon length, positive when lengthening
+    pub fn lengthening_speed(&self, kinematics: &[BodyKinematics], q: &[f64]) -> f64 {
+        -self
+            .pulls(kinematics, q)
+            .1
+            .iter()
+            .filter_map(|pull| {
+                let body = pull.body?;
+                let location = kinematics[body].pose.transform_point(pull.point);
+                Some(
+                    pull.direction
+                        .dot(kinematics[body].point_velocity(location)),
+                )
+            })
+            .sum::<f64>()
+    }
+
+    /// Moment arm about each generalized coordinate, -dL/dq, from the point Jacobians
+    pub fn moment_arms(&self, kinematics: &[BodyKinematics], q: &[f64]) -> Vec<f64> {
+        let dofs = kinematics.first().map_or(0, |kin| kin.jacobian.len());
+        let mut moment_arms = vec![0.0; dofs];
+        for pull in self.pulls(kinematics, q).1 {
+            let Some(body) = pull.body else { continue };
+            let location = kinematics[body].pose.transform_point(pull.point);
+            let columns = kinematics[body].point_jacobian(location);
+            for (arm, column) in moment_arms.iter_mut().zip(columns) {
+                *arm += pull.direction.dot(column);
+            }
+        }
+        moment_arms
+    }
+
+    /// World wrenches about the origin exerted by a path under `tension` on each body it crosses,
+    /// wrap surfaces included
+    pub fn wrenches(
+        &self,
+        kinematics: &[BodyKinematics],
+        q: &[f64],
+        tension: f64,
+    ) -> Vec<(usize, ForceVec6)> {
+        let mut wrenches: Vec<(usize, ForceVec6)> = Vec::new();
+        for pull in self.pulls(kinematics, q).1 {
+            let Some(body) = pull.body else { continue };
+            let wrench = ForceVec6::from_point_force(pull.point, pull.direction * tension);
+            match wrenches.iter_mut().find(|(b, _)| *b == body) {
+                Some((_, total)) => *total += wrench,
+                None => wrenches.push((body, wrench)),
+            }
+        }
+        wrenches
+    }
+
+    // length of the path and the points its tension pulls on. Every vertex of the path polyline
+    // is pulled toward its neighbours; on a wrap surface these pulls add up to the end forces of
+    // the wrapped stretch, which is what the surface's body feels
+    fn pulls(&self, kinematics: &[BodyKinematics], q: &[f64]) -> (f64, Vec<Pull>) {
+        let points = self.active_points(q);
+        let mut length = 0.0;
+        let mut pulls = Vec::new();
+        // zero-length segments, such as coincident via points, pull in no direction
+        let toward = |from: TranslationVector, to: TranslationVector| {
+            let offset = to - from;
+            if offset.norm() < MIN_LENGTH {
+                TranslationVector::new()
+            } else {
+                offset.normalize()
+            }
+        };
+        for pair in points.windows(2) {
+            let (start, end) = (pair[0].position(kinematics), pair[1].position(kinematics));
+            let wrap = self
+                .surfaces
+                .iter()
+                .find_map(|surface| Some((surface.body, surface.wrap(start, end, kinematics)?)));
+            let (surface, path, segment) = match wrap {
+                Some((body, wrap)) => (body, wrap.path, wrap.length),
+                None => (None, vec![start, end], (end - start).norm()),
+            };
+            length += segment;
+            let last = path.len() - 1;
+            pulls.push(Pull {
+                body: pair[0].body,
+                point: start,
+                direction: toward(start, path[1]),
+            });
+            for i in 1..last {
+                pulls.push(Pull {
+                    body: surface,
+                    point: path[i],
+                    direction: toward(path[i], path[i - 1]) + toward(path[i], path[i + 1]),
+                });
+            }
+            pulls.push(Pull {
+                body: pair[1].body,
+                point: end,
+                direction: toward(end, path[last - 1]),
+            });
+        }
+        (length, pulls)
+    }
+}
+
+impl Default for MusclePath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrapping::WrapShape;
+    use crate::{MotionVec6, RotationMatrix};
+
+    // one body hinged to the world about z at the origin, q = [angle]
+    fn hinge(angle: f64, rate: f64) -> Vec<BodyKinematics> {
+        let mut kin = BodyKinematics::new(RotationMatrix::from_z_rotation(angle).as_transform(), 1);
+        kin.jacobian[0] = MotionVec6::from_array([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        kin.velocity = kin.jacobian[0] * rate;
+        vec![kin]
+    }
+
+    fn path() -> MusclePath {
+        MusclePath::new()
+            .point(None, TranslationVector::from_array([0.0, 1.0, 0.0]))
+            .conditional_point(
+                Some(0),
+                TranslationVector::from_array([0.5, 0.3, 0.0]),
+                0,
+                -1.0,
+                0.0,
+            )
+            .point(Some(0), TranslationVector::from_array([1.0, 0.0, 0.0]))
+    }
+
+    #[test]
+    fn moment_arms_match_length_derivative() {
+        let path = path();
+        for angle in [-0.4, 0.4] {
+            let q = [angle];
+            let kinematics = hinge(angle, 0.7);
+            let h = 1e-6;
+            let slope = (path.length(&hinge(angle + h, 0.0), &q)
+                - path.length(&hinge(angle - h, 0.0), &q))
+                / (2.0 * h);
+            let arm = path.moment_arms(&kinematics, &q)[0];
+            assert!((arm + slope).abs() < 1e-6);
+            assert!((path.lengthening_speed(&kinematics, &q) + 0.7 * arm).abs() < 1e-12);
+
+            // the wrenches do the same work as the moment arm times the tension
+            let torque: f64 = path
+                .wrenches(&kinematics, &q, 100.0)
+                .iter()
+                .map(|(body, wrench)| kinematics[*body].world_jacobian()[0].dot(*wrench))
+                .sum();
+            assert!((torque - 100.0 * arm).abs() < 1e-9);
+        }
+        assert_eq!(path.active_points(&[0.4]).len(), 2);
+    }
+
+    #[test]
+    fn coincident_points_add_no_pull() {
+        let insertion = TranslationVector::from_array([1.0, 0.0, 0.0]);
+        let single = MusclePath::new()
+            .point(None, TranslationVector::from_array([0.0, 1.0, 0.0]))
+            .point(Some(0), insertion);
+        let doubled = single.clone().point(Some(0), insertion);
+        let kinematics = hinge(0.3, 0.7);
+
+        let arm = single.moment_arms(&kinematics, &[])[0];
+        assert!((doubled.moment_arms(&kinematics, &[])[0] - arm).abs() < 1e-12);
+        let wrench = doubled.wrenches(&kinematics, &[], 100.0)[0].1;
+        assert!(wrench.data.iter().all(|value| value.is_finite()));
+    }
+
+    #[test]
+    fn wrapped_moment_arms_match_length_derivative() {
+        let cylinder = WrapSurface::new(
+            Some(0),
+            WrapShape::Cylinder { radius: 0.08 },
+            TranslationVector::from_array([0.2, 0.0, 0.0]).as_transform(),
+        );
+        let path = MusclePath::new()
+            .point(None, TranslationVector::from_array([-0.5, 0.02, 0.0]))
+            .point(Some(0), TranslationVector::from_array([0.6, 0.0, 0.1]))
+            .wrap(cylinder);
+        for angle in [0.1, 0.2] {
+            let kinematics = hinge(angle, 1.0);
+            assert!(cylinder
+                .wrap(
+                    path.points[0].position(&kinematics),
+                    path.points[1].position(&kinematics),
+                    &kinematics,
+                )
+                .is_some());
+            let h = 1e-6;
+            let slope = (path.length(&hinge(angle + h, 0.0), &[])
+                - path.length(&hinge(angle - h, 0.0), &[]))
+                / (2.0 * h);
+            let arm = path.moment_arms(&kinematics, &[])[0];
+            assert!((arm + slope).abs() < 1e-6);
+            assert!((path.lengthening_speed(&kinematics, &[]) + arm).abs() < 1e-12);
+        }
+    }
+}
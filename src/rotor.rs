@@ -0,0 +1,201 @@
+use crate::body::BodyKinematics;
+use crate::linalg::Matrix;
+use crate::TranslationVector;
+
+/// Geared motor rotor driving one generalized coordinate
+///
+/// The rotor turns at the gear ratio times the joint speed. On its own this reflects
+/// G² I_rotor onto the diagonal of the mass matrix, the armature. With `gyroscopic` set, the
+/// rotor also spins with the body carrying its stator, which couples it to that body's
+/// coordinates and adds the gyroscopic torque of its spin momentum as the carrier turns.
+/// Only the inertia about the spin axis is modeled; the transverse inertia of the rotor
+/// belongs with the carrier's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rotor {
+    pub coordinate: usize,
+    /// about the spin axis
+    pub inertia: f64,
+    /// motor speed over joint speed
+    pub gear_ratio: f64,
+    /// body carrying the stator, `None` for the world
+    pub carrier: Option<usize>,
+    /// unit spin axis in carrier coordinates
+    pub axis: TranslationVector,
+    pub gyroscopic: bool,
+}
+
+impl Rotor {
+    /// Returns `None` unless `gear_ratio` is positive
+    pub fn new(coordinate: usize, inertia: f64, gear_ratio: f64) -> Option<Self> {
+        (gear_ratio > 0.0).then(|| Self {
+            coordinate,
+            inertia,
+            gear_ratio,
+            carrier: None,
+            axis: TranslationVector::from_array([0.0, 0.0, 1.0]),
+            gyroscopic: false,
+        })
+    }
+
+    /// Mount the stator on `carrier` with the rotor spinning about `axis`, and include the
+    /// coupling and gyroscopic terms
+    pub fn carrier(mut self, carrier: usize, axis: TranslationVector) -> Self {
+        self.carrier = Some(carrier);
+        self.axis = axis.normalize();
+        self.gyroscopic = true;
+        self
+    }
+
+    /// Armature, the rotor inertia seen at the joint
+    pub fn reflected_inertia(&self) -> f64 {
+        self.gear_ratio * self.gear_ratio * self.inertia
+    }
+
+    pub fn motor_position(&self, q: &[f64]) -> f64 {
+        self.gear_ratio * q[self.coordinate]
+    }
+
+    pub fn motor_velocity(&self, qd: &[f64]) -> f64 {
+        self.gear_ratio * qd[self.coordinate]
+    }
+
+    /// Joint torque delivered by a motor torque through the transmission
+    pub fn joint_torque(&self, motor_torque: f64) -> f64 {
+        self.gear_ratio * motor_torque
+    }
+
+    /// Motor torque needed for a joint torque
+    pub fn motor_torque(&self, joint_torque: f64) -> f64 {
+        joint_torque / self.gear_ratio
+    }
+
+    /// Spin rate of the rotor about its axis as a row over the generalized velocities
+    pub fn spin_jacobian(&self, kinematics: &[BodyKinematics], dofs: usize) -> Vec<f64> {
+        let mut row = vec![0.0; dofs];
+        if let Some(carrier) = self.carrier.filter(|_| self.gyroscopic) {
+            for (value, column) in row.iter_mut().zip(&kinematics[carrier].jacobian) {
+                *value = self.axis.dot(angular(column.rotational_motion()));
+            }
+        }
+        row[self.coordinate] += self.gear_ratio;
+        row
+    }
+}
+
+/// Contribution of the rotors to the mass matrix, to add to the one of the rigid bodies
+pub fn mass_matrix(rotors: &[Rotor], kinematics: &[BodyKinematics], dofs: usize) -> Matrix {
+    let mut matrix = Matrix::new(dofs, dofs);
+    for rotor in rotors {
+        let row = rotor.spin_jacobian(kinematics, dofs);
+        for i in 0..dofs {
+            for j in 0..dofs {
+                matrix[(i, j)] += rotor.inertia * row[i] * row[j];
+            }
+        }
+    }
+    matrix
+}
+
+/// Contribution of the rotors to the bias forces C of H qdd + C = tau: the spin acceleration
+/// the carrier's motion induces at qdd = 0, and the gyroscopic torque on the carrier
+pub fn bias_forces(rotors: &[Rotor], kinematics: &[BodyKinematics], qd: &[f64]) -> Vec<f64> {
+    let dofs = qd.len();
+    let mut forces = vec![0.0; dofs];
+    for rotor in rotors {
+        let Some(carrier) = rotor.carrier.filter(|_| rotor.gyroscopic) else {
+            continue;
+        };
+        let row = rotor.spin_jacobian(kinematics, dofs);
+        let carrier = &kinematics[carrier];
+        let spin: f64 = row.iter().zip(qd).map(|(j, v)| j * v).sum();
+        let spin_bias = rotor
+            .axis
+            .dot(angular(carrier.bias_acceleration.rotational_motion()));
+        // the spin momentum I s a turns with the carrier at w, which takes w x (I s a)
+        let w = angular(carrier.velocity.rotational_motion());
+        let gyroscopic = w.cross(rotor.axis) * (rotor.inertia * spin);
+        for (i, force) in forces.iter_mut().enumerate() {
+            let column = angular(carrier.jacobian[i].rotational_motion());
+            *force += rotor.inertia * row[i] * spin_bias + column.dot(gyroscopic);
+        }
+    }
+    forces
+}
+
+/// Generalized forces the rotors add to the inverse dynamics of the rigid bodies
+pub fn inverse_dynamics(
+    rotors: &[Rotor],
+    kinematics: &[BodyKinematics],
+    qd: &[f64],
+    qdd: &[f64],
+) -> Vec<f64> {
+    let inertial = mass_matrix(rotors, kinematics, qd.len()).multiply_vector(qdd);
+    inertial
+        .iter()
+        .zip(bias_forces(rotors, kinematics, qd))
+        .map(|(a, b)| a + b)
+        .collect()
+}
+
+fn angular(motion: [f64; 3]) -> TranslationVector {
+    TranslationVector::from_array(motion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MotionVec6, TransformationMatrix};
+
+    #[test]
+    fn armature_adds_reflected_inertia() {
+        let rotors = [Rotor::new(1, 2e-5, 100.0).unwrap()];
+        let kinematics = [BodyKinematics::new(TransformationMatrix::identity(), 2)];
+        let matrix = mass_matrix(&rotors, &kinematics, 2);
+        assert!((matrix[(1, 1)] - 0.2).abs() < 1e-12);
+        assert_eq!(matrix[(0, 0)], 0.0);
+        assert_eq!(matrix[(0, 1)], 0.0);
+
+        let tau = inverse_dynamics(&rotors, &kinematics, &[1.0, 3.0], &[0.0, 5.0]);
+        assert!((tau[1] - 1.0).abs() < 1e-12);
+        assert!((rotors[0].motor_torque(tau[1]) - 0.01).abs() < 1e-15);
+        assert!((rotors[0].joint_torque(0.01) - tau[1]).abs() < 1e-15);
+        assert_eq!(rotors[0].motor_velocity(&[1.0, 3.0]), 300.0);
+
+        assert!(Rotor::new(1, 2e-5, 0.0).is_none());
+        assert!(Rotor::new(1, 2e-5, -100.0).is_none());
+    }
+
+    #[test]
+    fn carrier_motion_couples_and_precesses() {
+        // the carrier turns about z with coordinate 0 and about y with coordinate 2; the rotor
+        // drives coordinate 1 and spins about the carrier x axis
+        let (inertia, ratio) = (1e-4, 50.0);
+        let qd = [0.4, 2.0, -0.3];
+        let mut carrier = BodyKinematics::new(TransformationMatrix::identity(), 3);
+        carrier.jacobian[0] = MotionVec6::from_array([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        carrier.jacobian[2] = MotionVec6::from_array([0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        carrier.velocity = MotionVec6::from_array([0.0, qd[2], qd[0], 0.0, 0.0, 0.0]);
+        let rotors = [Rotor::new(1, inertia, ratio)
+            .unwrap()
+            .carrier(0, TranslationVector::from_array([1.0, 0.0, 0.0]))];
+
+        let forces = bias_forces(&rotors, &[carrier.clone()], &qd);
+        // spin momentum I G qd1 along x, turned by w = (0, qd2, qd0), needs a torque
+        // w x h = (0, I G qd1 qd0, -I G qd1 qd2)
+        let h = inertia * ratio * qd[1];
+        assert!((forces[0] + h * qd[2]).abs() < 1e-12);
+        assert!((forces[2] - h * qd[0]).abs() < 1e-12);
+        // gyroscopic forces do no work
+        let power: f64 = forces.iter().zip(qd).map(|(f, v)| f * v).sum();
+        assert!(power.abs() < 1e-12);
+
+        // tilting the axis toward z couples the rotor to the carrier's turning about z
+        let tilted = Rotor::new(1, inertia, ratio)
+            .unwrap()
+            .carrier(0, TranslationVector::from_array([1.0, 0.0, 1.0]));
+        let matrix = mass_matrix(&[tilted], &[carrier], 3);
+        let z = 0.5_f64.sqrt();
+        assert!((matrix[(0, 1)] - inertia * z * ratio).abs() < 1e-12);
+        assert!((matrix[(0, 0)] - inertia * z * z).abs() < 1e-12);
+    }
+}
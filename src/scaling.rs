@@ -0,0 +1,273 @@
+use crate::body::{Body, BodyKinematics};
+use crate::ik::Marker;
+use crate::path::MusclePath;
+use crate::wrapping::WrapShape;
+use crate::{Inertia, TransformationMatrix, TranslationVector};
+
+/// Per-body scale factors applied to the geometry and mass properties of a model
+///
+/// Points fixed on a body scale component by component along the body axes: joint placements
+/// in the parent, centers of mass, markers, muscle attachments and wrap surface origins. Masses
+/// scale with the volume and inertias with the size squared times the mass ratio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scaling {
+    /// factors along the body x, y and z axes, one per body
+    pub factors: Vec<TranslationVector>,
+    /// rescale all masses so the model keeps its total mass
+    pub preserve_mass: bool,
+}
+
+impl Scaling {
+    pub fn new(bodies: usize) -> Self {
+        Self {
+            factors: vec![TranslationVector::from_array([1.0, 1.0, 1.0]); bodies],
+            preserve_mass: false,
+        }
+    }
+
+    pub fn uniform(mut self, body: usize, factor: f64) -> Self {
+        self.factors[body] = TranslationVector::from_array([factor; 3]);
+        self
+    }
+
+    pub fn factors(mut self, body: usize, factors: TranslationVector) -> Self {
+        self.factors[body] = factors;
+        self
+    }
+
+    pub fn preserve_mass(mut self, preserve_mass: bool) -> Self {
+        self.preserve_mass = preserve_mass;
+        self
+    }
+
+    /// Point fixed on `body`, or on the world when `None`, moved with the scaled body
+    pub fn point(&self, body: Option<usize>, point: TranslationVector) -> TranslationVector {
+        match body {
+            Some(body) => {
+                let (p, s) = (point.to_array(), self.factors[body].to_array());
+                TranslationVector::from_array([p[0] * s[0], p[1] * s[1], p[2] * s[2]])
+            }
+            None => point,
+        }
+    }
+
+    /// Body to frame transform with the frame origin scaled, such as the placement of a child
+    /// joint in its parent or a shape offset
+    pub fn transform(
+        &self,
+        body: Option<usize>,
+        transform: TransformationMatrix,
+    ) -> TransformationMatrix {
+        transform.to_rotation() + self.point(body, transform.to_translation())
+    }
+
+    pub fn bodies(&self, bodies: &[Body]) -> Vec<Body> {
+        let mut scaled: Vec<Body> = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let [sx, sy, sz] = self.factors[i].to_array();
+                let mut body = body.clone();
+                body.center_of_mass = self.point(Some(i), body.center_of_mass);
+                body.inertia = scale_inertia(body.inertia, [sx, sy, sz], sx * sy * sz);
+                body
+            })
+            .collect();
+        if self.preserve_mass {
+            let before: f64 = bodies.iter().map(|body| body.inertia.mass).sum();
+            let after: f64 = scaled.iter().map(|body| body.inertia.mass).sum();
+            if after > 0.0 {
+                for body in &mut scaled {
+                    body.inertia = scale_inertia(body.inertia, [1.0; 3], before / after);
+                }
+            }
+        }
+        scaled
+    }
+
+    pub fn markers(&self, markers: &[Marker]) -> Vec<Marker> {
+        markers
+            .iter()
+            .map(|marker| Marker {
+                offset: self.point(Some(marker.body), marker.offset),
+                ..marker.clone()
+            })
+            .collect()
+    }
+
+    /// Path with its points and wrap surface origins moved, and the surface dimensions scaled
+    /// by the geometric mean of their body's factors
+    pub fn path(&self, path: &MusclePath) -> MusclePath {
+        let mut scaled = path.clone();
+        for point in &mut scaled.points {
+            point.location = self.point(point.body, point.location);
+        }
+        for surface in &mut scaled.surfaces {
+            let Some(body) = surface.body else { continue };
+            let [sx, sy, sz] = self.factors[body].to_array();
+            let mean = (sx * sy * sz).cbrt();
+            surface.offset = self.transform(Some(body), surface.offset);
+            surface.shape = match surface.shape {
+                WrapShape::Cylinder { radius } => WrapShape::Cylinder {
+                    radius: radius * mean,
+                },
+                WrapShape::Sphere { radius } => WrapShape::Sphere {
+                    radius: radius * mean,
+                },
+                WrapShape::Ellipsoid { radii } => WrapShape::Ellipsoid {
+                    radii: radii * mean,
+                },
+                WrapShape::Torus {
+                    major_radius,
+                    minor_radius,
+                } => WrapShape::Torus {
+                    major_radius: major_radius * mean,
+                    minor_radius: minor_radius * mean,
+                },
+            };
+        }
+        scaled
+    }
+}
+
+/// Ratio of measured to model distances between marker pairs, averaged over the pairs seen in
+/// a static trial, with the model posed by `kinematics`
+pub fn marker_pair_factor(
+    markers: &[Marker],
+    pairs: &[(usize, usize)],
+    measured: &[Option<TranslationVector>],
+    kinematics: &[BodyKinematics],
+) -> Option<f64> {
+    let ratios: Vec<f64> = pairs
+        .iter()
+        .filter_map(|(a, b)| {
+            let distance = (measured[*a]? - measured[*b]?).norm();
+            let model =
+                (markers[*a].position(kinematics) - markers[*b].position(kinematics)).norm();
+            (model > 0.0).then(|| distance / model)
+        })
+        .collect();
+    (!ratios.is_empty()).then(|| ratios.iter().sum::<f64>() / ratios.len() as f64)
+}
+
+// inertia about the center of mass after stretching the body by `factors` along its axes and
+// multiplying its mass by `mass_ratio`, through the second moment J = tr(I)/2 - I
+fn scale_inertia(inertia: Inertia, factors: [f64; 3], mass_ratio: f64) -> Inertia {
+    let i = [
+        [inertia.i_xx, inertia.i_xy, inertia.i_xz],
+        [inertia.i_xy, inertia.i_yy, inertia.i_yz],
+        [inertia.i_xz, inertia.i_yz, inertia.i_zz],
+    ];
+    let half_trace = 0.5 * (i[0][0] + i[1][1] + i[2][2]);
+    let j = |r: usize, c: usize| {
+        let second_moment = if r == c {
+            half_trace - i[r][c]
+        } else {
+            -i[r][c]
+        };
+        mass_ratio * factors[r] * factors[c] * second_moment
+    };
+    let trace = j(0, 0) + j(1, 1) + j(2, 2);
+    Inertia::new(
+        inertia.mass * mass_ratio,
+        trace - j(0, 0),
+        trace - j(1, 1),
+        trace - j(2, 2),
+        -j(0, 1),
+        -j(0, 2),
+        -j(1, 2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(x: f64, y: f64, z: f64) -> TranslationVector {
+        TranslationVector::from_array([x, y, z])
+    }
+
+    // solid box of half extents a, b, c about its center
+    fn cuboid(mass: f64, [a, b, c]: [f64; 3]) -> Inertia {
+        let k = mass / 3.0;
+        Inertia::new(
+            mass,
+            k * (b * b + c * c),
+            k * (a * a + c * c),
+            k * (a * a + b * b),
+            0.0,
+            0.0,
+            0.0,
+        )
+    }
+
+    #[test]
+    fn scales_mass_properties_and_points() {
+        let bodies = [
+            Body::new(
+                "thigh",
+                None,
+                vector(0.0, 0.0, -0.2),
+                cuboid(8.0, [0.05, 0.06, 0.2]),
+            ),
+            Body::new(
+                "shank",
+                Some(0),
+                vector(0.0, 0.0, -0.18),
+                cuboid(3.0, [0.04, 0.04, 0.18]),
+            ),
+        ];
+        let factors = [1.1, 0.9, 1.2];
+        let scaling = Scaling::new(2)
+            .factors(0, vector(1.1, 0.9, 1.2))
+            .uniform(1, 1.05);
+        let scaled = scaling.bodies(&bodies);
+
+        let ratio = 1.1 * 0.9 * 1.2;
+        let expected = cuboid(8.0 * ratio, [0.055, 0.054, 0.24]);
+        let thigh = scaled[0].inertia;
+        for (value, expected) in [
+            (thigh.mass, expected.mass),
+            (thigh.i_xx, expected.i_xx),
+            (thigh.i_yy, expected.i_yy),
+            (thigh.i_zz, expected.i_zz),
+        ] {
+            assert!((value - expected).abs() < 1e-12);
+        }
+        assert!((scaled[0].center_of_mass - vector(0.0, 0.0, -0.2 * factors[2])).norm() < 1e-12);
+
+        // the same shape at the original total mass
+        let preserved = scaling.clone().preserve_mass(true).bodies(&bodies);
+        let total: f64 = preserved.iter().map(|body| body.inertia.mass).sum();
+        assert!((total - 11.0).abs() < 1e-12);
+        let shrink = preserved[1].inertia.mass / scaled[1].inertia.mass;
+        assert!((preserved[1].inertia.i_xx - shrink * scaled[1].inertia.i_xx).abs() < 1e-15);
+
+        let marker = Marker::new("knee", 0, vector(0.05, 0.0, -0.4));
+        let offset = scaling.markers(&[marker])[0].offset;
+        assert!((offset - vector(0.055, 0.0, -0.48)).norm() < 1e-12);
+        let placement = scaling.transform(Some(0), vector(0.0, 0.0, -0.4).as_transform());
+        assert!((placement.to_translation() - vector(0.0, 0.0, -0.48)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn marker_pairs_give_the_size_ratio() {
+        let kinematics = [BodyKinematics::new(TransformationMatrix::identity(), 0)];
+        let markers = [
+            Marker::new("hip", 0, vector(0.0, 0.0, 0.0)),
+            Marker::new("knee", 0, vector(0.0, 0.0, -0.4)),
+            Marker::new("ankle", 0, vector(0.0, 0.0, -0.8)),
+        ];
+        let measured = [
+            Some(vector(1.0, 0.0, 0.9)),
+            Some(vector(1.0, 0.0, 0.46)),
+            None,
+        ];
+        let factor = marker_pair_factor(&markers, &[(0, 1), (1, 2)], &measured, &kinematics);
+        assert!((factor.unwrap() - 1.1).abs() < 1e-12);
+        assert_eq!(
+            marker_pair_factor(&markers, &[(1, 2)], &measured, &kinematics),
+            None
+        );
+    }
+}
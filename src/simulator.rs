@@ -0,0 +1,417 @@
+/// Generalized positions and velocities of a system at time `t`
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub t: f64,
+    pub q: Vec<f64>,
+    pub qd: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    SemiImplicitEuler,
+    RungeKutta4,
+}
+
+/// Method used to locate the zero crossing of an event function inside a step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RootFinder {
+    Bisection,
+    Illinois,
+}
+
+/// Sign change of an event function that triggers the event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Rising,
+    Falling,
+    Either,
+}
+
+/// What the simulator does once an event has been located and its callback has run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventAction {
+    Stop,
+    Continue,
+}
+
+type EventFunction = Box<dyn Fn(f64, &[f64], &[f64]) -> f64>;
+type EventCallback = Box<dyn FnMut(&mut State)>;
+
+/// Zero crossing of a user-defined function g(t, q, qd)
+pub struct Event {
+    function: EventFunction,
+    callback: Option<EventCallback>,
+    direction: Direction,
+    action: EventAction,
+    // |g| the function must exceed before the event can fire again, set once it has fired
+    rearm: Option<f64>,
+}
+
+/// Event that was located during a step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventRecord {
+    pub event: usize,
+    pub t: f64,
+}
+
+/// Fixed-step integrator of qdd = f(t, q, qd) with event location
+pub struct Simulator<F>
+where
+    F: FnMut(f64, &[f64], &[f64]) -> Vec<f64>,
+{
+    dynamics: F,
+    step_size: f64,
+    integrator: Integrator,
+    root_finder: RootFinder,
+    tolerance: f64,
+    max_iterations: usize,
+    events: Vec<Event>,
+}
+
+impl State {
+    pub fn new(q: Vec<f64>, qd: Vec<f64>) -> Self {
+        Self { t: 0.0, q, qd }
+    }
+}
+
+impl Direction {
+    fn crosses(&self, before: f64, after: f64) -> bool {
+        let rising = (before < 0.0 && after >= 0.0) || (before == 0.0 && after > 0.0);
+        let falling = (before > 0.0 && after <= 0.0) || (before == 0.0 && after < 0.0);
+        match self {
+            Direction::Rising => rising,
+            Direction::Falling => falling,
+            Direction::Either => rising || falling,
+        }
+    }
+}
+
+impl Event {
+    pub fn new(function: impl Fn(f64, &[f64], &[f64]) -> f64 + 'static) -> Self {
+        Self {
+            function: Box::new(function),
+            callback: None,
+            direction: Direction::Either,
+            action: EventAction::Stop,
+            rearm: None,
+        }
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn action(mut self, action: EventAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Called with the state at the event time, e.g. to apply an impact reset
+    pub fn callback(mut self, callback: impl FnMut(&mut State) + 'static) -> Self {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    pub fn evaluate(&self, state: &State) -> f64 {
+        (self.function)(state.t, &state.q, &state.qd)
+    }
+}
+
+impl<F> Simulator<F>
+where
+    F: FnMut(f64, &[f64], &[f64]) -> Vec<f64>,
+{
+    pub fn new(dynamics: F, step_size: f64) -> Self {
+        Self {
+            dynamics,
+            step_size,
+            integrator: Integrator::RungeKutta4,
+            root_finder: RootFinder::Illinois,
+            tolerance: 1e-12,
+            max_iterations: 100,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    pub fn root_finder(mut self, root_finder: RootFinder) -> Self {
+        self.root_finder = root_finder;
+        self
+    }
+
+    /// Width of the time bracket at which event location stops
+    pub fn tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn add_event(&mut self, event: Event) -> usize {
+        self.events.push(event);
+        self.events.len() - 1
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn dynamics_mut(&mut self) -> &mut F {
+        &mut self.dynamics
+    }
+
+    /// Integrate `state` from `start` over `h` without looking at events
+    pub fn advance(&mut self, start: &State, h: f64) -> State {
+        match self.integrator {
+            Integrator::SemiImplicitEuler => {
+                let qdd = (self.dynamics)(start.t, &start.q, &start.qd);
+                let qd: Vec<f64> = start.qd.iter().zip(&qdd).map(|(v, a)| v + h * a).collect();
+                let q = start.q.iter().zip(&qd).map(|(p, v)| p + h * v).collect();
+                State {
+                    t: start.t + h,
+                    q,
+                    qd,
+                }
+            }
+            Integrator::RungeKutta4 => {
+                let offset = |x: &[f64], dx: &[f64], s: f64| -> Vec<f64> {
+                    x.iter().zip(dx).map(|(a, b)| a + s * b).collect()
+                };
+                let k1_q = start.qd.clone();
+                let k1_qd = (self.dynamics)(start.t, &start.q, &start.qd);
+                let q2 = offset(&start.q, &k1_q, 0.5 * h);
+                let k2_q = offset(&start.qd, &k1_qd, 0.5 * h);
+                let k2_qd = (self.dynamics)(start.t + 0.5 * h, &q2, &k2_q);
+                let q3 = offset(&start.q, &k2_q, 0.5 * h);
+                let k3_q = offset(&start.qd, &k2_qd, 0.5 * h);
+                let k3_qd = (self.dynamics)(start.t + 0.5 * h, &q3, &k3_q);
+                let q4 = offset(&start.q, &k3_q, h);
+                let k4_q = offset(&start.qd, &k3_qd, h);
+                let k4_qd = (self.dynamics)(start.t + h, &q4, &k4_q);
+
+                let combine = |x: &[f64], k1: &[f64], k2: &[f64], k3: &[f64], k4: &[f64]| {
+                    (0..x.len())
+                        .map(|i| x[i] + h / 6.0 * (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]))
+                        .collect::<Vec<f64>>()
+                };
+                State {
+                    t: start.t + h,
+                    q: combine(&start.q, &k1_q, &k2_q, &k3_q, &k4_q),
+                    qd: combine(&start.qd, &k1_qd, &k2_qd, &k3_qd, &k4_qd),
+                }
+            }
+        }
+    }
+
+    /// Take one step of at most `h`, stopping at the earliest event in the step if there is one
+    ///
+    /// An event that fired is not located again until its function has moved further from zero
+    /// than at the located root, so a callback leaving the state just past the root does not
+    /// retrigger it. A step starting exactly on a root fires at its start.
+    pub fn step(&mut self, state: &mut State, h: f64) -> Option<EventRecord> {
+        let start = state.clone();
+        let end = self.advance(&start, h);
+
+        let mut earliest: Option<(usize, State)> = None;
+        for i in 0..self.events.len() {
+            if self.events[i].rearm.is_some() {
+                continue;
+            }
+            let before = self.events[i].evaluate(&start);
+            let after = self.events[i].evaluate(&end);
+            if !self.events[i].direction.crosses(before, after) {
+                continue;
+            }
+            let located = if before == 0.0 {
+                start.clone()
+            } else {
+                self.locate(i, &start, h, before, after)
+            };
+            if earliest.as_ref().is_none_or(|(_, s)| located.t < s.t) {
+                earliest = Some((i, located));
+            }
+        }
+
+        let record = match earliest {
+            None => {
+                *state = end;
+                None
+            }
+            Some((i, located)) => {
+                *state = located;
+                let record = EventRecord {
+                    event: i,
+                    t: state.t,
+                };
+                let band = self.events[i].evaluate(state).abs();
+                self.events[i].rearm = Some(band);
+                if let Some(callback) = self.events[i].callback.as_mut() {
+                    callback(state);
+                }
+                Some(record)
+            }
+        };
+        for event in &mut self.events {
+            if let Some(band) = event.rearm {
+                if event.evaluate(state).abs() > band {
+                    event.rearm = None;
+                }
+            }
+        }
+        record
+    }
+
+    /// Integrate until `t_end` or until an event with `EventAction::Stop` occurs
+    pub fn simulate(&mut self, state: &mut State, t_end: f64) -> Vec<EventRecord> {
+        let mut records = Vec::new();
+        while t_end - state.t > self.tolerance {
+            let h = self.step_size.min(t_end - state.t);
+            if let Some(record) = self.step(state, h) {
+                records.push(record);
+                if self.events[record.event].action == EventAction::Stop {
+                    break;
+                }
+            }
+        }
+        records
+    }
+
+    // bracket the crossing in [0, h] and return the state just past it
+    fn locate(&mut self, event: usize, start: &State, h: f64, before: f64, after: f64) -> State {
+        let (mut a, mut b) = (0.0, h);
+        let (mut g_a, mut g_b) = (before, after);
+        let mut crossing = self.advance(start, h);
+        let mut retained = 0;
+
+        for _ in 0..self.max_iterations {
+            if b - a <= self.tolerance || g_b == 0.0 {
+                break;
+            }
+            let mut m = match self.root_finder {
+                RootFinder::Bisection => 0.5 * (a + b),
+                RootFinder::Illinois => (a * g_b - b * g_a) / (g_b - g_a),
+            };
+            if !(m > a && m < b) {
+                m = 0.5 * (a + b);
+            }
+            let trial = self.advance(start, m);
+            let g_m = self.events[event].evaluate(&trial);
+
+            if g_m == 0.0 || g_m.signum() != g_a.signum() {
+                b = m;
+                g_b = g_m;
+                crossing = trial;
+                if retained == -1 {
+                    g_a *= 0.5;
+                }
+                retained = -1;
+            } else {
+                a = m;
+                g_a = g_m;
+                if retained == 1 {
+                    g_b *= 0.5;
+                }
+                retained = 1;
+            }
+        }
+        crossing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falling_ball_stops_at_impact() {
+        let mut simulator = Simulator::new(|_, _, _| vec![-9.81], 0.01);
+        simulator.add_event(
+            Event::new(|_, q, _| q[0])
+                .direction(Direction::Falling)
+                .action(EventAction::Stop),
+        );
+        let mut state = State::new(vec![1.0], vec![0.0]);
+        let records = simulator.simulate(&mut state, 2.0);
+
+        let expected = (2.0 / 9.81_f64).sqrt();
+        assert_eq!(records.len(), 1);
+        assert!((records[0].t - expected).abs() < 1e-9);
+        assert!(state.q[0].abs() < 1e-9);
+    }
+
+    #[test]
+    fn bouncing_ball_resets_velocity() {
+        for root_finder in [RootFinder::Bisection, RootFinder::Illinois] {
+            let mut simulator =
+                Simulator::new(|_, _, _| vec![-9.81], 0.01).root_finder(root_finder);
+            simulator.add_event(
+                Event::new(|_, q, _| q[0])
+                    .direction(Direction::Falling)
+                    .action(EventAction::Continue)
+                    .callback(|state| state.qd[0] *= -0.5),
+            );
+            let mut state = State::new(vec![1.0], vec![0.0]);
+            let records = simulator.simulate(&mut state, 1.0);
+
+            let first = (2.0 / 9.81_f64).sqrt();
+            let second = first + 2.0 * 0.5 * 9.81 * first / 9.81;
+            assert_eq!(records.len(), 2);
+            assert!((records[0].t - first).abs() < 1e-9);
+            assert!((records[1].t - second).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn bounce_does_not_retrigger_either_direction() {
+        let mut simulator = Simulator::new(|_, _, _| vec![-9.81], 0.01);
+        simulator.add_event(
+            Event::new(|_, q, _| q[0])
+                .action(EventAction::Continue)
+                .callback(|state| state.qd[0] *= -0.5),
+        );
+        let mut state = State::new(vec![1.0], vec![0.0]);
+        let records = simulator.simulate(&mut state, 1.0);
+
+        // the ball leaves the ground without firing again, and lands once more
+        let first = (2.0 / 9.81_f64).sqrt();
+        let second = first + 2.0 * 0.5 * 9.81 * first / 9.81;
+        assert_eq!(records.len(), 2);
+        assert!((records[0].t - first).abs() < 1e-9);
+        assert!((records[1].t - second).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reset_away_from_root_rearms_event() {
+        // a conveyor that puts the falling point back at 1 each time it reaches 0
+        let mut simulator = Simulator::new(|_, _, _| vec![0.0], 0.01);
+        simulator.add_event(
+            Event::new(|_, q, _| q[0])
+                .direction(Direction::Falling)
+                .action(EventAction::Continue)
+                .callback(|state| state.q[0] = 1.0),
+        );
+        let mut state = State::new(vec![0.5], vec![-1.0]);
+        let records = simulator.simulate(&mut state, 2.6);
+        let times: Vec<f64> = records.iter().map(|r| r.t).collect();
+        assert_eq!(times.len(), 3);
+        for (t, expected) in times.iter().zip([0.5, 1.5, 2.5]) {
+            assert!((t - expected).abs() < 1e-9);
+        }
+        assert!((state.q[0] - 0.9).abs() < 1e-9);
+
+        // a step starting on the root still sees the crossing
+        let mut simulator = Simulator::new(|_, _, _| vec![0.0], 0.01);
+        simulator.add_event(Event::new(|_, q, _| q[0]).direction(Direction::Rising));
+        let mut state = State::new(vec![0.0], vec![1.0]);
+        let records = simulator.simulate(&mut state, 1.0);
+        assert_eq!(records, [EventRecord { event: 0, t: 0.0 }]);
+    }
+}
@@ -0,0 +1,433 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::c3d::unit_scale;
+use crate::ik::Marker;
+use crate::TranslationVector;
+
+// header keys written from the table itself rather than copied from the metadata
+const TABLE_KEYS: [&str; 6] = [
+    "version",
+    "nRows",
+    "nColumns",
+    "inDegrees",
+    "datacolumns",
+    "datarows",
+];
+
+/// Time series table in the OpenSim .sto and .mot formats
+#[derive(Debug, Clone, PartialEq)]
+pub struct Storage {
+    pub name: String,
+    /// other header entries, in file order
+    pub metadata: Vec<(String, String)>,
+    /// whether rotational coordinates are stored in degrees
+    pub in_degrees: bool,
+    /// column labels after the time column
+    pub labels: Vec<String>,
+    pub time: Vec<f64>,
+    pub rows: Vec<Vec<f64>>,
+}
+
+/// Marker trajectories in the OpenSim .trc format
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trc {
+    pub name: String,
+    pub data_rate: f64,
+    pub camera_rate: f64,
+    /// length unit used when writing, positions are held in meters
+    pub units: String,
+    pub labels: Vec<String>,
+    pub time: Vec<f64>,
+    /// marker positions in meters, one row per frame, `None` where the marker is missing
+    pub frames: Vec<Vec<Option<TranslationVector>>>,
+}
+
+impl Storage {
+    pub fn new(name: &str, labels: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            metadata: Vec::new(),
+            in_degrees: false,
+            labels,
+            time: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn in_degrees(mut self, in_degrees: bool) -> Self {
+        self.in_degrees = in_degrees;
+        self
+    }
+
+    pub fn push(&mut self, time: f64, row: Vec<f64>) {
+        self.time.push(time);
+        self.rows.push(row);
+    }
+
+    /// Table of coordinate trajectories given in radians, converted to degrees for the
+    /// rotational coordinates when `in_degrees` is set
+    pub fn from_coordinates(
+        name: &str,
+        names: &[&str],
+        rotational: &[bool],
+        time: &[f64],
+        q: &[Vec<f64>],
+        in_degrees: bool,
+    ) -> Self {
+        let mut storage =
+            Self::new(name, names.iter().map(|n| n.to_string()).collect()).in_degrees(in_degrees);
+        for (t, q) in time.iter().zip(q) {
+            let row = q
+                .iter()
+                .zip(rotational)
+                .map(|(value, rotational)| {
+                    if in_degrees && *rotational {
+                        value.to_degrees()
+                    } else {
+                        *value
+                    }
+                })
+                .collect();
+            storage.push(*t, row);
+        }
+        storage
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(path).ok()?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Reads both the current `key=value` header and the older `datacolumns`/`datarows` one
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        let mut storage = Self::new("", Vec::new());
+        for (i, line) in lines.by_ref().enumerate() {
+            let line = line.trim();
+            if line.eq_ignore_ascii_case("endheader") {
+                break;
+            }
+            let entry = line.split_once('=').or_else(|| {
+                (i > 0)
+                    .then(|| line.split_once(char::is_whitespace))
+                    .flatten()
+            });
+            match entry {
+                Some((key, value)) => {
+                    let (key, value) = (key.trim(), value.trim());
+                    if key == "inDegrees" {
+                        storage.in_degrees = value.eq_ignore_ascii_case("yes");
+                    } else if !TABLE_KEYS.contains(&key) {
+                        storage.metadata.push((key.to_string(), value.to_string()));
+                    }
+                }
+                None if i == 0 => storage.name = line.to_string(),
+                None => {}
+            }
+        }
+
+        let mut labels = lines
+            .by_ref()
+            .find(|line| !line.trim().is_empty())?
+            .split_whitespace();
+        if !labels.next()?.eq_ignore_ascii_case("time") {
+            return None;
+        }
+        storage.labels = labels.map(|label| label.to_string()).collect();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(|value| value.parse().ok())
+                .collect::<Option<_>>()?;
+            let (time, row) = values.split_first()?;
+            if row.len() != storage.labels.len() {
+                return None;
+            }
+            storage.push(*time, row.to_vec());
+        }
+        Some(storage)
+    }
+
+    /// Values of the column labeled `label`, or of the OpenSim 4 coordinate value path ending in
+    /// `label/value`
+    pub fn column(&self, label: &str) -> Option<Vec<f64>> {
+        let column = self.position(label)?;
+        Some(self.rows.iter().map(|row| row[column]).collect())
+    }
+
+    /// Coordinate values per row in the order of `names`, matched by column label as in
+    /// `column` and in radians for the rotational ones; coordinates without a column stay at
+    /// zero
+    pub fn coordinates(&self, names: &[&str], rotational: &[bool]) -> Vec<Vec<f64>> {
+        let columns: Vec<Option<usize>> = names.iter().map(|name| self.position(name)).collect();
+        self.rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .zip(rotational)
+                    .map(|(column, rotational)| {
+                        let value = column.map_or(0.0, |c| row[c]);
+                        if self.in_degrees && *rotational {
+                            value.to_radians()
+                        } else {
+                            value
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // column of a plain label, or of a path such as /jointset/knee_r/knee_angle_r/value
+    fn position(&self, name: &str) -> Option<usize> {
+        self.labels.iter().position(|l| l == name).or_else(|| {
+            self.labels.iter().position(|l| {
+                l.strip_suffix("/value")
+                    .and_then(|path| path.rsplit('/').next())
+                    .is_some_and(|coordinate| coordinate == name)
+            })
+        })
+    }
+}
+
+impl fmt::Display for Storage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        writeln!(f, "version=1")?;
+        writeln!(f, "nRows={}", self.rows.len())?;
+        writeln!(f, "nColumns={}", self.labels.len() + 1)?;
+        writeln!(
+            f,
+            "inDegrees={}",
+            if self.in_degrees { "yes" } else { "no" }
+        )?;
+        for (key, value) in &self.metadata {
+            writeln!(f, "{key}={value}")?;
+        }
+        writeln!(f, "endheader")?;
+        writeln!(f, "time\t{}", self.labels.join("\t"))?;
+        for (time, row) in self.time.iter().zip(&self.rows) {
+            write!(f, "{time}")?;
+            for value in row {
+                write!(f, "\t{value}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Trc {
+    pub fn new(name: &str, data_rate: f64, labels: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            data_rate,
+            camera_rate: data_rate,
+            units: "mm".to_string(),
+            labels,
+            time: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn units(mut self, units: &str) -> Self {
+        self.units = units.to_string();
+        self
+    }
+
+    pub fn push(&mut self, time: f64, frame: Vec<Option<TranslationVector>>) {
+        self.time.push(time);
+        self.frames.push(frame);
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(path).ok()?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        // fields are tab separated and missing markers leave them empty
+        let mut lines = text.lines().map(|line| line.split('\t').map(str::trim));
+        let name = lines.next()?.nth(3).unwrap_or_default().to_string();
+        let keys: Vec<&str> = lines.next()?.collect();
+        let values: Vec<&str> = lines.next()?.collect();
+        let field = |key: &str| {
+            let column = keys.iter().position(|k| k.eq_ignore_ascii_case(key))?;
+            values.get(column).copied()
+        };
+        let data_rate: f64 = field("DataRate")?.parse().ok()?;
+        let camera_rate = field("CameraRate")
+            .and_then(|rate| rate.parse().ok())
+            .unwrap_or(data_rate);
+        let units = field("Units").unwrap_or("mm").to_string();
+        let markers: usize = field("NumMarkers")?.parse().ok()?;
+        let labels: Vec<String> = lines
+            .next()?
+            .skip(2)
+            .filter(|label| !label.is_empty())
+            .map(|label| label.to_string())
+            .collect();
+        if labels.len() != markers {
+            return None;
+        }
+        // the row of X1 Y1 Z1 column names
+        let _axes = lines.next()?;
+
+        let mut trc = Trc::new(&name, data_rate, labels).units(&units);
+        trc.camera_rate = camera_rate;
+        let scale = unit_scale(&units);
+        for fields in lines {
+            let fields: Vec<&str> = fields.collect();
+            if fields.iter().all(|field| field.is_empty()) {
+                continue;
+            }
+            let time: f64 = fields.get(1)?.parse().ok()?;
+            let frame = (0..markers)
+                .map(|marker| {
+                    let coordinates: Vec<f64> = (0..3)
+                        .map(|k| fields.get(2 + 3 * marker + k)?.parse().ok())
+                        .collect::<Option<_>>()?;
+                    Some(
+                        TranslationVector::from_array([
+                            coordinates[0],
+                            coordinates[1],
+                            coordinates[2],
+                        ]) * scale,
+                    )
+                })
+                .collect();
+            trc.push(time, frame);
+        }
+        Some(trc)
+    }
+
+    /// Marker measurements per frame in the order of `markers`, matched by name, ready for
+    /// `MarkerIk::track`
+    pub fn marker_frames(&self, markers: &[Marker]) -> Vec<Vec<Option<TranslationVector>>> {
+        let columns: Vec<Option<usize>> = markers
+            .iter()
+            .map(|marker| self.labels.iter().position(|l| *l == marker.name))
+            .collect();
+        self.frames
+            .iter()
+            .map(|frame| {
+                columns
+                    .iter()
+                    .map(|column| column.and_then(|c| frame[c]))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Trc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let markers = self.labels.len();
+        let frames = self.frames.len();
+        writeln!(f, "PathFileType\t4\t(X/Y/Z)\t{}", self.name)?;
+        writeln!(
+            f,
+            "DataRate\tCameraRate\tNumFrames\tNumMarkers\tUnits\tOrigDataRate\tOrigDataStartFrame\tOrigNumFrames"
+        )?;
+        writeln!(
+            f,
+            "{}\t{}\t{frames}\t{markers}\t{}\t{}\t1\t{frames}",
+            self.data_rate, self.camera_rate, self.units, self.data_rate
+        )?;
+        write!(f, "Frame#\tTime")?;
+        for label in &self.labels {
+            write!(f, "\t{label}\t\t")?;
+        }
+        write!(f, "\n\t")?;
+        for marker in 1..=markers {
+            write!(f, "\tX{marker}\tY{marker}\tZ{marker}")?;
+        }
+        writeln!(f, "\n")?;
+        let scale = unit_scale(&self.units);
+        for (i, (time, frame)) in self.time.iter().zip(&self.frames).enumerate() {
+            write!(f, "{}\t{time}", i + 1)?;
+            for position in frame {
+                match position {
+                    Some(position) => {
+                        let [x, y, z] = (*position * (1.0 / scale)).to_array();
+                        write!(f, "\t{x}\t{y}\t{z}")?;
+                    }
+                    None => write!(f, "\t\t\t")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_round_trips_and_reads_old_headers() {
+        let names = ["hip_flexion", "pelvis_tx"];
+        let q = vec![vec![0.5, 0.1], vec![-0.25, 0.2]];
+        let storage =
+            Storage::from_coordinates("walk", &names, &[true, false], &[0.0, 0.01], &q, true);
+        let read = Storage::parse(&storage.to_string()).unwrap();
+        assert_eq!(read, storage);
+        assert!((read.column("hip_flexion").unwrap()[0] - 0.5f64.to_degrees()).abs() < 1e-9);
+
+        // columns are picked by name and missing ones left at zero
+        let coordinates =
+            read.coordinates(&["pelvis_tx", "knee", "hip_flexion"], &[false, true, true]);
+        assert_eq!(coordinates[1][0], 0.2);
+        assert_eq!(coordinates[1][1], 0.0);
+        assert!((coordinates[1][2] + 0.25).abs() < 1e-12);
+
+        let old = "Coordinates\ndatacolumns 2\ndatarows 1\nrange 0 0\nendheader\ntime knee\n0 30\n";
+        let old = Storage::parse(old).unwrap();
+        assert_eq!(old.name, "Coordinates");
+        assert_eq!(old.metadata, [("range".to_string(), "0 0".to_string())]);
+        assert!(!old.in_degrees);
+        assert_eq!(old.rows, [vec![30.0]]);
+        assert!(Storage::parse("endheader\ntime knee\n0 30 5\n").is_none());
+
+        // OpenSim 4 states name columns by path, with the speeds beside the values
+        let states = "states\nendheader\ntime /jointset/knee_r/knee_angle_r/speed \
+                      /jointset/knee_r/knee_angle_r/value\n0 2 -0.5\n";
+        let states = Storage::parse(states).unwrap();
+        assert_eq!(states.column("knee_angle_r").unwrap(), [-0.5]);
+        assert_eq!(states.coordinates(&["knee_angle_r"], &[true]), [vec![-0.5]]);
+    }
+
+    #[test]
+    fn trc_round_trips_with_missing_markers() {
+        let mut trc = Trc::new("static.trc", 100.0, vec!["RASI".into(), "RKNE".into()]);
+        trc.push(
+            0.0,
+            vec![Some(TranslationVector::from_array([0.1, 0.9, 0.05])), None],
+        );
+        trc.push(
+            0.01,
+            vec![
+                Some(TranslationVector::from_array([0.11, 0.9, 0.05])),
+                Some(TranslationVector::from_array([0.2, 0.5, -0.1])),
+            ],
+        );
+        let read = Trc::parse(&trc.to_string()).unwrap();
+        assert_eq!(read.labels, trc.labels);
+        assert_eq!(read.time, trc.time);
+        assert!(read.frames[0][1].is_none());
+        let knee = read.frames[1][1].unwrap();
+        assert!((knee - TranslationVector::from_array([0.2, 0.5, -0.1])).norm() < 1e-12);
+
+        let markers = [Marker::new("RKNE", 0, TranslationVector::new())];
+        assert_eq!(read.marker_frames(&markers)[1][0], Some(knee));
+    }
+}
@@ -0,0 +1,629 @@
+use std::f64::consts::TAU;
+
+use crate::body::BodyKinematics;
+use crate::{Basis, TransformationMatrix, TranslationVector};
+
+const ARC_SAMPLES: usize = 16;
+const CHAIN_NODES: usize = 32;
+const MAX_SWEEPS: usize = 5000;
+const TOLERANCE: f64 = 1e-12;
+
+/// Obstacle a muscle path wraps over, in its own frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapShape {
+    /// infinite cylinder along the z axis
+    Cylinder {
+        radius: f64,
+    },
+    Sphere {
+        radius: f64,
+    },
+    Ellipsoid {
+        radii: TranslationVector,
+    },
+    /// tube of `minor_radius` around a circle of `major_radius` in the xy plane
+    Torus {
+        major_radius: f64,
+        minor_radius: f64,
+    },
+}
+
+/// Half of the obstacle the path may wrap over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Quadrant {
+    All,
+    Positive(Basis),
+    Negative(Basis),
+}
+
+/// Wrapping obstacle fixed on a body, or on the world when `body` is `None`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WrapSurface {
+    pub body: Option<usize>,
+    /// body to surface transform
+    pub offset: TransformationMatrix,
+    pub shape: WrapShape,
+    pub quadrant: Quadrant,
+}
+
+/// Path of a straight segment deflected by a wrap surface
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wrap {
+    pub length: f64,
+    /// world polyline from the segment start over the surface to the segment end
+    pub path: Vec<TranslationVector>,
+}
+
+impl WrapSurface {
+    pub fn new(body: Option<usize>, shape: WrapShape, offset: TransformationMatrix) -> Self {
+        Self {
+            body,
+            offset,
+            shape,
+            quadrant: Quadrant::All,
+        }
+    }
+
+    /// Only wrap over the given half of the surface, going the other way around when the
+    /// shortest wrap would cross the other half. Cylinders and spheres also wrap when the
+    /// segment passes them on the other side without touching, as in OpenSim, so the length
+    /// does not jump as the segment reaches them.
+    pub fn quadrant(mut self, quadrant: Quadrant) -> Self {
+        self.quadrant = quadrant;
+        self
+    }
+
+    /// World to surface transform
+    pub fn pose(&self, kinematics: &[BodyKinematics]) -> TransformationMatrix {
+        match self.body {
+            Some(i) => self.offset * kinematics[i].pose,
+            None => self.offset,
+        }
+    }
+
+    /// Shortest path between world points `start` and `end` around the surface, or `None` when
+    /// the straight segment clears it, on the allowed side for a surface held to one half
+    ///
+    /// Cylinders and spheres are wrapped in closed form. Ellipsoids and tori relax a chain of
+    /// nodes held outside the surface, starting from the sphere solution for ellipsoids and
+    /// from the straight segment for tori, bent over the allowed half when that one lands on
+    /// the other.
+    pub fn wrap(
+        &self,
+        start: TranslationVector,
+        end: TranslationVector,
+        kinematics: &[BodyKinematics],
+    ) -> Option<Wrap> {
+        let pose = self.pose(kinematics);
+        let (p, s) = (pose.transform_point(start), pose.transform_point(end));
+        let (length, path) = match self.shape {
+            WrapShape::Cylinder { radius } => cylinder_wrap(p, s, radius, self.pole())?,
+            WrapShape::Sphere { radius } => sphere_wrap(p, s, radius, self.pole())?,
+            WrapShape::Ellipsoid { radii } => {
+                let scale = |v: TranslationVector, power: i32| {
+                    let (v, r) = (v.to_array(), radii.to_array());
+                    TranslationVector::from_array([
+                        v[0] * r[0].powi(power),
+                        v[1] * r[1].powi(power),
+                        v[2] * r[2].powi(power),
+                    ])
+                };
+                // scaling keeps the signs of the coordinates, and so the allowed half
+                let (_, sphere) = sphere_wrap(scale(p, -1), scale(s, -1), 1.0, self.pole())?;
+                let mut chain = vec![p];
+                chain.extend(
+                    resample(&sphere[1..sphere.len() - 1], CHAIN_NODES).map(|x| scale(x, 1)),
+                );
+                chain.push(s);
+                relax(chain, |x| ellipsoid_projection(x, radii))?
+            }
+            WrapShape::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let project = |x| torus_projection(x, major_radius, minor_radius);
+                let chain = |bulge: TranslationVector| -> Vec<TranslationVector> {
+                    (0..CHAIN_NODES + 2)
+                        .map(|i| {
+                            let f = i as f64 / (CHAIN_NODES + 1) as f64;
+                            p + (s - p) * f + bulge * (f * std::f64::consts::PI).sin()
+                        })
+                        .collect()
+                };
+                let straight = relax(chain(TranslationVector::new()), project)?;
+                match self.quadrant {
+                    Quadrant::Positive(axis) | Quadrant::Negative(axis)
+                        if !self.allows(straight.1[straight.1.len() / 2]) =>
+                    {
+                        let mut bulge = [0.0; 3];
+                        bulge[axis as usize] = 2.0 * (major_radius + minor_radius);
+                        if let Quadrant::Negative(_) = self.quadrant {
+                            bulge[axis as usize] *= -1.0;
+                        }
+                        relax(chain(TranslationVector::from_array(bulge)), project)?
+                    }
+                    _ => straight,
+                }
+            }
+        };
+
+        let to_world = pose.inverse_transform();
+        Some(Wrap {
+            length,
+            path: path.iter().map(|x| to_world.transform_point(*x)).collect(),
+        })
+    }
+
+    // whether a point in surface coordinates lies in the half the path may wrap over
+    fn allows(&self, x: TranslationVector) -> bool {
+        let x = x.to_array();
+        match self.quadrant {
+            Quadrant::All => true,
+            Quadrant::Positive(axis) => x[axis as usize] >= 0.0,
+            Quadrant::Negative(axis) => x[axis as usize] <= 0.0,
+        }
+    }
+
+    // unit direction into the allowed half, in surface coordinates
+    fn pole(&self) -> Option<TranslationVector> {
+        let (axis, sign) = match self.quadrant {
+            Quadrant::All => return None,
+            Quadrant::Positive(axis) => (axis, 1.0),
+            Quadrant::Negative(axis) => (axis, -1.0),
+        };
+        let mut pole = [0.0; 3];
+        pole[axis as usize] = sign;
+        Some(TranslationVector::from_array(pole))
+    }
+}
+
+// first tangent angle and signed sweep of the shortest path from p to s around a circle at the
+// origin, `None` when the segment clears the circle
+//
+// With a `pole`, the circle and the ray from its center away from the pole form one obstacle:
+// the path goes around the pole side whenever the segment crosses either of them.
+fn circle_wrap(
+    p: [f64; 2],
+    s: [f64; 2],
+    radius: f64,
+    pole: Option<[f64; 2]>,
+) -> Option<(f64, f64)> {
+    let (dp, ds) = (p[0].hypot(p[1]), s[0].hypot(s[1]));
+    let d = [s[0] - p[0], s[1] - p[1]];
+    let dd = d[0] * d[0] + d[1] * d[1];
+    if dp <= radius || ds <= radius || dd == 0.0 {
+        return None;
+    }
+    let t = (-(p[0] * d[0] + p[1] * d[1]) / dd).clamp(0.0, 1.0);
+    let touches = (p[0] + t * d[0]).hypot(p[1] + t * d[1]) < radius;
+    let crosses_ray = pole.is_some_and(|n| {
+        // where the segment meets the line of the ray, on the side away from the pole
+        let across = d[0] * n[1] - d[1] * n[0];
+        let t = (p[1] * n[0] - p[0] * n[1]) / across;
+        across != 0.0
+            && (0.0..=1.0).contains(&t)
+            && (p[0] + t * d[0]) * n[0] + (p[1] + t * d[1]) * n[1] < 0.0
+    });
+    if !touches && !crosses_ray {
+        return None;
+    }
+    let (ap, as_) = (p[1].atan2(p[0]), s[1].atan2(s[0]));
+    let (bp, bs) = ((radius / dp).acos(), (radius / ds).acos());
+    let counterclockwise = (as_ - bs - ap - bp).rem_euclid(TAU);
+    let clockwise = (ap - bp - as_ - bs).rem_euclid(TAU);
+    let (mut shortest, mut other) = ((ap + bp, counterclockwise), (ap - bp, -clockwise));
+    if clockwise < counterclockwise {
+        std::mem::swap(&mut shortest, &mut other);
+    }
+    if let Some(n) = pole {
+        let away = (-n[1]).atan2(-n[0]);
+        // including its ends, so an arc shrinking to the point away from the pole still counts
+        let passes = |(angle, sweep): (f64, f64)| {
+            let offset = if sweep >= 0.0 {
+                (away - angle).rem_euclid(TAU)
+            } else {
+                (angle - away).rem_euclid(TAU)
+            };
+            offset <= sweep.abs() + 1e-12 || offset >= TAU - 1e-12
+        };
+        if passes(shortest) && !passes(other) {
+            return Some(other);
+        }
+    }
+    Some(shortest)
+}
+
+// the tangent lines and the helix unroll into one straight line on the cylinder's development
+fn cylinder_wrap(
+    p: TranslationVector,
+    s: TranslationVector,
+    radius: f64,
+    pole: Option<TranslationVector>,
+) -> Option<(f64, Vec<TranslationVector>)> {
+    let (pa, sa) = (p.to_array(), s.to_array());
+    // a half along the axis leaves the cross-section unconstrained
+    let pole = pole
+        .map(|n| n.to_array())
+        .filter(|n| n[2] == 0.0)
+        .map(|n| [n[0], n[1]]);
+    let (angle, sweep) = circle_wrap([pa[0], pa[1]], [sa[0], sa[1]], radius, pole)?;
+    let at = |a: f64| [radius * a.cos(), radius * a.sin()];
+    let (t1, t2) = (at(angle), at(angle + sweep));
+    let before = (pa[0] - t1[0]).hypot(pa[1] - t1[1]);
+    let after = (sa[0] - t2[0]).hypot(sa[1] - t2[1]);
+    let arc = radius * sweep.abs();
+    let planar = before + arc + after;
+    let rise = sa[2] - pa[2];
+
+    let mut path = vec![p];
+    path.extend((0..=ARC_SAMPLES).map(|k| {
+        let f = k as f64 / ARC_SAMPLES as f64;
+        let [x, y] = at(angle + f * sweep);
+        TranslationVector::from_array([x, y, pa[2] + rise * (before + f * arc) / planar])
+    }));
+    path.push(s);
+    Some((planar.hypot(rise), path))
+}
+
+// the shortest path stays in the plane through the center and both end points
+fn sphere_wrap(
+    p: TranslationVector,
+    s: TranslationVector,
+    radius: f64,
+    pole: Option<TranslationVector>,
+) -> Option<(f64, Vec<TranslationVector>)> {
+    let e1 = p.normalize();
+    let mut normal = p.cross(s);
+    if normal.norm() <= 1e-12 * p.norm() * s.norm() {
+        // collinear with the center, any plane through the line will do, preferably one
+        // through the pole
+        let [_, t1, _] = crate::contact::tangent_basis(e1);
+        normal = pole
+            .map(|n| e1.cross(n))
+            .filter(|n| n.norm() > 1e-12)
+            .unwrap_or(t1);
+    }
+    let e2 = normal.normalize().cross(e1);
+    // the pole as seen in the plane of the wrap, if the plane is not square to it
+    let pole = pole
+        .map(|n| [n.dot(e1), n.dot(e2)])
+        .filter(|n| n[0].hypot(n[1]) > 1e-12);
+    let (angle, sweep) = circle_wrap([p.norm(), 0.0], [s.dot(e1), s.dot(e2)], radius, pole)?;
+    let at = |a: f64| (e1 * a.cos() + e2 * a.sin()) * radius;
+    let (t1, t2) = (at(angle), at(angle + sweep));
+
+    let mut path = vec![p];
+    path.extend((0..=ARC_SAMPLES).map(|k| at(angle + k as f64 / ARC_SAMPLES as f64 * sweep)));
+    path.push(s);
+    Some((
+        (t1 - p).norm() + radius * sweep.abs() + (s - t2).norm(),
+        path,
+    ))
+}
+
+// `count` points evenly spaced along a polyline
+fn resample(
+    points: &[TranslationVector],
+    count: usize,
+) -> impl Iterator<Item = TranslationVector> + '_ {
+    let lengths: Vec<f64> = points.windows(2).map(|w| (w[1] - w[0]).norm()).collect();
+    let total: f64 = lengths.iter().sum();
+    (0..count).map(move |k| {
+        let mut remaining = total * k as f64 / (count - 1) as f64;
+        for (i, length) in lengths.iter().enumerate() {
+            if remaining <= *length && *length > 0.0 {
+                return points[i] + (points[i + 1] - points[i]) * (remaining / length);
+            }
+            remaining -= length;
+        }
+        points[points.len() - 1]
+    })
+}
+
+// pull the chain taut with its end points fixed, keeping every node outside the surface through
+// `project`, which returns the nearest surface point of points inside
+fn relax(
+    mut chain: Vec<TranslationVector>,
+    project: impl Fn(TranslationVector) -> Option<TranslationVector>,
+) -> Option<(f64, Vec<TranslationVector>)> {
+    let n = chain.len();
+    let scale = (chain[n - 1] - chain[0]).norm();
+    let mut touching = false;
+    for _ in 0..MAX_SWEEPS {
+        let mut change = 0.0_f64;
+        touching = false;
+        for i in 1..n - 1 {
+            let mut x = (chain[i - 1] + chain[i + 1]) * 0.5;
+            if let Some(surface) = project(x) {
+                x = surface;
+                touching = true;
+            }
+            change = change.max((x - chain[i]).norm());
+            chain[i] = x;
+        }
+        if change <= TOLERANCE * scale {
+            break;
+        }
+    }
+    if !touching {
+        return None;
+    }
+    let length = chain.windows(2).map(|w| (w[1] - w[0]).norm()).sum();
+    Some((length, chain))
+}
+
+// nearest point on the ellipsoid x^T diag(r)^-2 x = 1 of a point inside it, from the Lagrange
+// condition x_i = p_i r_i^2 / (r_i^2 + t)
+fn ellipsoid_projection(
+    point: TranslationVector,
+    radii: TranslationVector,
+) -> Option<TranslationVector> {
+    let (p, r) = (point.to_array(), radii.to_array());
+    let level = |t: f64| {
+        (0..3)
+            .map(|i| (p[i] * r[i] / (r[i] * r[i] + t)).powi(2))
+            .sum::<f64>()
+    };
+    if level(0.0) >= 1.0 {
+        return None;
+    }
+    let smallest = r.iter().fold(f64::INFINITY, |m, x| m.min(x * x));
+    let (mut low, mut high) = (-smallest * (1.0 - 1e-12), 0.0);
+    if level(low) < 1.0 {
+        // the point lies in the plane of the shortest axis, fall back to a radial projection
+        let scale = level(0.0).sqrt();
+        return Some(point * (1.0 / scale.max(1e-300)));
+    }
+    for _ in 0..100 {
+        let middle = 0.5 * (low + high);
+        if level(middle) > 1.0 {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let t = 0.5 * (low + high);
+    Some(TranslationVector::from_array([
+        p[0] * r[0] * r[0] / (r[0] * r[0] + t),
+        p[1] * r[1] * r[1] / (r[1] * r[1] + t),
+        p[2] * r[2] * r[2] / (r[2] * r[2] + t),
+    ]))
+}
+
+fn torus_projection(
+    point: TranslationVector,
+    major_radius: f64,
+    minor_radius: f64,
+) -> Option<TranslationVector> {
+    let p = point.to_array();
+    let rho = p[0].hypot(p[1]);
+    let ring = if rho > 0.0 {
+        TranslationVector::from_array([major_radius * p[0] / rho, major_radius * p[1] / rho, 0.0])
+    } else {
+        TranslationVector::from_array([major_radius, 0.0, 0.0])
+    };
+    let offset = point - ring;
+    if offset.norm() >= minor_radius {
+        return None;
+    }
+    let direction = if offset.norm() > 0.0 {
+        offset.normalize()
+    } else {
+        TranslationVector::from_array([0.0, 0.0, 1.0])
+    };
+    Some(ring + direction * minor_radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world(x: f64, y: f64, z: f64) -> TranslationVector {
+        TranslationVector::from_array([x, y, z])
+    }
+
+    #[test]
+    fn cylinder_wrap_is_continuous_at_contact() {
+        let cylinder = WrapSurface::new(
+            None,
+            WrapShape::Cylinder { radius: 0.1 },
+            TransformationMatrix::identity(),
+        );
+        let start = world(-0.3, 0.0, 0.0);
+        // the segment touches the cylinder at y = 0.1
+        let lengths: Vec<f64> = [0.1 + 1e-6, 0.1 - 1e-6]
+            .iter()
+            .map(|y| {
+                let (a, b) = (world(-0.3, *y, 0.0), world(0.3, *y, 0.2));
+                cylinder
+                    .wrap(a, b, &[])
+                    .map_or((b - a).norm(), |wrap| wrap.length)
+            })
+            .collect();
+        assert!(cylinder
+            .wrap(
+                world(-0.3, 0.1 + 1e-6, 0.0),
+                world(0.3, 0.1 + 1e-6, 0.2),
+                &[]
+            )
+            .is_none());
+        assert!((lengths[0] - lengths[1]).abs() < 1e-5);
+
+        // half a turn over the top, unrolled with the rise
+        let wrap = cylinder.wrap(start, world(0.3, 0.0, 0.0), &[]).unwrap();
+        let tangent = (0.3_f64.powi(2) - 0.01).sqrt();
+        let angle = std::f64::consts::PI - 2.0 * (0.1_f64 / 0.3).acos();
+        assert!((wrap.length - (2.0 * tangent + 0.1 * angle)).abs() < 1e-12);
+
+        // held to the bottom half, the path goes the long way under the cylinder
+        let (a, b) = (world(-0.3, 0.05, 0.0), world(0.3, 0.05, 0.0));
+        let over = cylinder.wrap(a, b, &[]).unwrap();
+        let below = cylinder.quadrant(Quadrant::Negative(Basis::Y));
+        let under = below.wrap(a, b, &[]).unwrap();
+        assert!(under.path[under.path.len() / 2].to_array()[1] < -0.09);
+        assert!(under.length > over.length);
+    }
+
+    #[test]
+    fn quadrant_switches_direction_continuously() {
+        let cylinder = WrapSurface::new(
+            None,
+            WrapShape::Cylinder { radius: 0.1 },
+            TransformationMatrix::identity(),
+        )
+        .quadrant(Quadrant::Positive(Basis::Y));
+        let length = |y: f64| {
+            cylinder
+                .wrap(world(-0.3, y, 0.0), world(0.3, y, 0.1), &[])
+                .unwrap()
+                .length
+        };
+        // as the ends pass below the axis the shortest wrap turns to the bottom, and the
+        // constrained one keeps going over the top with no jump in length or its slope
+        let h = 1e-6;
+        assert!((length(h) - length(-h)).abs() < 1e-5);
+        let slope = |y: f64| (length(y + h) - length(y - h)) / (2.0 * h);
+        let across = slope(1e-4) - slope(-1e-4);
+        let beside = slope(3e-4) - slope(1e-4);
+        assert!((across - beside).abs() < 1e-5);
+        let wrap = cylinder
+            .wrap(world(-0.3, -0.02, 0.0), world(0.3, -0.02, 0.1), &[])
+            .unwrap();
+        assert!(wrap.path[wrap.path.len() / 2].to_array()[1] > 0.09);
+    }
+
+    #[test]
+    fn quadrant_wrap_engages_continuously() {
+        // held to the top, a segment sweeping up from well below has to go over the cylinder
+        // or sphere from the moment it passes under it until it clears the top
+        for shape in [
+            WrapShape::Cylinder { radius: 0.1 },
+            WrapShape::Sphere { radius: 0.1 },
+        ] {
+            let surface = WrapSurface::new(None, shape, TransformationMatrix::identity())
+                .quadrant(Quadrant::Positive(Basis::Y));
+            let length = |y: f64| {
+                let (a, b) = (world(-0.3, y, 0.0), world(0.3, y, 0.0));
+                surface
+                    .wrap(a, b, &[])
+                    .map_or((b - a).norm(), |wrap| wrap.length)
+            };
+            assert!((length(-0.0999) - length(-0.1001)).abs() < 1e-3);
+            assert!(length(-0.2) > 0.7);
+            let mut previous = length(-0.3);
+            for k in 1..=500 {
+                let next = length(-0.3 + 1e-3 * k as f64);
+                assert!((next - previous).abs() < 2e-3);
+                previous = next;
+            }
+            assert_eq!(length(0.2), 0.6);
+        }
+    }
+
+    #[test]
+    fn ellipsoid_with_equal_radii_matches_sphere() {
+        let (a, b) = (world(-0.3, 0.05, 0.02), world(0.25, 0.0, -0.04));
+        let sphere = WrapSurface::new(
+            None,
+            WrapShape::Sphere { radius: 0.1 },
+            TransformationMatrix::identity(),
+        );
+        let ellipsoid = WrapSurface::new(
+            None,
+            WrapShape::Ellipsoid {
+                radii: world(0.1, 0.1, 0.1),
+            },
+            TransformationMatrix::identity(),
+        );
+        let exact = sphere.wrap(a, b, &[]).unwrap().length;
+        let relaxed = ellipsoid.wrap(a, b, &[]).unwrap().length;
+        assert!((relaxed - exact).abs() < 1e-3 * exact);
+        assert!(relaxed <= exact + 1e-12);
+    }
+
+    #[test]
+    fn ellipsoid_wrap_is_continuous_and_matches_its_moment_arm() {
+        let ellipsoid = WrapSurface::new(
+            None,
+            WrapShape::Ellipsoid {
+                radii: world(0.1, 0.06, 0.08),
+            },
+            TransformationMatrix::identity(),
+        );
+        let length = |a: TranslationVector, b: TranslationVector| {
+            ellipsoid
+                .wrap(a, b, &[])
+                .map_or((b - a).norm(), |wrap| wrap.length)
+        };
+        // the segment touches the ellipsoid at y = 0.06
+        let touching: Vec<f64> = [0.06 + 1e-6, 0.06 - 1e-6]
+            .iter()
+            .map(|y| length(world(-0.3, *y, 0.0), world(0.3, *y, 0.0)))
+            .collect();
+        assert!((touching[0] - touching[1]).abs() < 1e-5);
+
+        // turning the end about z, the length changes at the lever arm of the last segment
+        let start = world(-0.3, 0.02, 0.0);
+        let end = |q: f64| world(0.3 * q.cos(), 0.3 * q.sin(), 0.0);
+        let q = 0.1;
+        let h = 1e-5;
+        let slope = (length(start, end(q + h)) - length(start, end(q - h))) / (2.0 * h);
+        let path = ellipsoid.wrap(start, end(q), &[]).unwrap().path;
+        let last = (path[path.len() - 1] - path[path.len() - 2]).normalize();
+        let arm = end(q).cross(last).to_array()[2];
+        assert!(arm.abs() > 0.01);
+        assert!((slope - arm).abs() < 1e-3 * arm.abs());
+    }
+
+    #[test]
+    fn torus_wraps_over_its_tube() {
+        let torus = WrapSurface::new(
+            None,
+            WrapShape::Torus {
+                major_radius: 0.2,
+                minor_radius: 0.05,
+            },
+            TransformationMatrix::identity(),
+        );
+        // a vertical segment through the tube goes over its outer side, in the plane y = 0
+        // where the tube is a circle of radius 0.05 about x = 0.2
+        let (a, b) = (world(0.22, 0.0, -0.3), world(0.22, 0.0, 0.3));
+        let relaxed = torus.wrap(a, b, &[]).unwrap();
+        let circle = WrapSurface::new(
+            None,
+            WrapShape::Cylinder { radius: 0.05 },
+            TransformationMatrix::identity(),
+        );
+        let exact = circle
+            .wrap(world(0.02, -0.3, 0.0), world(0.02, 0.3, 0.0), &[])
+            .unwrap()
+            .length;
+        assert!((relaxed.length - exact).abs() < 1e-3 * exact);
+        assert!(relaxed.length <= exact + 1e-12);
+        assert!(relaxed.path.iter().all(|x| x.to_array()[0] >= 0.22 - 1e-12));
+
+        // it leaves the tube with no jump in length, and segments through the hole miss it
+        let length = |x: f64| {
+            let (a, b) = (world(x, 0.0, -0.3), world(x, 0.0, 0.3));
+            torus
+                .wrap(a, b, &[])
+                .map_or((b - a).norm(), |wrap| wrap.length)
+        };
+        assert!((length(0.25 + 1e-6) - length(0.25 - 1e-6)).abs() < 1e-5);
+        assert!(torus
+            .wrap(world(0.1, 0.0, -0.3), world(0.1, 0.0, 0.3), &[])
+            .is_none());
+
+        // a segment from the hole out through the tube goes over its top, and under its bottom
+        // when held to the lower half
+        let (a, b) = (world(0.05, 0.0, 0.02), world(0.4, 0.0, 0.02));
+        let over = torus.wrap(a, b, &[]).unwrap();
+        let under = torus
+            .quadrant(Quadrant::Negative(Basis::Z))
+            .wrap(a, b, &[])
+            .unwrap();
+        assert!(over.path[over.path.len() / 2].to_array()[2] > 0.0);
+        assert!(under.path[under.path.len() / 2].to_array()[2] < 0.0);
+        assert!(under.length > over.length);
+    }
+}